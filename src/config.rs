@@ -1,10 +1,41 @@
 use std::env;
 
+use validator::Validate;
+
+use crate::models::{CaptureMethod, DunningFinalAction, DunningPolicy, Merchant, OrderPolicy, PostShipmentCancellation};
+
 #[derive(Clone, Debug)]
 pub struct Config {
     pub stripe_secret_key: String,
     pub stripe_webhook_secret: String,
     pub base_url: String,
+    
+    // Paniers abandonnés
+    pub abandoned_cart_after_minutes: i64,
+    pub cart_expiry_hours: i64,
+    pub cart_sweep_interval_secs: u64,
+    
+    // Paiements: capture manuelle et surveillance des autorisations
+    pub capture_method: CaptureMethod,
+    pub authorization_expiry_warning_hours: i64,
+    pub authorization_check_interval_secs: u64,
+    
+    // Politique par défaut d'annulation / modification des commandes
+    pub order_policy: OrderPolicy,
+    
+    // Relances des paiements d'abonnement échoués
+    pub dunning_policy: DunningPolicy,
+    pub dunning_check_interval_secs: u64,
+    
+    // Facturation à l'usage: remontée des consommations en attente vers Stripe
+    pub usage_report_interval_secs: u64,
+    
+    // Vérification des droits d'accès: durée maximale de mise en cache des réponses
+    pub entitlement_cache_secs: i64,
+    
+    // Factures
+    pub merchant: Merchant,
+    pub vat_rate_percent: i64,
 }
 
 impl Config {
@@ -16,6 +47,118 @@ impl Config {
                 .unwrap_or_else(|_| String::from("")),
             base_url: env::var("BASE_URL")
                 .unwrap_or_else(|_| String::from("http://localhost:3000")),
+            abandoned_cart_after_minutes: env::var("ABANDONED_CART_AFTER_MINUTES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(60),
+            cart_expiry_hours: env::var("CART_EXPIRY_HOURS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(72),
+            cart_sweep_interval_secs: env::var("CART_SWEEP_INTERVAL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(300),
+            capture_method: match env::var("CAPTURE_METHOD").as_deref() {
+                Ok("manual") => CaptureMethod::Manual,
+                _ => CaptureMethod::Automatic,
+            },
+            authorization_expiry_warning_hours: env::var("AUTHORIZATION_EXPIRY_WARNING_HOURS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(24),
+            authorization_check_interval_secs: env::var("AUTHORIZATION_CHECK_INTERVAL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(3600),
+            order_policy: order_policy_from_env(),
+            dunning_policy: dunning_policy_from_env(),
+            dunning_check_interval_secs: env::var("DUNNING_CHECK_INTERVAL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(900),
+            usage_report_interval_secs: env::var("USAGE_REPORT_INTERVAL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(300),
+            entitlement_cache_secs: env::var("ENTITLEMENT_CACHE_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(60),
+            merchant: Merchant {
+                name: env::var("MERCHANT_NAME")
+                    .unwrap_or_else(|_| String::from("RustStripe Casquettes")),
+                address: env::var("MERCHANT_ADDRESS")
+                    .unwrap_or_else(|_| String::from("1 rue de la Paix, 75002 Paris, France")),
+                vat_number: env::var("MERCHANT_VAT_NUMBER")
+                    .unwrap_or_else(|_| String::from("FR00123456789")),
+                email: env::var("MERCHANT_EMAIL")
+                    .unwrap_or_else(|_| String::from("contact@example.com")),
+            },
+            vat_rate_percent: env::var("VAT_RATE_PERCENT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(20),
         })
     }
 }
+
+// Une politique hors bornes (mêmes règles que les surcharges admin) est ignorée.
+fn order_policy_from_env() -> OrderPolicy {
+    let defaults = OrderPolicy::default();
+    let number = |name: &str, default: i64| env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default);
+    
+    let policy = OrderPolicy {
+        cancel_window_hours: number("ORDER_CANCEL_WINDOW_HOURS", defaults.cancel_window_hours),
+        modify_window_hours: number("ORDER_MODIFY_WINDOW_HOURS", defaults.modify_window_hours),
+        cancel_after_shipment: match env::var("CANCEL_AFTER_SHIPMENT").as_deref() {
+            Ok("refuse") => PostShipmentCancellation::Refuse,
+            Ok("return") => PostShipmentCancellation::Return,
+            _ => defaults.cancel_after_shipment,
+        },
+        return_window_days: number("RETURN_WINDOW_DAYS", defaults.return_window_days),
+    };
+    
+    match policy.validate() {
+        Ok(()) => policy,
+        Err(e) => {
+            tracing::warn!("Politique de commande invalide ({}), valeurs par défaut utilisées", e);
+            defaults
+        }
+    }
+}
+
+// Les modèles de notification gardent leurs valeurs par défaut (modifiables via l'API admin).
+// Une politique hors bornes (mêmes règles que l'API admin) est ignorée.
+fn dunning_policy_from_env() -> DunningPolicy {
+    let defaults = DunningPolicy::default();
+    
+    let policy = DunningPolicy {
+        retry_schedule_days: env::var("DUNNING_RETRY_DAYS")
+            .ok()
+            .and_then(|v| v.split(',').map(|d| d.trim().parse().ok()).collect())
+            .unwrap_or_else(|| defaults.retry_schedule_days.clone()),
+        grace_period_days: env::var("DUNNING_GRACE_DAYS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(defaults.grace_period_days),
+        final_action: match env::var("DUNNING_FINAL_ACTION").as_deref() {
+            Ok("cancel") => DunningFinalAction::Cancel,
+            Ok("pause") => DunningFinalAction::Pause,
+            Ok("unpaid") => DunningFinalAction::MarkUnpaid,
+            _ => defaults.final_action,
+        },
+        ..defaults.clone()
+    };
+    
+    match policy.validate() {
+        Ok(()) => policy,
+        Err(e) => {
+            tracing::warn!("Politique de relance invalide ({}), valeurs par défaut utilisées", e);
+            defaults
+        }
+    }
+}
@@ -0,0 +1,192 @@
+// Tâches de fond (maintenance périodique de l'état en mémoire)
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::models::UsageReportStatus;
+use crate::services::{captures, dunning, stripe_service, usage};
+use crate::state::AppState;
+
+/// Résultat d'un passage de la tâche des paniers abandonnés
+#[derive(Debug, Default)]
+pub struct CartSweepReport {
+    pub flagged: Vec<String>, // user_id des paniers marqués abandonnés
+    pub purged: Vec<String>,  // user_id des paniers supprimés (expirés)
+}
+
+/// Marquer les paniers inactifs, envoyer les relances et purger les paniers expirés
+pub fn sweep_carts(state: &AppState, now: DateTime<Utc>) -> CartSweepReport {
+    let idle = Duration::minutes(state.config.abandoned_cart_after_minutes);
+    let expiry = Duration::hours(state.config.cart_expiry_hours);
+    let mut report = CartSweepReport::default();
+
+    // Purge dure: au-delà de l'expiration le panier disparaît
+    state.carts.retain(|user_id, cart| {
+        let expired = now - cart.updated_at >= expiry;
+        if expired {
+            report.purged.push(user_id.clone());
+        }
+        !expired
+    });
+
+    // Relance unique par période d'inactivité
+    for mut cart in state.carts.iter_mut() {
+        if cart.items.is_empty() || cart.abandoned_at.is_some() {
+            continue;
+        }
+
+        if now - cart.updated_at >= idle {
+            cart.abandoned_at = Some(now);
+            report.flagged.push(cart.user_id.clone());
+
+            tracing::info!("🛒 Panier abandonné: user {} ({} articles)",
+                          cart.user_id, cart.items.len());
+            println!("\n NOTIFICATION CLIENT: Vous avez oublié des articles dans votre panier, {}!",
+                    cart.user_id);
+        }
+    }
+
+    report
+}
+
+/// Lancer la tâche périodique des paniers abandonnés
+pub fn spawn_cart_sweeper(state: AppState) {
+    let period = std::time::Duration::from_secs(state.config.cart_sweep_interval_secs);
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            let report = sweep_carts(&state, Utc::now());
+            if !report.flagged.is_empty() || !report.purged.is_empty() {
+                tracing::info!("🧹 Paniers: {} abandonnés, {} purgés",
+                              report.flagged.len(), report.purged.len());
+            }
+        }
+    });
+}
+
+/// Signaler les autorisations proches de l'expiration (une alerte par commande)
+///
+/// Retourne les commandes nouvellement signalées.
+pub fn check_authorizations(state: &AppState, now: DateTime<Utc>) -> Vec<String> {
+    let warning_hours = state.config.authorization_expiry_warning_hours;
+    let mut flagged = Vec::new();
+    
+    for mut order in state.orders.iter_mut() {
+        if captures::flag_if_expiring(&mut order, now, warning_hours) {
+            let expires_at = order.authorization.as_ref().map(|a| a.expires_at);
+            tracing::warn!("⏰ Autorisation de la commande {} expire le {:?}: capturer ou annuler",
+                          order.id, expires_at);
+            flagged.push(order.id.clone());
+        }
+    }
+    
+    flagged
+}
+
+/// Lancer la surveillance périodique des autorisations non capturées
+pub fn spawn_authorization_monitor(state: AppState) {
+    let period = std::time::Duration::from_secs(state.config.authorization_check_interval_secs);
+    
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            check_authorizations(&state, Utc::now());
+        }
+    });
+}
+
+/// Relances arrivées à échéance: (abonnement, facture Stripe) à représenter
+///
+/// L'échéance est consommée: une relance n'est lancée qu'une fois, le
+/// webhook de résultat programme la suivante.
+pub fn due_dunning_retries(state: &AppState, now: DateTime<Utc>) -> Vec<(String, String)> {
+    let mut due = Vec::new();
+    
+    for mut sub in state.subscriptions.iter_mut() {
+        let sub_id = sub.id.clone();
+        let Some(dunning) = sub.dunning.as_mut() else {
+            continue;
+        };
+        if dunning.next_retry_at.is_some_and(|at| at <= now) {
+            dunning.next_retry_at = None;
+            due.push((sub_id, dunning.stripe_invoice_id.clone()));
+        }
+    }
+    
+    due
+}
+
+/// Reprogrammer une relance dont la tentative n'a pas atteint la banque
+///
+/// Un refus de carte est notifié par le webhook `invoice.payment_failed`, qui
+/// programme la suite; toute autre erreur laisserait la facture sans relance.
+pub fn reschedule_dunning_retry(state: &AppState, sub_id: &str, invoice_id: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let mut sub = state.subscriptions.get_mut(sub_id)?;
+    let dunning = sub.dunning.as_mut()
+        .filter(|d| d.stripe_invoice_id == invoice_id && d.next_retry_at.is_none())?;
+    
+    let next_retry_at = now + dunning::technical_retry_delay(dunning.technical_failures);
+    dunning.technical_failures += 1;
+    dunning.next_retry_at = Some(next_retry_at);
+    Some(next_retry_at)
+}
+
+/// Lancer les nouvelles tentatives de paiement selon la politique de relance
+pub fn spawn_dunning_retrier(state: AppState) {
+    let period = std::time::Duration::from_secs(state.config.dunning_check_interval_secs);
+    
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            for (sub_id, invoice_id) in due_dunning_retries(&state, Utc::now()) {
+                tracing::info!("🔁 Nouvelle tentative de paiement: abonnement {} (facture {})", sub_id, invoice_id);
+                match stripe_service::pay_invoice(&state.stripe_client, &invoice_id).await {
+                    Ok(_) => {}
+                    // Un refus est aussi notifié par invoice.payment_failed, traité par le webhook
+                    Err(stripe::StripeError::Stripe(e)) if e.error_type == stripe::ErrorType::Card => {
+                        tracing::warn!("Paiement refusé pour la facture {}: {:?}", invoice_id, e.message);
+                    }
+                    Err(e) => {
+                        let next = reschedule_dunning_retry(&state, &sub_id, &invoice_id, Utc::now());
+                        tracing::warn!("Tentative non envoyée pour la facture {} ({}), reprogrammée: {:?}", invoice_id, e, next);
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Abonnements dont des consommations n'ont pas encore été remontées à Stripe
+///
+/// Un lot resté en cours d'envoi après un échec est repris tel quel.
+pub fn subscriptions_with_pending_usage(state: &AppState) -> Vec<String> {
+    state.usage_events.iter()
+        .filter(|entry| entry.value().iter().any(|event| matches!(
+            event.report_status,
+            UsageReportStatus::Pending | UsageReportStatus::Reporting
+        )))
+        .map(|entry| entry.key().clone())
+        .collect()
+}
+
+/// Remonter périodiquement les consommations dont l'envoi immédiat a échoué
+pub fn spawn_usage_reporter(state: AppState) {
+    let period = std::time::Duration::from_secs(state.config.usage_report_interval_secs);
+    
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            for sub_id in subscriptions_with_pending_usage(&state) {
+                match usage::report_pending(&state, &sub_id).await {
+                    Ok(Some(batch)) => tracing::info!("📈 Consommation remontée: abonnement {} (+{})", sub_id, batch.quantity),
+                    Ok(None) => {}
+                    Err(e) => tracing::warn!("Remontée de consommation échouée pour l'abonnement {}: {}", sub_id, e),
+                }
+            }
+        }
+    });
+}
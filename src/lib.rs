@@ -1,6 +1,7 @@
 // Library crate pour exposer les modules aux tests
 
 pub mod config;
+pub mod jobs;
 pub mod models;
 pub mod routes;
 pub mod services;
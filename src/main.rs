@@ -4,16 +4,11 @@ use axum::{
 };
 use std::net::SocketAddr;
 use tower_http::cors::CorsLayer;
-use tracing_subscriber;
 
-mod config;
-mod models;
-mod routes;
-mod services;
-mod state;
-
-use config::Config;
-use state::AppState;
+use ruststripe::config::Config;
+use ruststripe::jobs;
+use ruststripe::routes;
+use ruststripe::state::AppState;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -31,6 +26,12 @@ async fn main() -> anyhow::Result<()> {
 
     // Créer l'état partagé de l'application
     let state = AppState::new(config);
+    
+    // Tâches de fond
+    jobs::spawn_cart_sweeper(state.clone());
+    jobs::spawn_authorization_monitor(state.clone());
+    jobs::spawn_dunning_retrier(state.clone());
+    jobs::spawn_usage_reporter(state.clone());
 
     // Créer le routeur
     let app = Router::new()
@@ -38,19 +39,59 @@ async fn main() -> anyhow::Result<()> {
         .route("/", get(|| async { "RustStripe API - Exercices Stripe" }))
         .route("/health", get(|| async { "OK" }))
         
+        // Catalogue produits
+        .route("/api/products", get(routes::products::list_products))
+        .route("/api/admin/products", get(routes::products::list_all_products).post(routes::products::create_product))
+        .route("/api/admin/products/:product_id/update", post(routes::products::update_product))
+        .route("/api/admin/products/:product_id/archive", post(routes::products::archive_product))
+        
         // EXERCICE 1: Gestion de panier et paiement
         .route("/api/cart/add", post(routes::cart::add_to_cart))
         .route("/api/cart/view", get(routes::cart::view_cart))
+        .route("/api/cart/shipping", post(routes::cart::set_shipping))
         .route("/api/cart/checkout", post(routes::cart::checkout))
+        .route("/api/shipping/methods", get(routes::cart::list_shipping_methods))
         .route("/api/orders/:order_id", get(routes::cart::get_order))
+        .route("/api/orders/:order_id/timeline", get(routes::cart::get_order_timeline))
         .route("/api/orders/:order_id/cancel", post(routes::cart::cancel_order))
+        .route("/api/orders/:order_id/refund", post(routes::refunds::refund_order))
+        .route("/api/orders/:order_id/invoice", get(routes::invoices::get_invoice))
+        .route("/api/orders/:order_id/receipt", get(routes::invoices::get_receipt))
+        .route("/api/orders/:order_id/capture", post(routes::captures::capture_order))
+        .route("/api/orders/:order_id/policy", get(routes::policies::get_order_policy))
         .route("/api/orders/:order_id/update", post(routes::cart::update_order))
         .route("/api/orders", get(routes::cart::list_orders))
+        .route("/api/admin/orders", get(routes::cart::list_all_orders))
+        .route("/api/admin/orders/authorizations", get(routes::captures::list_authorizations))
+        .route("/api/admin/orders/returns", get(routes::fulfillment::list_return_requests))
+        .route("/api/admin/exports/accounting", get(routes::exports::export_accounting))
+        .route("/api/admin/carts/abandoned", get(routes::cart::list_abandoned_carts))
+        .route("/api/admin/dunning-policy", get(routes::subscriptions::get_dunning_policy).post(routes::subscriptions::set_dunning_policy))
+        .route("/api/admin/policies", get(routes::policies::get_policies))
+        .route("/api/admin/policies/tiers/:tier", post(routes::policies::set_tier_policy))
+        .route("/api/admin/policies/products/:product_id", post(routes::policies::set_product_policy))
+        .route("/api/admin/customers/:user_id/tier", post(routes::policies::set_customer_tier))
+        
+        // Logistique
+        .route("/api/logistics/orders", get(routes::cart::list_all_orders))
+        .route("/api/logistics/orders/:order_id/fulfillment", post(routes::fulfillment::advance_fulfillment))
         
         // EXERCICE 2: Abonnements récurrents
+        .route("/api/subscriptions", get(routes::subscriptions::list_subscriptions))
         .route("/api/subscriptions/create", post(routes::subscriptions::create_subscription))
         .route("/api/subscriptions/:sub_id", get(routes::subscriptions::get_subscription))
         .route("/api/subscriptions/:sub_id/cancel", post(routes::subscriptions::cancel_subscription))
+        .route("/api/subscriptions/:sub_id/cancel/refund", post(routes::subscriptions::retry_cancellation_refund))
+        .route("/api/subscriptions/:sub_id/resume", post(routes::subscriptions::resume_subscription))
+        .route("/api/subscriptions/:sub_id/pause", post(routes::subscriptions::pause_subscription))
+        .route("/api/subscriptions/:sub_id/unpause", post(routes::subscriptions::unpause_subscription))
+        .route("/api/subscriptions/:sub_id/refresh", post(routes::subscriptions::refresh_subscription))
+        .route("/api/subscriptions/:sub_id/change-plan", post(routes::subscriptions::change_plan))
+        .route("/api/subscriptions/:sub_id/change-interval", post(routes::subscriptions::change_interval).delete(routes::subscriptions::cancel_interval_change))
+        .route("/api/subscriptions/:sub_id/usage", get(routes::subscriptions::get_usage).post(routes::subscriptions::report_usage))
+        
+        // Droits d'accès (consultés par les autres services)
+        .route("/api/entitlements/check", get(routes::entitlements::check_access))
         
         // EXERCICE 3: Moyens de paiement
         .route("/api/payment-methods/setup", post(routes::payment_methods::setup_payment_method))
@@ -1,6 +1,7 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use uuid::Uuid;
+use std::collections::BTreeMap;
+use validator::Validate;
 
 // ========== EXERCICE 1: Gestion de panier ==========
 
@@ -11,11 +12,68 @@ pub struct Product {
     pub price: i64, // En centimes
     pub stock: i32,
     pub description: String,
+    #[serde(default)]
+    pub weight_grams: i32, // Poids unitaire pour le calcul des frais de port
+    #[serde(default)]
+    pub archived: bool, // Retiré du catalogue (reste lisible pour l'historique)
+    #[serde(default)]
+    pub variants: Vec<ProductVariant>, // Si non vide, le stock est géré par variante
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Product {
+    /// Retrouver une variante par son SKU
+    pub fn variant(&self, sku: &str) -> Option<&ProductVariant> {
+        self.variants.iter().find(|v| v.sku == sku)
+    }
+    
+    /// Stock disponible tous SKU confondus
+    pub fn total_stock(&self) -> i32 {
+        if self.variants.is_empty() {
+            self.stock
+        } else {
+            self.variants.iter().map(|v| v.stock).sum()
+        }
+    }
+    
+    /// Prix réellement payés: un par variante, ou le prix du produit sans variante
+    pub fn unit_prices(&self) -> Vec<i64> {
+        if self.variants.is_empty() {
+            vec![self.price]
+        } else {
+            self.variants.iter().map(|v| v.price.unwrap_or(self.price)).collect()
+        }
+    }
+}
+
+/// Déclinaison d'un produit (taille, couleur) avec son propre stock
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+pub struct ProductVariant {
+    #[validate(custom(function = "validate_not_blank", message = "Le SKU est requis"))]
+    pub sku: String,
+    pub size: Option<String>,
+    pub color: Option<String>,
+    #[validate(range(min = 0, message = "Le stock ne peut pas être négatif"))]
+    pub stock: i32,
+    #[validate(range(min = 0, message = "Le prix ne peut pas être négatif"))]
+    pub price: Option<i64>, // Remplace le prix du produit si renseigné
+}
+
+// Un identifiant ou un nom fait uniquement d'espaces est refusé comme un champ vide
+fn validate_not_blank(value: &str) -> Result<(), validator::ValidationError> {
+    if value.trim().is_empty() {
+        Err(validator::ValidationError::new("blank"))
+    } else {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
 pub struct CartItem {
+    #[validate(length(min = 1, message = "Le produit est requis"))]
     pub product_id: String,
+    #[serde(default)]
+    pub variant_sku: Option<String>,
+    #[validate(range(min = 1, max = 1000, message = "La quantité doit être comprise entre 1 et 1000"))]
     pub quantity: i32,
 }
 
@@ -24,6 +82,13 @@ pub struct Cart {
     pub user_id: String,
     pub items: Vec<CartItem>,
     pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>, // Dernière activité (détection d'abandon)
+    #[serde(default)]
+    pub abandoned_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub shipping_address: Option<ShippingAddress>,
+    #[serde(default)]
+    pub shipping_method_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -31,28 +96,299 @@ pub struct Order {
     pub id: String,
     pub user_id: String,
     pub items: Vec<OrderItem>,
-    pub total: i64,
+    pub subtotal: i64, // Articles seuls
+    pub total: i64,    // Articles + frais de port
     pub status: OrderStatus,
+    pub shipping_address: Option<ShippingAddress>,
+    pub shipping: Option<OrderShipping>,
     pub payment_intent_id: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    #[serde(default)]
+    pub status_history: Vec<OrderStatusChange>,
+    #[serde(default)]
+    pub refunds: Vec<OrderRefund>,
+    #[serde(default)]
+    pub stock_reserved: bool, // Stock déduit pour cette commande et pas encore libéré
+    #[serde(default)]
+    pub payment_attempts: Vec<PaymentAttempt>,
+    #[serde(default)]
+    pub capture_method: CaptureMethod,
+    #[serde(default)]
+    pub authorization: Option<PaymentAuthorization>, // Capture manuelle uniquement
+    #[serde(default)]
+    pub fulfillment_status: FulfillmentStatus, // Logistique, indépendant du paiement
+    #[serde(default)]
+    pub fulfillment_history: Vec<FulfillmentChange>,
+    #[serde(default)]
+    pub shipments: Vec<Shipment>,
+    #[serde(default)]
+    pub return_requested_at: Option<DateTime<Utc>>, // Annulation après expédition convertie en retour
+}
+
+/// Avancement logistique d'une commande
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FulfillmentStatus {
+    #[default]
+    Unfulfilled,
+    Picking, // Préparation commencée: la commande n'est plus modifiable
+    Shipped,
+    Delivered,
+    Returned,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FulfillmentChange {
+    pub from: FulfillmentStatus,
+    pub to: FulfillmentStatus,
+    pub at: DateTime<Utc>,
+    pub reason: String,
+}
+
+/// Colis remis au transporteur
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Shipment {
+    pub id: String,
+    pub carrier: String,
+    pub tracking_number: String,
+    pub tracking_url: Option<String>,
+    pub shipped_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+/// Débit immédiat, ou autorisation au checkout puis capture à l'expédition
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptureMethod {
+    #[default]
+    Automatic,
+    Manual,
+}
+
+/// Autorisation bancaire en attente de capture
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaymentAuthorization {
+    pub amount: i64, // Montant capturable
+    pub authorized_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>, // Stripe annule l'autorisation au-delà
+    #[serde(default)]
+    pub expiring_soon: bool, // Signalée par la tâche de surveillance
+    pub captured_amount: Option<i64>,
+    pub captured_at: Option<DateTime<Utc>>,
+}
+
+/// Tentative de paiement: un PaymentIntent pour un montant donné
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaymentAttempt {
+    pub payment_intent_id: String,
+    pub amount: i64,
+    pub status: PaymentAttemptStatus,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum PaymentAttemptStatus {
+    Pending,
+    Authorized, // Capture manuelle: fonds bloqués, pas encore débités
+    Superseded, // Montant modifié sur le même PaymentIntent
+    Cancelled,  // PaymentIntent remplacé ou annulé
+    Succeeded,
+    Failed,
+    Refunded,   // Ancien PaymentIntent payé après son remplacement, remboursé
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OrderItem {
     pub product_id: String,
+    #[serde(default)]
+    pub variant_sku: Option<String>,
     pub product_name: String,
     pub quantity: i32,
     pub price: i64,
 }
 
+// ========== Livraison ==========
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+pub struct ShippingAddress {
+    #[validate(length(min = 1, message = "Le nom du destinataire est requis"))]
+    pub name: String,
+    #[validate(length(min = 1, message = "L'adresse est requise"))]
+    pub line1: String,
+    #[serde(default)]
+    pub line2: Option<String>,
+    #[validate(length(min = 1, message = "Le code postal est requis"))]
+    pub postal_code: String,
+    #[validate(length(min = 1, message = "La ville est requise"))]
+    pub city: String,
+    #[validate(length(equal = 2, message = "Le pays doit être un code ISO à 2 lettres"))]
+    pub country: String, // Code ISO 3166-1 alpha-2 (FR, DE, ...)
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum ShippingZone {
+    France,
+    Europe,
+    International,
+}
+
+/// Tarif d'une méthode de livraison pour une zone, jusqu'à un poids donné
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShippingRate {
+    pub zone: ShippingZone,
+    pub max_weight_grams: i32,
+    pub price: i64, // En centimes
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShippingMethod {
+    pub id: String,
+    pub name: String,
+    pub rates: Vec<ShippingRate>,
+    pub free_shipping_threshold: Option<i64>, // Port offert à partir de ce sous-total
+}
+
+/// Livraison retenue sur une commande (figée au moment du checkout)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderShipping {
+    pub method_id: String,
+    pub method_name: String,
+    pub cost: i64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum OrderStatus {
     Pending,
     Processing,
+    Authorized, // Paiement autorisé, capturé à l'expédition
     Completed,
     Failed,
     Cancelled,
+    PartiallyRefunded,
+    Refunded,
+}
+
+/// Remboursement (total ou partiel) enregistré sur une commande
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderRefund {
+    pub id: String,
+    pub stripe_refund_id: String,
+    pub amount: i64,
+    pub items: Vec<RefundItem>, // Vide pour un remboursement du montant restant sans détail
+    pub restocked: bool,
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, PartialEq)]
+pub struct RefundItem {
+    #[validate(length(min = 1, message = "Le produit est requis"))]
+    pub product_id: String,
+    #[serde(default)]
+    pub variant_sku: Option<String>,
+    #[validate(range(min = 1, message = "La quantité doit être au moins 1"))]
+    pub quantity: i32,
+}
+
+/// Origine d'un changement de statut
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum StatusChangeSource {
+    Customer(String), // user_id
+    Admin,
+    StripeWebhook,
+    System,
+}
+
+/// Entrée de l'historique des statuts d'une commande
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderStatusChange {
+    pub from: Option<OrderStatus>, // None pour la création
+    pub to: OrderStatus,
+    pub at: DateTime<Utc>,
+    pub source: StatusChangeSource,
+    pub reason: String,
+}
+
+// ========== Politiques d'annulation et de modification ==========
+
+/// Ce que devient une demande d'annulation d'une commande déjà expédiée
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PostShipmentCancellation {
+    Refuse,
+    Return, // Convertie en demande de retour
+}
+
+/// Délais appliqués à une commande (mêmes bornes que les surcharges)
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, PartialEq)]
+pub struct OrderPolicy {
+    #[validate(range(min = 0, max = 8760, message = "Délai d'annulation entre 0 et 8760 heures"))]
+    pub cancel_window_hours: i64,
+    #[validate(range(min = 0, max = 8760, message = "Délai de modification entre 0 et 8760 heures"))]
+    pub modify_window_hours: i64,
+    pub cancel_after_shipment: PostShipmentCancellation,
+    #[validate(range(min = 0, max = 365, message = "Délai de retour entre 0 et 365 jours"))]
+    pub return_window_days: i64, // À partir de l'expédition
+}
+
+impl Default for OrderPolicy {
+    fn default() -> Self {
+        Self {
+            cancel_window_hours: 24,
+            modify_window_hours: 24,
+            cancel_after_shipment: PostShipmentCancellation::Return,
+            return_window_days: 14,
+        }
+    }
+}
+
+/// Surcharge par produit ou par niveau client (champs absents = inchangés)
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Validate, PartialEq)]
+pub struct PolicyOverride {
+    #[validate(range(min = 0, max = 8760, message = "Délai d'annulation entre 0 et 8760 heures"))]
+    pub cancel_window_hours: Option<i64>,
+    #[validate(range(min = 0, max = 8760, message = "Délai de modification entre 0 et 8760 heures"))]
+    pub modify_window_hours: Option<i64>,
+    pub cancel_after_shipment: Option<PostShipmentCancellation>,
+    #[validate(range(min = 0, max = 365, message = "Délai de retour entre 0 et 365 jours"))]
+    pub return_window_days: Option<i64>,
+}
+
+// ========== Factures et reçus ==========
+
+/// Coordonnées du vendeur imprimées sur les documents
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Merchant {
+    pub name: String,
+    pub address: String,
+    pub vat_number: String, // N° de TVA intracommunautaire
+    pub email: String,
+}
+
+/// Facture émise au paiement d'une commande (figée: prix et vendeur à date d'émission)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Invoice {
+    pub number: String, // Numérotation continue, sans trou: FAC-000001
+    pub order_id: String,
+    pub user_id: String,
+    pub issued_at: DateTime<Utc>,
+    pub merchant: Merchant,
+    pub billing_address: Option<ShippingAddress>,
+    pub lines: Vec<InvoiceLine>,
+    pub shipping: Option<OrderShipping>,
+    pub vat_rate_percent: i64,
+    pub total_excl_tax: i64,
+    pub tax_amount: i64,
+    pub total: i64, // TTC, en centimes
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvoiceLine {
+    pub description: String,
+    pub quantity: i32,
+    pub unit_price: i64, // TTC
+    pub total: i64,
 }
 
 // ========== EXERCICE 2: Abonnements ==========
@@ -63,6 +399,49 @@ pub struct SubscriptionPlan {
     pub name: String,
     pub price: i64, // En centimes par mois
     pub description: String,
+    #[serde(default)]
+    pub trial_days: Option<i64>, // Essai gratuit à la première souscription
+    #[serde(default)]
+    pub prices: BTreeMap<BillingInterval, i64>, // Prix par période de facturation (remise sur les longues durées)
+    #[serde(default)]
+    pub metered: Option<MeteredPrice>, // Option facturée à l'usage, en plus de l'abonnement
+    #[serde(default)]
+    pub entitlements: Vec<String>, // Droits d'accès accordés (daily_paper, supplement, weekend_magazine, ...)
+}
+
+/// Tarif à l'usage d'une formule (ex: article numérique débloqué)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MeteredPrice {
+    pub unit_amount: i64, // En centimes par unité
+    pub unit_label: String,
+}
+
+/// Période de facturation d'un abonnement
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum BillingInterval {
+    #[default]
+    Monthly,
+    Quarterly,
+    Annual,
+}
+
+impl BillingInterval {
+    pub fn months(&self) -> u64 {
+        match self {
+            BillingInterval::Monthly => 1,
+            BillingInterval::Quarterly => 3,
+            BillingInterval::Annual => 12,
+        }
+    }
+    
+    pub fn label(&self) -> &'static str {
+        match self {
+            BillingInterval::Monthly => "mois",
+            BillingInterval::Quarterly => "trimestre",
+            BillingInterval::Annual => "an",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -72,18 +451,229 @@ pub struct UserSubscription {
     pub plan_id: String,
     pub stripe_subscription_id: String,
     pub status: SubscriptionStatus,
+    #[serde(default)]
+    pub current_period_start: DateTime<Utc>,
     pub current_period_end: DateTime<Utc>,
     pub cancel_at_period_end: bool,
     pub created_at: DateTime<Utc>,
     pub payment_method_id: Option<String>,
+    #[serde(default)]
+    pub plan_history: Vec<PlanChange>,
+    #[serde(default)]
+    pub trial_end: Option<DateTime<Utc>>, // Renseigné si l'abonnement a commencé par un essai
+    #[serde(default)]
+    pub cancellation: Option<SubscriptionCancellation>,
+    #[serde(default)]
+    pub dunning: Option<DunningState>, // Relances en cours après un échec de paiement
+    #[serde(default)]
+    pub latest_invoice: Option<SubscriptionInvoiceSummary>,
+    #[serde(default)]
+    pub synced_at: Option<DateTime<Utc>>, // Dernière mise à jour depuis Stripe
+    #[serde(default)]
+    pub interval: BillingInterval,
+    #[serde(default)]
+    pub scheduled_interval: Option<BillingInterval>, // Appliquée au prochain renouvellement
+    #[serde(default)]
+    pub interval_schedule_id: Option<String>, // Calendrier Stripe portant ce changement
+    #[serde(default)]
+    pub pause: Option<SubscriptionPause>, // Prélèvements suspendus (vacances, ...)
+    #[serde(default)]
+    pub metered: Option<MeteredItem>, // Option à l'usage souscrite avec l'abonnement
+}
+
+/// Article Stripe facturé à l'usage, au tarif en vigueur à la souscription
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MeteredItem {
+    pub stripe_item_id: String,
+    pub unit_amount: i64,
+    pub unit_label: String,
+}
+
+/// Consommation déclarée sur un abonnement, identifiée par l'appelant
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UsageEvent {
+    pub id: String, // Identifiant fourni par l'appelant: une même déclaration n'est comptée qu'une fois
+    pub quantity: i64,
+    pub occurred_at: DateTime<Utc>,
+    pub received_at: DateTime<Utc>,
+    pub report_status: UsageReportStatus,
+    pub stripe_usage_record_id: Option<String>,
+}
+
+/// Remontée de la consommation vers Stripe
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageReportStatus {
+    Pending,
+    Reporting, // Dans le lot en cours d'envoi, renvoyé tel quel jusqu'au succès
+    Reported,
+}
+
+/// Dernière facture Stripe d'un abonnement
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SubscriptionInvoiceSummary {
+    pub stripe_invoice_id: String,
+    pub status: Option<String>, // draft, open, paid, uncollectible, void (absent si non détaillée)
+    pub amount_due: i64,
+    pub amount_paid: i64,
+    pub hosted_invoice_url: Option<String>,
+}
+
+/// Mode d'annulation d'un abonnement
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SubscriptionCancelMode {
+    #[default]
+    AtPeriodEnd,         // Accès jusqu'à la fin de la période payée
+    Immediate,           // Fin immédiate, sans remboursement
+    ImmediateWithRefund, // Fin immédiate, temps non consommé remboursé au prorata
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubscriptionCancellation {
+    pub mode: SubscriptionCancelMode,
+    pub requested_at: DateTime<Utc>,
+    pub refund_amount: i64, // En centimes, 0 sans remboursement
+    pub stripe_refund_id: Option<String>,
+    #[serde(default)]
+    pub refunded_at: Option<DateTime<Utc>>, // Remboursement accepté par Stripe
+    #[serde(default)]
+    pub pending_refund: Option<PendingRefund>, // Remboursement dû mais pas encore accepté par Stripe
+}
+
+/// Remboursement au prorata à retenter après une résiliation immédiate
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PendingRefund {
+    pub payment_intent_id: String,
+    pub amount: i64,
+}
+
+/// Suspension des prélèvements: pas de facturation ni d'accès pendant la pause
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SubscriptionPause {
+    pub paused_at: DateTime<Utc>,
+    pub resumes_at: Option<DateTime<Utc>>, // Reprise automatique par Stripe, sinon manuelle
+}
+
+/// Traitement du prorata lors d'un changement de formule
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProrationBehavior {
+    ImmediateInvoice, // Prorata facturé et prélevé immédiatement
+    #[default]
+    NextInvoice,      // Prorata reporté sur la prochaine facture
+    None,             // Pas de prorata, nouveau prix au prochain renouvellement
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanChange {
+    pub from_plan_id: String,
+    pub to_plan_id: String,
+    pub proration: ProrationBehavior,
+    pub changed_at: DateTime<Utc>,
+}
+
+/// Suivi des relances d'une facture impayée
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DunningState {
+    pub stripe_invoice_id: String,
+    pub attempts: i64, // Tentatives de paiement échouées
+    pub started_at: DateTime<Utc>,
+    pub next_retry_at: Option<DateTime<Utc>>, // Absent une fois la relance lancée ou les tentatives épuisées
+    pub grace_until: DateTime<Utc>,           // Accès maintenu jusqu'à cette date
+    #[serde(default)]
+    pub technical_failures: u32, // Relances consécutives restées sans réponse de la banque
+}
+
+/// Action appliquée quand toutes les tentatives ont échoué
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DunningFinalAction {
+    Cancel,
+    Pause,
+    MarkUnpaid,
+}
+
+/// Politique de relance des paiements d'abonnement échoués
+///
+/// Modèles de notification: `{plan}`, `{amount}`, `{attempt}`, `{max_attempts}`,
+/// `{next_retry}`, `{grace_until}` et `{action}` (message final) sont remplacés.
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, PartialEq)]
+pub struct DunningPolicy {
+    // Délai (jours) avant chaque nouvelle tentative: [1, 3] = 3 tentatives au total
+    #[validate(length(max = 10, message = "10 nouvelles tentatives au maximum"))]
+    #[validate(custom(function = "validate_retry_days"))]
+    pub retry_schedule_days: Vec<i64>,
+    #[validate(range(min = 0, max = 60, message = "Période de grâce entre 0 et 60 jours"))]
+    pub grace_period_days: i64,
+    // Un modèle par échec (le dernier sert pour les échecs suivants)
+    #[validate(length(min = 1, message = "Au moins un modèle de relance est requis"))]
+    pub step_templates: Vec<String>,
+    #[validate(length(min = 1, message = "Le modèle final est requis"))]
+    pub final_template: String,
+    #[validate(length(min = 1, message = "Le modèle de rétablissement est requis"))]
+    pub recovered_template: String,
+    pub final_action: DunningFinalAction,
+}
+
+fn validate_retry_days(days: &[i64]) -> Result<(), validator::ValidationError> {
+    if days.iter().all(|d| (1..=30).contains(d)) {
+        Ok(())
+    } else {
+        let mut error = validator::ValidationError::new("range");
+        error.message = Some("Chaque délai doit être compris entre 1 et 30 jours".into());
+        Err(error)
+    }
+}
+
+impl Default for DunningPolicy {
+    fn default() -> Self {
+        Self {
+            retry_schedule_days: vec![1, 1],
+            grace_period_days: 3,
+            step_templates: vec![
+                "Le paiement de votre abonnement {plan} ({amount}) a échoué. Nouvelle tentative le {next_retry}; \
+votre accès est maintenu jusqu'au {grace_until}.".to_string(),
+                "Nouvel échec du paiement de votre abonnement {plan} ({amount}), tentative {attempt}/{max_attempts}. \
+Prochaine tentative le {next_retry}: pensez à mettre à jour votre carte.".to_string(),
+            ],
+            final_template: "Le paiement de votre abonnement {plan} a échoué {attempt} fois: votre abonnement est {action}."
+                .to_string(),
+            recovered_template: "Paiement reçu ({amount}): votre abonnement {plan} est de nouveau actif.".to_string(),
+            final_action: DunningFinalAction::MarkUnpaid,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum SubscriptionStatus {
+    Trialing,
     Active,
-    PastDue,
+    PastDue,   // Paiement en échec, relances en cours
     Cancelled,
     Incomplete,
+    Paused,    // Prélèvements suspendus
+    Unpaid,    // Relances épuisées, accès coupé
+}
+
+/// Paiement (ou échec) d'une facture d'abonnement Stripe, pour la comptabilité
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubscriptionPayment {
+    pub stripe_invoice_id: String,
+    pub stripe_subscription_id: String,
+    pub stripe_payment_intent_id: Option<String>,
+    pub user_id: Option<String>, // Absent si l'abonnement n'est pas connu localement
+    pub amount: i64,
+    pub tax: i64,
+    pub currency: String,
+    pub status: SubscriptionPaymentStatus,
+    pub occurred_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum SubscriptionPaymentStatus {
+    Paid,
+    Failed,
 }
 
 // ========== EXERCICE 3: Moyens de paiement ==========
@@ -103,44 +693,177 @@ pub struct SavedPaymentMethod {
 
 // ========== Requêtes API ==========
 
-#[derive(Debug, Deserialize)]
+// Chaque requête déclare ses contraintes: elles sont vérifiées par
+// `ValidatedJson` avant l'exécution du handler (et donc avant tout appel Stripe).
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateProductRequest {
+    #[validate(custom(function = "validate_not_blank", message = "L'identifiant du produit est requis"))]
+    pub id: String,
+    #[validate(custom(function = "validate_not_blank", message = "Le nom du produit est requis"))]
+    pub name: String,
+    #[validate(range(min = 0, message = "Le prix ne peut pas être négatif"))]
+    pub price: i64,
+    #[validate(range(min = 0, message = "Le stock ne peut pas être négatif"))]
+    pub stock: i32,
+    pub description: String,
+    #[serde(default)]
+    #[validate(range(min = 0, message = "Le poids ne peut pas être négatif"))]
+    pub weight_grams: i32,
+    #[serde(default)]
+    #[validate(nested)]
+    pub variants: Vec<ProductVariant>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct UpdateProductRequest {
+    #[validate(custom(function = "validate_not_blank", message = "Le nom du produit est requis"))]
+    pub name: Option<String>,
+    #[validate(range(min = 0, message = "Le prix ne peut pas être négatif"))]
+    pub price: Option<i64>,
+    #[validate(range(min = 0, message = "Le stock ne peut pas être négatif"))]
+    pub stock: Option<i32>,
+    pub description: Option<String>,
+    #[validate(range(min = 0, message = "Le poids ne peut pas être négatif"))]
+    pub weight_grams: Option<i32>,
+    #[validate(nested)]
+    pub variants: Option<Vec<ProductVariant>>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
 pub struct AddToCartRequest {
+    #[validate(length(min = 1, message = "L'utilisateur est requis"))]
     pub user_id: String,
+    #[validate(length(min = 1, message = "Le produit est requis"))]
     pub product_id: String,
+    #[serde(default)]
+    pub variant_sku: Option<String>,
+    #[validate(range(min = 1, max = 1000, message = "La quantité doit être comprise entre 1 et 1000"))]
     pub quantity: i32,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Validate)]
+pub struct SetShippingRequest {
+    #[validate(length(min = 1, message = "L'utilisateur est requis"))]
+    pub user_id: String,
+    #[validate(nested)]
+    pub address: ShippingAddress,
+    #[validate(length(min = 1, message = "La méthode de livraison est requise"))]
+    pub shipping_method_id: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
 pub struct CheckoutRequest {
+    #[validate(length(min = 1, message = "L'utilisateur est requis"))]
     pub user_id: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Validate)]
 pub struct UpdateOrderRequest {
+    #[validate(length(min = 1, message = "La commande doit contenir au moins un article"), nested)]
     pub items: Vec<CartItem>,
 }
 
-#[derive(Debug, Deserialize)]
+/// Sans `items`, tout le montant restant est remboursé (port compris)
+#[derive(Debug, Deserialize, Validate)]
+pub struct RefundOrderRequest {
+    #[validate(length(min = 1, message = "Au moins un article à rembourser"), nested)]
+    pub items: Option<Vec<RefundItem>>,
+    #[serde(default)]
+    pub restock: bool,
+    #[validate(length(max = 500, message = "La raison ne doit pas dépasser 500 caractères"))]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
 pub struct CreateSubscriptionRequest {
+    #[validate(length(min = 1, message = "L'utilisateur est requis"))]
     pub user_id: String,
+    #[validate(length(min = 1, message = "Le plan est requis"))]
     pub plan_id: String,
+    #[validate(email(message = "Adresse email invalide"))]
     pub email: String,
+    #[validate(length(min = 1, message = "Le moyen de paiement est requis"))]
     pub payment_method: String,
+    #[serde(default)]
+    pub interval: BillingInterval,
+    #[serde(default)]
+    pub metered: bool, // Souscrire aussi l'option à l'usage de la formule
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ReportUsageRequest {
+    #[validate(length(min = 1, message = "L'identifiant de l'événement est requis"))]
+    pub event_id: String,
+    #[validate(range(min = 1, message = "La quantité doit être positive"))]
+    pub quantity: i64,
+    #[serde(default)]
+    pub occurred_at: Option<DateTime<Utc>>, // Maintenant par défaut
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Validate)]
+pub struct PauseSubscriptionRequest {
+    #[serde(default)]
+    pub resumes_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ChangeIntervalRequest {
+    pub interval: BillingInterval,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ChangePlanRequest {
+    #[validate(length(min = 1, message = "Le plan est requis"))]
+    pub plan_id: String,
+    #[serde(default)]
+    pub proration: ProrationBehavior,
+}
+
+#[derive(Debug, Deserialize, Validate)]
 pub struct SetupPaymentMethodRequest {
+    #[validate(length(min = 1, message = "L'utilisateur est requis"))]
     pub user_id: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Validate)]
 pub struct PayWithSavedMethodRequest {
+    #[validate(length(min = 1, message = "L'utilisateur est requis"))]
     pub user_id: String,
+    #[validate(length(min = 1, message = "Le moyen de paiement est requis"))]
     pub payment_method_id: String,
+    // Minimum Stripe: 0,50€ / maximum: 999 999,99€
+    #[validate(range(min = 50, max = 99_999_999, message = "Le montant doit être compris entre 50 et 99999999 centimes"))]
     pub amount: i64,
+    #[validate(length(min = 1, max = 500, message = "La description doit faire entre 1 et 500 caractères"))]
     pub description: String,
 }
 
+#[derive(Debug, Deserialize, Validate)]
+pub struct CaptureOrderRequest {
+    #[validate(range(min = 1, message = "Le montant à capturer doit être positif"))]
+    pub amount: Option<i64>, // Absent = montant autorisé complet
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct AdvanceFulfillmentRequest {
+    pub status: FulfillmentStatus,
+    #[validate(length(min = 1, message = "Le transporteur ne peut pas être vide"))]
+    pub carrier: Option<String>, // Requis pour `shipped`
+    #[validate(length(min = 1, message = "Le numéro de suivi ne peut pas être vide"))]
+    pub tracking_number: Option<String>, // Requis pour `shipped`
+    #[validate(url(message = "URL de suivi invalide"))]
+    pub tracking_url: Option<String>,
+    #[validate(length(max = 500, message = "Motif trop long (500 caractères max)"))]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct SetCustomerTierRequest {
+    #[validate(length(min = 1, max = 50, message = "Le niveau client doit faire entre 1 et 50 caractères"))]
+    pub tier: String,
+}
+
 // ========== Réponses API ==========
 
 #[derive(Debug, Serialize)]
@@ -150,6 +873,20 @@ pub struct CheckoutResponse {
     pub client_secret: String,
 }
 
+#[derive(Debug, Serialize)]
+pub struct UpdateOrderResponse {
+    pub order: Order,
+    pub client_secret: String, // À utiliser pour confirmer le nouveau montant
+}
+
+/// Page de commandes; `next_cursor` est absent sur la dernière page
+#[derive(Debug, Serialize)]
+pub struct OrderPage {
+    pub orders: Vec<Order>,
+    pub total_count: usize, // Nombre de commandes correspondant aux filtres
+    pub next_cursor: Option<String>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct SubscriptionResponse {
     pub subscription_id: String,
@@ -167,3 +904,10 @@ pub struct SetupResponse {
 pub struct ApiError {
     pub error: String,
 }
+
+/// Erreur de validation avec le détail par champ (ex. `items[0].quantity`)
+#[derive(Debug, Serialize)]
+pub struct ValidationErrorResponse {
+    pub error: String,
+    pub fields: BTreeMap<String, Vec<String>>,
+}
@@ -0,0 +1,91 @@
+// Capture manuelle des paiements autorisés (à l'expédition)
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::Utc;
+
+use crate::models::*;
+use crate::routes::validation::ValidatedJson;
+use crate::services::{captures, stripe_service};
+use crate::state::AppState;
+
+/// Capturer tout ou partie du paiement autorisé d'une commande
+pub async fn capture_order(
+    State(state): State<AppState>,
+    Path(order_id): Path<String>,
+    ValidatedJson(req): ValidatedJson<CaptureOrderRequest>,
+) -> Result<Json<Order>, (StatusCode, Json<ApiError>)> {
+    let _operation = state.begin_order_operation(&order_id)?;
+    
+    capture_payment(&state, &order_id, req.amount).await.map(Json)
+}
+
+/// Capture partagée par l'endpoint et l'expédition des commandes
+///
+/// Le passage en `Completed` (facture, notification) est fait par le webhook
+/// `payment_intent.succeeded`, comme pour un débit immédiat.
+pub async fn capture_payment(
+    state: &AppState,
+    order_id: &str,
+    amount: Option<i64>,
+) -> Result<Order, (StatusCode, Json<ApiError>)> {
+    let (amount, payment_intent_id) = {
+        let order = state.orders.get(order_id)
+            .ok_or_else(|| (
+                StatusCode::NOT_FOUND,
+                Json(ApiError { error: "Commande non trouvée".to_string() })
+            ))?;
+        
+        let amount = captures::plan_capture(&order, amount)?;
+        let payment_intent_id = order.payment_intent_id.clone()
+            .ok_or_else(|| (
+                StatusCode::CONFLICT,
+                Json(ApiError { error: "Aucun paiement associé à cette commande".to_string() })
+            ))?;
+        
+        (amount, payment_intent_id)
+    };
+    
+    // Le montant non capturé est libéré par Stripe
+    let payment_intent = stripe_service::capture_payment_intent(
+        &state.stripe_client,
+        &payment_intent_id,
+        Some(amount),
+    ).await.map_err(|e| (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ApiError { error: format!("Erreur capture Stripe: {}", e) })
+    ))?;
+    
+    let mut order = state.orders.get_mut(order_id)
+        .ok_or_else(|| (
+            StatusCode::NOT_FOUND,
+            Json(ApiError { error: "Commande non trouvée".to_string() })
+        ))?;
+    
+    if let Some(authorization) = order.authorization.as_mut() {
+        authorization.captured_amount = Some(payment_intent.amount_received);
+        authorization.captured_at = Some(Utc::now());
+    }
+    order.updated_at = Utc::now();
+    
+    tracing::info!("💰 Commande {} capturée: {}€", order_id, payment_intent.amount_received as f64 / 100.0);
+    
+    Ok(order.clone())
+}
+
+/// Lister les autorisations en attente de capture, les plus proches de l'expiration d'abord (admin)
+pub async fn list_authorizations(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<Order>>, (StatusCode, Json<ApiError>)> {
+    let mut orders: Vec<Order> = state.orders
+        .iter()
+        .filter(|entry| entry.status == OrderStatus::Authorized)
+        .map(|entry| entry.value().clone())
+        .collect();
+    orders.sort_by_key(|order| order.authorization.as_ref().map(|a| a.expires_at));
+    
+    Ok(Json(orders))
+}
@@ -5,21 +5,22 @@ use axum::{
     http::StatusCode,
     Json,
 };
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use serde::Deserialize;
+use std::collections::HashSet;
 use uuid::Uuid;
 
 use crate::models::*;
-use crate::services::stripe_service;
+use crate::routes::validation::ValidatedJson;
+use crate::services::order_listing::{self, OrderFilter, OrderSortField, PageRequest, SortDirection};
+use crate::services::policies::{self, CancellationOutcome};
+use crate::services::{fulfillment, inventory, order_state, shipping, stripe_service};
 use crate::state::AppState;
 
-// Policy d'annulation: 24 heures max après création de commande
-const CANCELLATION_WINDOW_HOURS: i64 = 24;
-
 /// Ajouter un article au panier
 pub async fn add_to_cart(
     State(state): State<AppState>,
-    Json(req): Json<AddToCartRequest>,
+    ValidatedJson(req): ValidatedJson<AddToCartRequest>,
 ) -> Result<Json<Cart>, (StatusCode, Json<ApiError>)> {
     // Vérifier que le produit existe et a du stock
     let product = state.products.get(&req.product_id)
@@ -28,11 +29,21 @@ pub async fn add_to_cart(
             Json(ApiError { error: "Produit non trouvé".to_string() })
         ))?;
     
-    if product.stock < req.quantity {
+    if product.archived {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError { error: format!("{} n'est plus disponible à la vente", product.name) })
+        ));
+    }
+    
+    // Prix et stock au niveau de la variante si le produit en a
+    let line = resolve_line(&product, req.variant_sku.as_deref())?;
+    
+    if line.stock < req.quantity {
         return Err((
             StatusCode::BAD_REQUEST,
             Json(ApiError { 
-                error: format!("Stock insuffisant.")
+                error: "Stock insuffisant.".to_string()
             })
         ));
     }
@@ -43,27 +54,82 @@ pub async fn add_to_cart(
             user_id: req.user_id.clone(),
             items: vec![],
             created_at: Utc::now(),
+            updated_at: Utc::now(),
+            abandoned_at: None,
+            shipping_address: None,
+            shipping_method_id: None,
         });
     
-    // Ajouter ou mettre à jour l'article
-    if let Some(item) = cart_ref.items.iter_mut().find(|i| i.product_id == req.product_id) {
+    // Ajouter ou mettre à jour l'article (une ligne par produit et variante)
+    if let Some(item) = cart_ref.items.iter_mut()
+        .find(|i| i.product_id == req.product_id && i.variant_sku == req.variant_sku) {
         item.quantity += req.quantity;
     } else {
         cart_ref.items.push(CartItem {
             product_id: req.product_id.clone(),
+            variant_sku: req.variant_sku.clone(),
             quantity: req.quantity,
         });
     }
     
+    // Le panier redevient actif
+    cart_ref.updated_at = Utc::now();
+    cart_ref.abandoned_at = None;
+    
     tracing::info!("✅ Article ajouté au panier pour user {}", req.user_id);
     // Clone uniquement pour la réponse JSON
     Ok(Json(cart_ref.clone()))
 }
 
+// Ligne de panier résolue (produit simple ou variante)
+struct ResolvedLine {
+    name: String,
+    price: i64,
+    stock: i32,
+}
+
+// Fonction helper pour retrouver prix et stock d'un produit ou d'une de ses variantes
+fn resolve_line(
+    product: &Product,
+    variant_sku: Option<&str>,
+) -> Result<ResolvedLine, (StatusCode, Json<ApiError>)> {
+    match variant_sku {
+        None if product.variants.is_empty() => Ok(ResolvedLine {
+            name: product.name.clone(),
+            price: product.price,
+            stock: product.stock,
+        }),
+        None => Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError { error: format!("Choisissez une variante (taille/couleur) pour {}", product.name) })
+        )),
+        Some(sku) => {
+            let variant = product.variant(sku)
+                .ok_or_else(|| (
+                    StatusCode::NOT_FOUND,
+                    Json(ApiError { error: format!("Variante {} introuvable pour {}", sku, product.name) })
+                ))?;
+            
+            let options: Vec<&str> = [&variant.size, &variant.color]
+                .into_iter()
+                .flatten()
+                .map(|s| s.as_str())
+                .collect();
+            
+            Ok(ResolvedLine {
+                name: format!("{} ({})", product.name, options.join(" / ")),
+                price: variant.price.unwrap_or(product.price),
+                stock: variant.stock,
+            })
+        }
+    }
+}
+
 // Fonction helper pour calculer le panier avec validation
 struct CartCalculation {
     items: Vec<OrderItem>,
     total: i64,
+    weight_grams: i32,
     stock_warnings: Vec<String>,
 }
 
@@ -74,6 +140,7 @@ fn calculate_cart(
 ) -> Result<CartCalculation, (StatusCode, Json<ApiError>)> {
     let mut items = vec![];
     let mut total = 0i64;
+    let mut weight_grams = 0i32;
     let mut stock_warnings = vec![];
     
     for item in &cart.items {
@@ -83,10 +150,23 @@ fn calculate_cart(
                 Json(ApiError { error: format!("Produit {} introuvable", item.product_id) })
             ))?;
         
-        // Vérifier le stock
-        if product.stock < item.quantity {
+        // Un produit archivé ne peut plus être commandé
+        if product.archived {
+            if validate_stock {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    Json(ApiError { error: format!("{} n'est plus disponible à la vente", product.name) })
+                ));
+            }
+            stock_warnings.push(format!("{}: n'est plus disponible à la vente", product.name));
+        }
+        
+        // Vérifier le stock (au niveau de la variante le cas échéant)
+        let line = resolve_line(&product, item.variant_sku.as_deref())?;
+        
+        if line.stock < item.quantity {
             let warning = format!("{}: demandé {}, disponible {}", 
-                                product.name, item.quantity, product.stock);
+                                line.name, item.quantity, line.stock);
             
             if validate_stock {
                 // Mode strict (checkout): bloquer
@@ -94,7 +174,7 @@ fn calculate_cart(
                     StatusCode::BAD_REQUEST,
                     Json(ApiError { 
                         error: format!("Stock insuffisant pour {}. Disponible: {}", 
-                                     product.name, product.stock)
+                                     line.name, line.stock)
                     })
                 ));
             } else {
@@ -103,18 +183,50 @@ fn calculate_cart(
             }
         }
         
-        let subtotal = product.price * item.quantity as i64;
+        let subtotal = line.price * item.quantity as i64;
         total += subtotal;
+        weight_grams += product.weight_grams * item.quantity;
         
         items.push(OrderItem {
             product_id: product.id.clone(),
-            product_name: product.name.clone(),
+            variant_sku: item.variant_sku.clone(),
+            product_name: line.name,
             quantity: item.quantity,
-            price: product.price,
+            price: line.price,
         });
     }
     
-    Ok(CartCalculation { items, total, stock_warnings })
+    Ok(CartCalculation { items, total, weight_grams, stock_warnings })
+}
+
+// Fonction helper pour calculer les frais de port d'un panier calculé
+fn calculate_shipping(
+    calc: &CartCalculation,
+    address: &ShippingAddress,
+    method_id: &str,
+    methods: &dashmap::DashMap<String, ShippingMethod>,
+) -> Result<OrderShipping, (StatusCode, Json<ApiError>)> {
+    let method = methods.get(method_id)
+        .ok_or_else(|| (
+            StatusCode::NOT_FOUND,
+            Json(ApiError { error: format!("Méthode de livraison {} introuvable", method_id) })
+        ))?;
+    
+    let zone = shipping::zone_for_country(&address.country);
+    let cost = shipping::quote(&method, zone, calc.weight_grams, calc.total)
+        .ok_or_else(|| (
+            StatusCode::BAD_REQUEST,
+            Json(ApiError { 
+                error: format!("{} ne livre pas en {} pour un colis de {}g", 
+                             method.name, address.country, calc.weight_grams)
+            })
+        ))?;
+    
+    Ok(OrderShipping {
+        method_id: method.id.clone(),
+        method_name: method.name.clone(),
+        cost,
+    })
 }
 
 #[derive(Deserialize)]
@@ -136,10 +248,20 @@ pub async fn view_cart(
     // Utiliser la fonction helper (sans validation stock pour le view)
     let calc = calculate_cart(&cart, &state.products, false)?;
     
+    // Frais de port si l'adresse et la méthode sont déjà choisies
+    let shipping = match (&cart.shipping_address, &cart.shipping_method_id) {
+        (Some(address), Some(method_id)) => {
+            Some(calculate_shipping(&calc, address, method_id, &state.shipping_methods)?)
+        }
+        _ => None,
+    };
+    let shipping_cost = shipping.as_ref().map(|s| s.cost).unwrap_or(0);
+    
     // Formater pour la réponse JSON
     let items_detail: Vec<_> = calc.items.iter().map(|item| {
         serde_json::json!({
             "product_id": item.product_id,
+            "variant_sku": item.variant_sku,
             "name": item.product_name,
             "price": item.price,
             "quantity": item.quantity,
@@ -150,16 +272,89 @@ pub async fn view_cart(
     Ok(Json(serde_json::json!({
         "user_id": cart.user_id,
         "items": items_detail,
-        "total": calc.total,
+        "subtotal": calc.total,
+        "shipping_address": cart.shipping_address,
+        "shipping": shipping,
+        "total": calc.total + shipping_cost,
         "created_at": cart.created_at,
         "stock_warnings": calc.stock_warnings,  // Alertes visibles!
     })))
 }
 
+/// Choisir l'adresse et la méthode de livraison du panier
+pub async fn set_shipping(
+    State(state): State<AppState>,
+    ValidatedJson(req): ValidatedJson<SetShippingRequest>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ApiError>)> {
+    let mut cart = state.carts.get_mut(&req.user_id)
+        .ok_or_else(|| (
+            StatusCode::NOT_FOUND,
+            Json(ApiError { error: "Panier vide".to_string() })
+        ))?;
+    
+    // Vérifier que la méthode dessert bien l'adresse pour ce panier
+    let calc = calculate_cart(&cart, &state.products, false)?;
+    let shipping = calculate_shipping(&calc, &req.address, &req.shipping_method_id, &state.shipping_methods)?;
+    
+    cart.shipping_address = Some(req.address);
+    cart.shipping_method_id = Some(req.shipping_method_id);
+    cart.updated_at = Utc::now();
+    cart.abandoned_at = None;
+    
+    tracing::info!("🚚 Livraison {} choisie pour user {} - Frais: {}€", 
+                  shipping.method_name, req.user_id, shipping.cost as f64 / 100.0);
+    
+    Ok(Json(serde_json::json!({
+        "user_id": req.user_id,
+        "shipping_address": cart.shipping_address,
+        "shipping": shipping,
+        "subtotal": calc.total,
+        "total": calc.total + shipping.cost,
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct ShippingMethodsQuery {
+    user_id: String,
+    country: String,
+}
+
+/// Lister les méthodes de livraison disponibles pour le panier et un pays
+pub async fn list_shipping_methods(
+    State(state): State<AppState>,
+    Query(query): Query<ShippingMethodsQuery>,
+) -> Result<Json<Vec<serde_json::Value>>, (StatusCode, Json<ApiError>)> {
+    let cart = state.carts.get(&query.user_id)
+        .ok_or_else(|| (
+            StatusCode::NOT_FOUND,
+            Json(ApiError { error: "Panier vide".to_string() })
+        ))?;
+    
+    let calc = calculate_cart(&cart, &state.products, false)?;
+    let zone = shipping::zone_for_country(&query.country);
+    
+    // Seules les méthodes qui desservent la zone pour ce poids sont proposées
+    let methods: Vec<_> = state.shipping_methods
+        .iter()
+        .filter_map(|method| {
+            shipping::quote(&method, zone, calc.weight_grams, calc.total).map(|cost| {
+                serde_json::json!({
+                    "id": method.id,
+                    "name": method.name,
+                    "cost": cost,
+                    "free_shipping_threshold": method.free_shipping_threshold,
+                })
+            })
+        })
+        .collect();
+    
+    Ok(Json(methods))
+}
+
 /// Passer à la caisse (créer un PaymentIntent Stripe)
 pub async fn checkout(
     State(state): State<AppState>,
-    Json(req): Json<CheckoutRequest>,
+    ValidatedJson(req): ValidatedJson<CheckoutRequest>,
 ) -> Result<Json<CheckoutResponse>, (StatusCode, Json<ApiError>)> {
     // Récupérer le panier
     let cart = state.carts.get(&req.user_id)
@@ -178,38 +373,92 @@ pub async fn checkout(
     // Utiliser la fonction helper (avec validation stock pour le checkout)
     let calc = calculate_cart(&cart, &state.products, true)?;
     
+    // La livraison doit être choisie avant le paiement
+    let (address, method_id) = match (&cart.shipping_address, &cart.shipping_method_id) {
+        (Some(address), Some(method_id)) => (address.clone(), method_id.clone()),
+        _ => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(ApiError { error: "Adresse et méthode de livraison requises".to_string() })
+            ));
+        }
+    };
+    drop(cart);
+    
+    let order_shipping = calculate_shipping(&calc, &address, &method_id, &state.shipping_methods)?;
+    let total = calc.total + order_shipping.cost;
+    
     // Créer la commande
     let order_id = Uuid::new_v4().to_string();
     let order = Order {
         id: order_id.clone(),
         user_id: req.user_id.clone(),
         items: calc.items,
-        total: calc.total,
+        subtotal: calc.total,
+        total,
         status: OrderStatus::Pending,
+        shipping_address: Some(address.clone()),
+        shipping: Some(order_shipping),
         payment_intent_id: None,
         created_at: Utc::now(),
         updated_at: Utc::now(),
+        status_history: vec![order_state::creation_entry(
+            StatusChangeSource::Customer(req.user_id.clone()),
+        )],
+        refunds: vec![],
+        stock_reserved: false,
+        payment_attempts: vec![],
+        capture_method: state.config.capture_method,
+        authorization: None,
+        fulfillment_status: FulfillmentStatus::Unfulfilled,
+        fulfillment_history: vec![],
+        shipments: vec![],
+        return_requested_at: None,
     };
     
-    // Créer le PaymentIntent Stripe
-    let payment_intent = stripe_service::create_payment_intent(
+    // Réserver le stock avant l'appel Stripe: deux checkouts simultanés
+    // ne peuvent pas se partager les dernières unités
+    inventory::try_reserve_items(&state.products, &order.items)?;
+    
+    // Créer le PaymentIntent Stripe (articles + port)
+    let payment_intent = match stripe_service::create_payment_intent(
         &state.stripe_client,
-        calc.total,
+        total,
         &order_id,
-    ).await.map_err(|e| (
-        StatusCode::INTERNAL_SERVER_ERROR,
-        Json(ApiError { error: format!("Erreur Stripe: {}", e) })
-    ))?;
+        Some(&address),
+        order.capture_method,
+    ).await {
+        Ok(payment_intent) => payment_intent,
+        Err(e) => {
+            inventory::release_items(&state.products, &order.items);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiError { error: format!("Erreur Stripe: {}", e) })
+            ));
+        }
+    };
     
     // Mettre à jour la commande avec le PaymentIntent ID
     let mut order = order;
+    order.stock_reserved = true;
     order.payment_intent_id = Some(payment_intent.id.to_string());
-    order.status = OrderStatus::Processing;
+    order.payment_attempts.push(PaymentAttempt {
+        payment_intent_id: payment_intent.id.to_string(),
+        amount: total,
+        status: PaymentAttemptStatus::Pending,
+        created_at: Utc::now(),
+    });
+    order_state::transition(
+        &mut order,
+        OrderStatus::Processing,
+        StatusChangeSource::System,
+        "PaymentIntent créé",
+    ).inspect_err(|_| inventory::release_items(&state.products, &order.items))?;
     
     state.orders.insert(order_id.clone(), order);
     
     tracing::info!("💳 Checkout créé pour user {} - Montant: {}€", 
-                  req.user_id, calc.total as f64 / 100.0);
+                  req.user_id, total as f64 / 100.0);
     
     Ok(Json(CheckoutResponse {
         order_id: order_id.clone(),
@@ -232,79 +481,168 @@ pub async fn get_order(
     Ok(Json(order.clone()))
 }
 
+/// Historique des statuts d'une commande
+pub async fn get_order_timeline(
+    State(state): State<AppState>,
+    Path(order_id): Path<String>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ApiError>)> {
+    let order = state.orders.get(&order_id)
+        .ok_or_else(|| (
+            StatusCode::NOT_FOUND,
+            Json(ApiError { error: "Commande non trouvée".to_string() })
+        ))?;
+    
+    Ok(Json(serde_json::json!({
+        "order_id": order.id,
+        "status": order.status,
+        "timeline": order.status_history,
+    })))
+}
+
 #[derive(Deserialize)]
 pub struct ListOrdersQuery {
-    user_id: String,
+    user_id: Option<String>,
+    status: Option<OrderStatus>,
+    fulfillment_status: Option<FulfillmentStatus>,
+    created_from: Option<DateTime<Utc>>,
+    created_to: Option<DateTime<Utc>>,
+    min_total: Option<i64>,
+    max_total: Option<i64>,
+    #[serde(default)]
+    sort: OrderSortField,
+    #[serde(default)]
+    direction: SortDirection,
+    cursor: Option<String>,
+    limit: Option<usize>,
+}
+
+impl ListOrdersQuery {
+    fn into_parts(self) -> (OrderFilter, PageRequest) {
+        (
+            OrderFilter {
+                user_id: self.user_id,
+                status: self.status,
+                fulfillment_status: self.fulfillment_status,
+                created_from: self.created_from,
+                created_to: self.created_to,
+                min_total: self.min_total,
+                max_total: self.max_total,
+            },
+            PageRequest {
+                sort: self.sort,
+                direction: self.direction,
+                cursor: self.cursor,
+                limit: self.limit,
+            },
+        )
+    }
 }
 
-/// Lister toutes les commandes d'un utilisateur
+/// Lister les commandes d'un utilisateur (filtres, tri, pagination par curseur)
 pub async fn list_orders(
     State(state): State<AppState>,
     Query(query): Query<ListOrdersQuery>,
-) -> Result<Json<Vec<Order>>, (StatusCode, Json<ApiError>)> {
-    let orders: Vec<Order> = state.orders
-        .iter()
-        .filter(|entry| entry.value().user_id == query.user_id)
-        .map(|entry| entry.value().clone())
-        .collect();
+) -> Result<Json<OrderPage>, (StatusCode, Json<ApiError>)> {
+    if query.user_id.is_none() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError { error: "Paramètre user_id requis".to_string() })
+        ));
+    }
+    
+    let (filter, page) = query.into_parts();
+    let page = order_listing::list_orders(&state.orders, &filter, &page)?;
+    
+    Ok(Json(page))
+}
+
+/// Lister les commandes de tous les utilisateurs (admin), avec le nombre total
+pub async fn list_all_orders(
+    State(state): State<AppState>,
+    Query(query): Query<ListOrdersQuery>,
+) -> Result<Json<OrderPage>, (StatusCode, Json<ApiError>)> {
+    let (filter, page) = query.into_parts();
+    let page = order_listing::list_orders(&state.orders, &filter, &page)?;
     
-    Ok(Json(orders))
+    Ok(Json(page))
 }
 
-/// Annuler une commande (seulement si Pending ou Failed + dans les 24h)
+/// Annuler une commande dans le délai de sa politique d'annulation
+///
+/// Le PaymentIntent est annulé sur Stripe avant la commande pour qu'il ne
+/// puisse plus être payé, puis le stock réservé est libéré. Une commande
+/// déjà expédiée devient une demande de retour si la politique le permet.
 pub async fn cancel_order(
     State(state): State<AppState>,
     Path(order_id): Path<String>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, Json<ApiError>)> {
+    let now = Utc::now();
+    let _operation = state.begin_order_operation(&order_id)?;
+    
+    let (payment_intent_id, elapsed_hours) = {
+        let mut order = state.orders.get_mut(&order_id)
+            .ok_or_else(|| (
+                StatusCode::NOT_FOUND,
+                Json(ApiError { error: "Commande non trouvée".to_string() })
+            ))?;
+        
+        // Délais et traitement après expédition selon la politique applicable
+        let applied = policies::policy_for_order(&state, &order);
+        if policies::check_cancel(&order, &applied, now)? == CancellationOutcome::Return {
+            order.return_requested_at = Some(now);
+            order.updated_at = now;
+            
+            tracing::info!("↩️ Commande {} expédiée: annulation convertie en retour", order_id);
+            println!("\n NOTIFICATION CLIENT: Retour enregistré pour la commande {}: renvoyez le colis pour être remboursé", order_id);
+            
+            // Le remboursement suit la réception du colis (logistique → `returned`)
+            return Ok(Json(serde_json::json!({
+                "message": "Commande déjà expédiée: demande de retour enregistrée, remboursement à réception du colis",
+                "order_id": order_id,
+                "return_requested_at": now,
+                "policy": applied,
+            })));
+        }
+        
+        let elapsed_hours = (now - order.created_at).num_hours();
+        
+        // Vérifier la transition avant de toucher à Stripe
+        if !order_state::can_transition(&order.status, &OrderStatus::Cancelled) {
+            return Err(order_state::TransitionError::Forbidden {
+                from: order.status.clone(),
+                to: OrderStatus::Cancelled,
+            }.into());
+        }
+        
+        (order.payment_intent_id.clone(), elapsed_hours)
+    };
+    
+    // Annuler le PaymentIntent (sans garder la commande verrouillée pendant l'appel);
+    // déjà annulé sur Stripe, il ne peut plus être payé: l'annulation continue
+    if let Some(payment_intent_id) = &payment_intent_id {
+        cancel_payment_intent(&state, payment_intent_id).await?;
+    }
+    
     let mut order = state.orders.get_mut(&order_id)
         .ok_or_else(|| (
             StatusCode::NOT_FOUND,
             Json(ApiError { error: "Commande non trouvée".to_string() })
         ))?;
     
-    // Vérifier le délai d'annulation (24h max)
-    let now = Utc::now();
-    let elapsed_hours = (now - order.created_at).num_hours();
-    
-    if elapsed_hours > CANCELLATION_WINDOW_HOURS {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(ApiError { 
-                error: format!(
-                    "Délai d'annulation dépassé. Vous pouviez annuler dans les {} heures suivant la commande. Commande créée il y a {} heures.",
-                    CANCELLATION_WINDOW_HOURS,
-                    elapsed_hours
-                )
-            })
-        ));
-    }
-    
-    // On ne peut annuler que les commandes Pending ou Failed
-    match order.status {
-        OrderStatus::Completed => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                Json(ApiError { error: "Commande déjà complétée, annulation impossible".to_string() })
-            ));
-        }
-        OrderStatus::Processing => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                Json(ApiError { error: "Paiement en cours, attendez la confirmation".to_string() })
-            ));
-        }
-        OrderStatus::Cancelled => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                Json(ApiError { error: "Commande déjà annulée".to_string() })
-            ));
-        }
-        _ => {}
+    let user_id = order.user_id.clone();
+    order_state::transition(
+        &mut order,
+        OrderStatus::Cancelled,
+        StatusChangeSource::Customer(user_id),
+        "Annulation demandée par le client",
+    )?;
+    
+    // Libérer le stock réservé au checkout
+    if order.stock_reserved {
+        inventory::release_items(&state.products, &order.items);
+        order.stock_reserved = false;
     }
     
-    order.status = OrderStatus::Cancelled;
-    order.updated_at = Utc::now();
-    
     tracing::info!("Commande {} annulée par l'utilisateur ({}h après création)", 
                   order_id, elapsed_hours);
     
@@ -316,77 +654,314 @@ pub async fn cancel_order(
     })))
 }
 
-/// Modifier une commande (jusqu'au dernier moment si stock dispo)
-/// ⚠️ L'équipe logistique va vous détester pour cette feature
+/// Modifier une commande tant que sa préparation n'a pas commencé
+///
+/// Le PaymentIntent est remis au nouveau montant (ou remplacé s'il ne peut
+/// plus être modifié) pour que le client ne puisse pas payer l'ancien prix.
+/// Le stock des nouveaux articles est réservé avant les appels Stripe et
+/// l'ancienne réservation rétablie si la modification échoue.
 pub async fn update_order(
     State(state): State<AppState>,
     Path(order_id): Path<String>,
-    Json(req): Json<UpdateOrderRequest>,
-) -> Result<Json<Order>, (StatusCode, Json<ApiError>)> {
-    let mut order = state.orders.get_mut(&order_id)
-        .ok_or_else(|| (
-            StatusCode::NOT_FOUND,
-            Json(ApiError { error: "Commande non trouvée".to_string() })
-        ))?;
-    
-    // Vérifier le délai de modification (même fenêtre que l'annulation)
+    ValidatedJson(req): ValidatedJson<UpdateOrderRequest>,
+) -> Result<Json<UpdateOrderResponse>, (StatusCode, Json<ApiError>)> {
     let now = Utc::now();
-    let elapsed_hours = (now - order.created_at).num_hours();
+    let _operation = state.begin_order_operation(&order_id)?;
+    
+    // 1. Valider la modification (sans garder la commande verrouillée pendant les appels Stripe)
+    let (calc, order_shipping, total, current_pi, shipping_address, capture_method, elapsed_hours, previous_items, reserved) = {
+        let order = state.orders.get(&order_id)
+            .ok_or_else(|| (
+                StatusCode::NOT_FOUND,
+                Json(ApiError { error: "Commande non trouvée".to_string() })
+            ))?;
+        
+        // Délai de modification de la politique applicable (distinct de l'annulation)
+        let applied = policies::policy_for_order(&state, &order);
+        policies::check_modify(&order, &applied, now)?;
+        let elapsed_hours = (now - order.created_at).num_hours();
+        
+        // On ne peut modifier qu'une commande pas encore payée
+        ensure_editable(&order)?;
+        
+        // Créer un panier temporaire pour valider les nouveaux items
+        let temp_cart = Cart {
+            user_id: order.user_id.clone(),
+            items: req.items.clone(),
+            created_at: order.created_at,
+            updated_at: now,
+            abandoned_at: None,
+            shipping_address: order.shipping_address.clone(),
+            shipping_method_id: order.shipping.as_ref().map(|s| s.method_id.clone()),
+        };
+        
+        // Le stock réservé par la commande compte comme disponible pendant la validation
+        if order.stock_reserved {
+            inventory::release_items(&state.products, &order.items);
+        }
+        
+        // Valider le nouveau panier (stock, etc.) et recalculer le port (le poids a pu changer)
+        let validated = calculate_cart(&temp_cart, &state.products, true).and_then(|calc| {
+            let order_shipping = match (&temp_cart.shipping_address, &temp_cart.shipping_method_id) {
+                (Some(address), Some(method_id)) => {
+                    Some(calculate_shipping(&calc, address, method_id, &state.shipping_methods)?)
+                }
+                _ => None,
+            };
+            Ok((calc, order_shipping))
+        });
+        
+        // Basculer la réservation sur les nouveaux articles, ou rétablir l'ancienne
+        let reservation = validated.and_then(|validated| {
+            if order.stock_reserved {
+                inventory::try_reserve_items(&state.products, &validated.0.items)?;
+            }
+            Ok(validated)
+        });
+        if reservation.is_err() && order.stock_reserved {
+            inventory::reserve_items(&state.products, &order.items);
+        }
+        
+        let (calc, order_shipping) = reservation?;
+        let total = calc.total + order_shipping.as_ref().map(|s| s.cost).unwrap_or(0);
+        
+        (
+            calc,
+            order_shipping,
+            total,
+            order.payment_intent_id.clone(),
+            order.shipping_address.clone(),
+            order.capture_method,
+            elapsed_hours,
+            order.items.clone(),
+            order.stock_reserved,
+        )
+    };
+    
+    // Modification abandonnée: rendre le stock réservé aux anciens articles
+    let restore_reservation = || {
+        if reserved {
+            inventory::release_items(&state.products, &calc.items);
+            inventory::reserve_items(&state.products, &previous_items);
+        }
+    };
+    
+    // 2. Remettre le PaymentIntent au nouveau montant, ou le remplacer
+    let (payment_intent, replaced) = match reprice_payment_intent(
+        &state,
+        &order_id,
+        current_pi.as_deref(),
+        total,
+        shipping_address.as_ref(),
+        capture_method,
+    ).await {
+        Ok(repriced) => repriced,
+        Err(e) => {
+            restore_reservation();
+            return Err(e);
+        }
+    };
+    let payment_intent_id = payment_intent.id.to_string();
     
-    if elapsed_hours > CANCELLATION_WINDOW_HOURS {
+    // 3. Enregistrer la modification
+    let Some(mut order) = state.orders.get_mut(&order_id) else {
+        restore_reservation();
         return Err((
-            StatusCode::BAD_REQUEST,
-            Json(ApiError { 
-                error: format!(
-                    "Délai de modification dépassé. Vous pouviez modifier dans les {} heures suivant la commande.",
-                    CANCELLATION_WINDOW_HOURS
-                )
-            })
+            StatusCode::NOT_FOUND,
+            Json(ApiError { error: "Commande non trouvée".to_string() })
         ));
+    };
+    
+    // Un webhook a pu changer le statut pendant les appels Stripe
+    if let Err(e) = ensure_editable(&order) {
+        restore_reservation();
+        return Err(e);
     }
     
-    // On ne peut modifier que les commandes Pending ou Failed
-    match order.status {
-        OrderStatus::Completed => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                Json(ApiError { error: "Commande déjà complétée, modification impossible".to_string() })
-            ));
-        }
-        OrderStatus::Processing => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                Json(ApiError { error: "Paiement en cours, modification impossible".to_string() })
-            ));
+    // Un échec de paiement a pu libérer l'ancienne réservation entre-temps:
+    // elle l'a déjà été au moment de la bascule
+    if reserved && !order.stock_reserved {
+        inventory::reserve_items(&state.products, &previous_items);
+        order.stock_reserved = true;
+    }
+    order.items = calc.items;
+    order.subtotal = calc.total;
+    order.total = total;
+    order.shipping = order_shipping;
+    order.updated_at = now;
+    
+    // Historique des tentatives: l'ancienne est remplacée ou annulée
+    for attempt in order.payment_attempts.iter_mut()
+        .filter(|a| a.status == PaymentAttemptStatus::Pending) {
+        attempt.status = if replaced {
+            PaymentAttemptStatus::Cancelled
+        } else {
+            PaymentAttemptStatus::Superseded
+        };
+    }
+    order.payment_attempts.push(PaymentAttempt {
+        payment_intent_id: payment_intent_id.clone(),
+        amount: total,
+        status: PaymentAttemptStatus::Pending,
+        created_at: now,
+    });
+    order.payment_intent_id = Some(payment_intent_id);
+    
+    // Le nouveau montant est en attente de paiement
+    if order.status != OrderStatus::Processing {
+        let user_id = order.user_id.clone();
+        order_state::transition(
+            &mut order,
+            OrderStatus::Processing,
+            StatusChangeSource::Customer(user_id),
+            "Commande modifiée par le client",
+        )?;
+    }
+    
+    tracing::info!("Commande {} modifiée par l'utilisateur ({}h après création) - Nouveau total: {}€", 
+                  order_id, elapsed_hours, total as f64 / 100.0);
+    
+    Ok(Json(UpdateOrderResponse {
+        order: order.clone(),
+        client_secret: payment_intent.client_secret.unwrap_or_default(),
+    }))
+}
+
+// Une commande reste modifiable tant que la préparation n'a pas commencé
+// et que le paiement n'a pas abouti
+fn ensure_editable(order: &Order) -> Result<(), (StatusCode, Json<ApiError>)> {
+    fulfillment::ensure_editable(order)?;
+    
+    if matches!(order.status, OrderStatus::Pending | OrderStatus::Processing | OrderStatus::Failed) {
+        Ok(())
+    } else {
+        Err(order_state::TransitionError::Forbidden {
+            from: order.status.clone(),
+            to: OrderStatus::Processing,
+        }.into())
+    }
+}
+
+// Mettre le PaymentIntent au nouveau montant; s'il n'est plus modifiable,
+// en créer un nouveau et annuler l'ancien. Retourne (PaymentIntent, remplacé?).
+async fn reprice_payment_intent(
+    state: &AppState,
+    order_id: &str,
+    current_pi: Option<&str>,
+    amount: i64,
+    shipping_address: Option<&ShippingAddress>,
+    capture_method: CaptureMethod,
+) -> Result<(stripe::PaymentIntent, bool), (StatusCode, Json<ApiError>)> {
+    use stripe::PaymentIntentStatus;
+    
+    let stripe_error = |e: stripe::StripeError| (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ApiError { error: format!("Erreur Stripe: {}", e) })
+    );
+    
+    if let Some(pi_id) = current_pi {
+        let current = stripe_service::retrieve_payment_intent(&state.stripe_client, pi_id)
+            .await
+            .map_err(stripe_error)?;
+        
+        match current.status {
+            // Trop tard: le paiement a abouti ou est en cours
+            PaymentIntentStatus::Succeeded
+            | PaymentIntentStatus::Processing
+            | PaymentIntentStatus::RequiresCapture => {
+                return Err((
+                    StatusCode::CONFLICT,
+                    Json(ApiError { error: "Paiement déjà effectué ou en cours, modification impossible".to_string() })
+                ));
+            }
+            PaymentIntentStatus::Canceled => {}
+            _ => {
+                match stripe_service::update_payment_intent_amount(&state.stripe_client, pi_id, amount).await {
+                    Ok(updated) => return Ok((updated, false)),
+                    Err(e) => tracing::warn!("PI {} non modifiable ({}), remplacement", pi_id, e),
+                }
+            }
         }
-        OrderStatus::Cancelled => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                Json(ApiError { error: "Commande annulée, modification impossible".to_string() })
-            ));
+    }
+    
+    let payment_intent = stripe_service::create_payment_intent(
+        &state.stripe_client,
+        amount,
+        order_id,
+        shipping_address,
+        capture_method,
+    ).await.map_err(stripe_error)?;
+    
+    // L'ancien PaymentIntent ne doit plus pouvoir être payé: sinon, abandonner
+    // la modification et le nouveau PaymentIntent
+    if let Some(pi_id) = current_pi {
+        if let Err(e) = cancel_payment_intent(state, pi_id).await {
+            if let Err(cancel_error) = stripe_service::cancel_payment_intent(&state.stripe_client, payment_intent.id.as_str()).await {
+                tracing::warn!("Nouveau PI {} non annulé: {}", payment_intent.id, cancel_error);
+            }
+            return Err(e);
         }
-        _ => {}
     }
     
-    // Créer un panier temporaire pour valider les nouveaux items
-    let temp_cart = Cart {
-        user_id: order.user_id.clone(),
-        items: req.items.clone(),
-        created_at: order.created_at,
+    Ok((payment_intent, true))
+}
+
+// Annuler un PaymentIntent sur Stripe; déjà annulé compte comme un succès
+async fn cancel_payment_intent(state: &AppState, payment_intent_id: &str) -> Result<(), (StatusCode, Json<ApiError>)> {
+    let Err(e) = stripe_service::cancel_payment_intent(&state.stripe_client, payment_intent_id).await else {
+        return Ok(());
     };
     
-    // Valider le nouveau panier (stock, etc.) avec le helper
-    let calc = calculate_cart(&temp_cart, &state.products, true)?;
+    // Course possible: le paiement a pu aboutir au même moment
+    let status = stripe_service::retrieve_payment_intent(&state.stripe_client, payment_intent_id)
+        .await
+        .map(|pi| pi.status)
+        .ok();
     
-    // Mettre à jour la commande
-    order.items = calc.items;
-    order.total = calc.total;
-    order.updated_at = now;
-    order.status = OrderStatus::Pending; // Réinitialiser si besoin d'un nouveau paiement
+    match status {
+        Some(stripe::PaymentIntentStatus::Canceled) => Ok(()),
+        Some(stripe::PaymentIntentStatus::Succeeded) => Err((
+            StatusCode::CONFLICT,
+            Json(ApiError { 
+                error: "Le paiement vient d'aboutir: la commande ne peut plus changer, demandez un remboursement".to_string()
+            })
+        )),
+        _ => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiError { error: format!("Erreur annulation paiement: {}", e) })
+        )),
+    }
+}
+
+/// Lister les paniers abandonnés avec leur valeur (admin)
+///
+/// Un client dont une commande est en cours de paiement n'a pas abandonné son achat.
+pub async fn list_abandoned_carts(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<serde_json::Value>>, (StatusCode, Json<ApiError>)> {
+    let paying: HashSet<String> = state.orders
+        .iter()
+        .filter(|order| matches!(order.status, OrderStatus::Pending | OrderStatus::Processing))
+        .map(|order| order.user_id.clone())
+        .collect();
+    let mut carts = vec![];
     
-    tracing::warn!("⚠️ Commande {} modifiée par l'utilisateur ({}h après création) - Nouveau total: {}€", 
-                  order_id, elapsed_hours, calc.total as f64 / 100.0);
-    tracing::warn!("📢 ALERTE LOGISTIQUE: Commande {} modifiée!", order_id);
+    for cart in state.carts.iter().filter(|cart| cart.abandoned_at.is_some() && !paying.contains(&cart.user_id)) {
+        // Un produit retiré du catalogue ne doit pas masquer tout le rapport
+        let value = calculate_cart(&cart, &state.products, false)
+            .map(|calc| calc.total)
+            .unwrap_or(0);
+        
+        carts.push(serde_json::json!({
+            "user_id": cart.user_id,
+            "items": cart.items,
+            "value": value,
+            "last_activity": cart.updated_at,
+            "abandoned_at": cart.abandoned_at,
+        }));
+    }
     
-    Ok(Json(order.clone()))
+    tracing::info!("🛒 {} paniers abandonnés", carts.len());
+    
+    Ok(Json(carts))
 }
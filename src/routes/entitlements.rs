@@ -0,0 +1,62 @@
+// Vérification des droits d'accès, pour les autres services (lecteur, apps, ...)
+
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
+use serde::Deserialize;
+
+use crate::models::*;
+use crate::services::entitlements;
+use crate::state::AppState;
+
+#[derive(Deserialize)]
+pub struct AccessCheckQuery {
+    user_id: String,
+    entitlement: String,
+}
+
+/// L'utilisateur a-t-il accès à ce contenu ?
+///
+/// La réponse peut être mise en cache (`Cache-Control`), jamais au-delà de la
+/// date où la décision peut changer (fin de période, fin de période de grâce).
+pub async fn check_access(
+    State(state): State<AppState>,
+    Query(query): Query<AccessCheckQuery>,
+) -> Result<Response, (StatusCode, Json<ApiError>)> {
+    // Un droit qu'aucune formule ne déclare est sans doute une faute de frappe
+    let known = state.subscription_plans.iter()
+        .any(|plan| plan.entitlements.contains(&query.entitlement));
+    if !known {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError { error: format!("Droit d'accès inconnu: {}", query.entitlement) })
+        ));
+    }
+    
+    let candidates: Vec<(UserSubscription, Vec<String>)> = state.subscriptions.iter()
+        .filter(|sub| sub.user_id == query.user_id)
+        .map(|sub| {
+            let plan_entitlements = state.subscription_plans.get(&sub.plan_id)
+                .map(|plan| plan.entitlements.clone())
+                .unwrap_or_default();
+            (sub.clone(), plan_entitlements)
+        })
+        .collect();
+    
+    let check = entitlements::check_access(
+        &query.user_id,
+        &query.entitlement,
+        candidates.iter().map(|(sub, names)| (sub, names.as_slice())),
+        Utc::now(),
+    );
+    let max_age = entitlements::cache_max_age(&check, state.config.entitlement_cache_secs);
+    
+    Ok((
+        [(header::CACHE_CONTROL, format!("private, max-age={}", max_age))],
+        Json(check),
+    ).into_response())
+}
@@ -0,0 +1,46 @@
+// Exports pour la comptabilité (admin)
+
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::models::*;
+use crate::services::accounting::{self, ExportFormat};
+use crate::state::AppState;
+
+#[derive(Deserialize)]
+pub struct AccountingExportQuery {
+    from: DateTime<Utc>,
+    to: DateTime<Utc>, // Exclu
+    #[serde(default)]
+    format: ExportFormat,
+}
+
+/// Export comptable d'une période en CSV ou JSON Lines, envoyé en flux
+pub async fn export_accounting(
+    State(state): State<AppState>,
+    Query(query): Query<AccountingExportQuery>,
+) -> Result<Response, (StatusCode, Json<ApiError>)> {
+    let stream = accounting::export_stream(state, query.from, query.to, query.format)?;
+    
+    let filename = format!(
+        "export-comptable-{}-{}.{}",
+        query.from.format("%Y%m%d"),
+        query.to.format("%Y%m%d"),
+        query.format.extension(),
+    );
+    
+    Ok((
+        [
+            (header::CONTENT_TYPE, query.format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        Body::from_stream(stream),
+    ).into_response())
+}
@@ -0,0 +1,157 @@
+// Routes logistique: avancement de la préparation et des expéditions
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::models::*;
+use crate::routes::{captures, refunds as refund_routes};
+use crate::routes::validation::ValidatedJson;
+use crate::services::{fulfillment, refunds};
+use crate::state::AppState;
+
+/// Faire avancer la logistique d'une commande (préparation, expédition, livraison, retour)
+///
+/// En capture manuelle, l'expédition déclenche la capture du paiement autorisé.
+/// La réception d'un retour rembourse le solde et remet les articles en stock.
+pub async fn advance_fulfillment(
+    State(state): State<AppState>,
+    Path(order_id): Path<String>,
+    ValidatedJson(req): ValidatedJson<AdvanceFulfillmentRequest>,
+) -> Result<Json<Order>, (StatusCode, Json<ApiError>)> {
+    let shipment = match (req.status, &req.carrier, &req.tracking_number) {
+        (FulfillmentStatus::Shipped, Some(carrier), Some(tracking_number)) => Some(Shipment {
+            id: Uuid::new_v4().to_string(),
+            carrier: carrier.clone(),
+            tracking_number: tracking_number.clone(),
+            tracking_url: req.tracking_url.clone(),
+            shipped_at: Utc::now(),
+            delivered_at: None,
+        }),
+        (FulfillmentStatus::Shipped, _, _) => {
+            return Err(fulfillment::FulfillmentError::MissingTracking.into());
+        }
+        _ => None,
+    };
+    
+    // Aucune autre opération (capture, remboursement, modification) pendant la transition
+    let _operation = state.begin_order_operation(&order_id)?;
+    
+    // Vérifier l'étape avant de capturer quoi que ce soit
+    let (capture_needed, refund_on_return) = {
+        let order = state.orders.get(&order_id)
+            .ok_or_else(|| (
+                StatusCode::NOT_FOUND,
+                Json(ApiError { error: "Commande non trouvée".to_string() })
+            ))?;
+        
+        fulfillment::check_advance(&order, req.status)?;
+        
+        // Un retour n'est enregistré que sur un paiement encaissé (ou déjà remboursé)
+        let refund_on_return = if req.status == FulfillmentStatus::Returned
+            && order.status != OrderStatus::Refunded
+        {
+            match refunds::plan_refund(&order, None) {
+                Ok(_) => true,
+                Err(refunds::RefundError::NothingToRefund) => false,
+                Err(e) => return Err(e.into()),
+            }
+        } else {
+            false
+        };
+        
+        (
+            req.status == FulfillmentStatus::Shipped
+                && order.status == OrderStatus::Authorized
+                && order.authorization.as_ref().is_some_and(|a| a.captured_amount.is_none()),
+            refund_on_return,
+        )
+    };
+    
+    // Débiter le client au moment où le colis part
+    if capture_needed {
+        captures::capture_payment(&state, &order_id, None).await?;
+    }
+    
+    {
+        let mut order = state.orders.get_mut(&order_id)
+            .ok_or_else(|| (
+                StatusCode::NOT_FOUND,
+                Json(ApiError { error: "Commande non trouvée".to_string() })
+            ))?;
+        
+        let reason = req.reason.clone().unwrap_or_else(|| "Mise à jour logistique".to_string());
+        if let Err(e) = fulfillment::advance(&mut order, req.status, shipment, &reason) {
+            // L'opération est exclusive: n'arrive que si la commande a changé par un webhook
+            if capture_needed {
+                tracing::error!("Commande {} capturée mais expédition non enregistrée: {}", order_id, e);
+                return Err((
+                    StatusCode::CONFLICT,
+                    Json(ApiError { error: format!("Paiement capturé, mais expédition non enregistrée: {}", e) })
+                ));
+            }
+            return Err(e.into());
+        }
+    }
+    
+    // Rembourser le solde et remettre en stock ce qui n'a pas encore été remboursé
+    if refund_on_return {
+        let reason = Some(req.reason.clone().unwrap_or_else(|| "Retour reçu".to_string()));
+        if let Err((status, Json(error))) =
+            refund_routes::refund_payment(&state, &order_id, None, true, reason).await
+        {
+            tracing::error!("Retour de la commande {} enregistré sans remboursement: {}", order_id, error.error);
+            return Err((
+                status,
+                Json(ApiError { error: format!("Retour enregistré, mais remboursement en échec (à relancer): {}", error.error) })
+            ));
+        }
+    }
+    
+    let order = state.orders.get(&order_id)
+        .ok_or_else(|| (
+            StatusCode::NOT_FOUND,
+            Json(ApiError { error: "Commande non trouvée".to_string() })
+        ))?;
+    
+    tracing::info!("📦 Commande {} → {:?}", order_id, req.status);
+    match req.status {
+        FulfillmentStatus::Shipped => {
+            if let Some(shipment) = order.shipments.last() {
+                println!("\n NOTIFICATION CLIENT: Votre commande {} a été expédiée ({} - suivi {})",
+                        order_id, shipment.carrier, shipment.tracking_number);
+            }
+        }
+        FulfillmentStatus::Delivered => {
+            println!("\n NOTIFICATION CLIENT: Votre commande {} a été livrée", order_id);
+        }
+        FulfillmentStatus::Returned => {
+            println!("\n NOTIFICATION CLIENT: Le retour de votre commande {} a bien été reçu", order_id);
+        }
+        _ => {}
+    }
+    
+    Ok(Json(order.clone()))
+}
+
+/// Lister les demandes de retour en attente du colis, les plus anciennes d'abord (admin)
+///
+/// Passer la commande en `returned` à réception rembourse et remet en stock.
+pub async fn list_return_requests(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<Order>>, (StatusCode, Json<ApiError>)> {
+    let mut orders: Vec<Order> = state.orders
+        .iter()
+        .filter(|entry| {
+            entry.return_requested_at.is_some() && entry.fulfillment_status != FulfillmentStatus::Returned
+        })
+        .map(|entry| entry.value().clone())
+        .collect();
+    orders.sort_by_key(|order| order.return_requested_at);
+    
+    Ok(Json(orders))
+}
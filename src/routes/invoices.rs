@@ -0,0 +1,66 @@
+// Reçus et factures des commandes payées (HTML ou PDF)
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+
+use crate::models::*;
+use crate::services::documents::{self, DocumentFormat, DocumentKind};
+use crate::services::invoices;
+use crate::state::AppState;
+
+#[derive(Deserialize)]
+pub struct DocumentQuery {
+    #[serde(default)]
+    format: DocumentFormat,
+}
+
+/// Facture numérotée d'une commande (`?format=pdf` pour le PDF)
+pub async fn get_invoice(
+    State(state): State<AppState>,
+    Path(order_id): Path<String>,
+    Query(query): Query<DocumentQuery>,
+) -> Result<Response, (StatusCode, Json<ApiError>)> {
+    document_response(&state, &order_id, DocumentKind::Invoice, query.format)
+}
+
+/// Reçu de paiement d'une commande
+pub async fn get_receipt(
+    State(state): State<AppState>,
+    Path(order_id): Path<String>,
+    Query(query): Query<DocumentQuery>,
+) -> Result<Response, (StatusCode, Json<ApiError>)> {
+    document_response(&state, &order_id, DocumentKind::Receipt, query.format)
+}
+
+fn document_response(
+    state: &AppState,
+    order_id: &str,
+    kind: DocumentKind,
+    format: DocumentFormat,
+) -> Result<Response, (StatusCode, Json<ApiError>)> {
+    let order = state.orders.get(order_id)
+        .ok_or_else(|| (
+            StatusCode::NOT_FOUND,
+            Json(ApiError { error: "Commande non trouvée".to_string() })
+        ))?;
+    
+    // Émise au paiement; rattrapage pour les commandes payées avant son introduction
+    let invoice = invoices::issue_invoice(state, &order)?;
+    drop(order);
+    
+    let attachment = documents::attachment(&invoice, kind, format);
+    let disposition = format!("inline; filename=\"{}\"", attachment.filename);
+    
+    Ok((
+        [
+            (header::CONTENT_TYPE, attachment.content_type.to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        attachment.content,
+    ).into_response())
+}
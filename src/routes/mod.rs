@@ -1,4 +1,13 @@
+pub mod captures;
 pub mod cart;
+pub mod fulfillment;
+pub mod invoices;
+pub mod policies;
+pub mod products;
+pub mod refunds;
 pub mod subscriptions;
 pub mod payment_methods;
 pub mod webhooks;
+pub mod exports;
+pub mod entitlements;
+pub mod validation;
@@ -5,18 +5,17 @@ use axum::{
     http::StatusCode,
     Json,
 };
-use chrono::Utc;
 use serde::Deserialize;
-use uuid::Uuid;
 
 use crate::models::*;
+use crate::routes::validation::ValidatedJson;
 use crate::services::stripe_service;
 use crate::state::AppState;
 
 /// Configurer un nouveau moyen de paiement
 pub async fn setup_payment_method(
     State(state): State<AppState>,
-    Json(req): Json<SetupPaymentMethodRequest>,
+    ValidatedJson(req): ValidatedJson<SetupPaymentMethodRequest>,
 ) -> Result<Json<SetupResponse>, (StatusCode, Json<ApiError>)> {
     tracing::info!("💳 Configuration moyen de paiement pour user {}", req.user_id);
     
@@ -90,7 +89,7 @@ pub async fn delete_payment_method(
 /// Payer avec un moyen de paiement sauvegardé
 pub async fn pay_with_saved_method(
     State(state): State<AppState>,
-    Json(req): Json<PayWithSavedMethodRequest>,
+    ValidatedJson(req): ValidatedJson<PayWithSavedMethodRequest>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, Json<ApiError>)> {
     // Vérifier que le moyen de paiement existe
     let payment_method = state.payment_methods.get(&req.payment_method_id)
@@ -0,0 +1,90 @@
+// Politiques d'annulation / modification: consultation et surcharges (admin)
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use std::collections::BTreeMap;
+
+use crate::models::*;
+use crate::routes::validation::ValidatedJson;
+use crate::services::policies::{self, AppliedPolicy};
+use crate::state::AppState;
+
+/// Politique par défaut et toutes les surcharges
+pub async fn get_policies(
+    State(state): State<AppState>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ApiError>)> {
+    let tiers: BTreeMap<String, PolicyOverride> = state.tier_policies.iter()
+        .map(|entry| (entry.key().clone(), entry.value().clone()))
+        .collect();
+    let products: BTreeMap<String, PolicyOverride> = state.product_policies.iter()
+        .map(|entry| (entry.key().clone(), entry.value().clone()))
+        .collect();
+    
+    Ok(Json(serde_json::json!({
+        "default": state.config.order_policy,
+        "tiers": tiers,
+        "products": products,
+    })))
+}
+
+/// Définir la surcharge d'un niveau client
+pub async fn set_tier_policy(
+    State(state): State<AppState>,
+    Path(tier): Path<String>,
+    ValidatedJson(rule): ValidatedJson<PolicyOverride>,
+) -> Result<Json<PolicyOverride>, (StatusCode, Json<ApiError>)> {
+    state.tier_policies.insert(tier.clone(), rule.clone());
+    tracing::info!("Politique du niveau {} mise à jour", tier);
+    
+    Ok(Json(rule))
+}
+
+/// Définir la surcharge d'un produit
+pub async fn set_product_policy(
+    State(state): State<AppState>,
+    Path(product_id): Path<String>,
+    ValidatedJson(rule): ValidatedJson<PolicyOverride>,
+) -> Result<Json<PolicyOverride>, (StatusCode, Json<ApiError>)> {
+    if !state.products.contains_key(&product_id) {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ApiError { error: "Produit non trouvé".to_string() })
+        ));
+    }
+    
+    state.product_policies.insert(product_id.clone(), rule.clone());
+    tracing::info!("Politique du produit {} mise à jour", product_id);
+    
+    Ok(Json(rule))
+}
+
+/// Affecter un niveau à un client
+pub async fn set_customer_tier(
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+    ValidatedJson(req): ValidatedJson<SetCustomerTierRequest>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ApiError>)> {
+    state.customer_tiers.insert(user_id.clone(), req.tier.clone());
+    
+    Ok(Json(serde_json::json!({
+        "user_id": user_id,
+        "tier": req.tier,
+    })))
+}
+
+/// Politique qui s'applique à une commande
+pub async fn get_order_policy(
+    State(state): State<AppState>,
+    Path(order_id): Path<String>,
+) -> Result<Json<AppliedPolicy>, (StatusCode, Json<ApiError>)> {
+    let order = state.orders.get(&order_id)
+        .ok_or_else(|| (
+            StatusCode::NOT_FOUND,
+            Json(ApiError { error: "Commande non trouvée".to_string() })
+        ))?;
+    
+    Ok(Json(policies::policy_for_order(&state, &order)))
+}
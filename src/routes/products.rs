@@ -0,0 +1,162 @@
+// Gestion du catalogue produits (admin + catalogue public)
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
+
+use crate::models::*;
+use crate::routes::validation::ValidatedJson;
+use crate::state::AppState;
+
+// Vérifier les invariants qui portent sur plusieurs champs
+// (les contraintes par champ sont déclarées sur les requêtes)
+fn validate_product(product: &Product) -> Result<(), (StatusCode, Json<ApiError>)> {
+    let duplicate_sku = product.variants.iter().enumerate()
+        .find(|(i, v)| product.variants[..*i].iter().any(|other| other.sku == v.sku))
+        .map(|(_, v)| v.sku.clone());
+    
+    match duplicate_sku {
+        Some(sku) => Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError { error: format!("SKU en double: {}", sku) })
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Créer un produit (admin)
+pub async fn create_product(
+    State(state): State<AppState>,
+    ValidatedJson(req): ValidatedJson<CreateProductRequest>,
+) -> Result<Json<Product>, (StatusCode, Json<ApiError>)> {
+    let product = Product {
+        id: req.id,
+        name: req.name,
+        price: req.price,
+        stock: req.stock,
+        description: req.description,
+        weight_grams: req.weight_grams,
+        archived: false,
+        variants: req.variants,
+    };
+    
+    validate_product(&product)?;
+    
+    // L'identifiant doit être unique, y compris parmi les produits archivés
+    match state.products.entry(product.id.clone()) {
+        dashmap::mapref::entry::Entry::Occupied(_) => Err((
+            StatusCode::CONFLICT,
+            Json(ApiError { error: format!("Le produit {} existe déjà", product.id) })
+        )),
+        dashmap::mapref::entry::Entry::Vacant(entry) => {
+            entry.insert(product.clone());
+            tracing::info!("📦 Produit créé: {} ({})", product.name, product.id);
+            Ok(Json(product))
+        }
+    }
+}
+
+/// Modifier un produit (admin)
+pub async fn update_product(
+    State(state): State<AppState>,
+    Path(product_id): Path<String>,
+    ValidatedJson(req): ValidatedJson<UpdateProductRequest>,
+) -> Result<Json<Product>, (StatusCode, Json<ApiError>)> {
+    let mut product = state.products.get_mut(&product_id)
+        .ok_or_else(|| (
+            StatusCode::NOT_FOUND,
+            Json(ApiError { error: "Produit non trouvé".to_string() })
+        ))?;
+    
+    // Valider sur une copie pour ne rien modifier en cas d'erreur
+    let mut updated = product.clone();
+    if let Some(name) = req.name { updated.name = name; }
+    if let Some(price) = req.price { updated.price = price; }
+    if let Some(stock) = req.stock { updated.stock = stock; }
+    if let Some(description) = req.description { updated.description = description; }
+    if let Some(weight_grams) = req.weight_grams { updated.weight_grams = weight_grams; }
+    if let Some(variants) = req.variants { updated.variants = variants; }
+    
+    validate_product(&updated)?;
+    *product = updated;
+    
+    tracing::info!("📦 Produit modifié: {}", product_id);
+    
+    Ok(Json(product.clone()))
+}
+
+/// Archiver un produit (admin)
+///
+/// Le produit reste consultable pour les commandes passées mais ne peut plus
+/// être ajouté au panier.
+pub async fn archive_product(
+    State(state): State<AppState>,
+    Path(product_id): Path<String>,
+) -> Result<Json<Product>, (StatusCode, Json<ApiError>)> {
+    let mut product = state.products.get_mut(&product_id)
+        .ok_or_else(|| (
+            StatusCode::NOT_FOUND,
+            Json(ApiError { error: "Produit non trouvé".to_string() })
+        ))?;
+    
+    product.archived = true;
+    
+    tracing::info!("🗄️ Produit archivé: {}", product_id);
+    
+    Ok(Json(product.clone()))
+}
+
+/// Lister tous les produits, archivés compris (admin)
+pub async fn list_all_products(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<Product>>, (StatusCode, Json<ApiError>)> {
+    let mut products: Vec<Product> = state.products
+        .iter()
+        .map(|entry| entry.value().clone())
+        .collect();
+    products.sort_by(|a, b| a.id.cmp(&b.id));
+    
+    Ok(Json(products))
+}
+
+#[derive(Deserialize)]
+pub struct CatalogQuery {
+    search: Option<String>,
+    min_price: Option<i64>,
+    max_price: Option<i64>,
+    #[serde(default)]
+    in_stock: bool,
+}
+
+/// Catalogue public: produits actifs avec recherche et filtres
+pub async fn list_products(
+    State(state): State<AppState>,
+    Query(query): Query<CatalogQuery>,
+) -> Result<Json<Vec<Product>>, (StatusCode, Json<ApiError>)> {
+    let search = query.search.map(|s| s.to_lowercase());
+    
+    let mut products: Vec<Product> = state.products
+        .iter()
+        .filter(|entry| {
+            let product = entry.value();
+            !product.archived
+                && search.as_ref().is_none_or(|s| {
+                    product.name.to_lowercase().contains(s)
+                        || product.description.to_lowercase().contains(s)
+                })
+                // Un produit correspond si l'une de ses variantes est dans la fourchette
+                && product.unit_prices().into_iter().any(|price| {
+                    query.min_price.is_none_or(|min| price >= min)
+                        && query.max_price.is_none_or(|max| price <= max)
+                })
+                && (!query.in_stock || product.total_stock() > 0)
+        })
+        .map(|entry| entry.value().clone())
+        .collect();
+    products.sort_by(|a, b| a.id.cmp(&b.id));
+    
+    Ok(Json(products))
+}
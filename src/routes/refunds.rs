@@ -0,0 +1,122 @@
+// Remboursements totaux ou partiels des commandes payées
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::models::*;
+use crate::routes::validation::ValidatedJson;
+use crate::services::{inventory, order_state, refunds, stripe_service};
+use crate::state::AppState;
+
+/// Rembourser une commande (totalité du solde ou articles choisis)
+///
+/// Un seul remboursement à la fois par commande: le suivant est calculé
+/// une fois le précédent enregistré.
+pub async fn refund_order(
+    State(state): State<AppState>,
+    Path(order_id): Path<String>,
+    ValidatedJson(req): ValidatedJson<RefundOrderRequest>,
+) -> Result<Json<OrderRefund>, (StatusCode, Json<ApiError>)> {
+    let _operation = state.begin_order_operation(&order_id)?;
+    
+    refund_payment(&state, &order_id, req.items.as_deref(), req.restock, req.reason).await.map(Json)
+}
+
+/// Remboursement partagé par l'endpoint et la réception d'un retour
+///
+/// L'appelant a marqué l'opération en cours sur la commande.
+pub async fn refund_payment(
+    state: &AppState,
+    order_id: &str,
+    items: Option<&[RefundItem]>,
+    restock: bool,
+    reason: Option<String>,
+) -> Result<OrderRefund, (StatusCode, Json<ApiError>)> {
+    // Préparer le remboursement sans garder la commande verrouillée pendant l'appel Stripe
+    let (plan, payment_intent_id) = {
+        let order = state.orders.get(order_id)
+            .ok_or_else(|| (
+                StatusCode::NOT_FOUND,
+                Json(ApiError { error: "Commande non trouvée".to_string() })
+            ))?;
+        
+        let plan = refunds::plan_refund(&order, items)?;
+        let payment_intent_id = order.payment_intent_id.clone()
+            .ok_or_else(|| (
+                StatusCode::CONFLICT,
+                Json(ApiError { error: "Aucun paiement associé à cette commande".to_string() })
+            ))?;
+        
+        (plan, payment_intent_id)
+    };
+    
+    // Stripe refuse de toute façon un montant supérieur au montant capturé
+    let stripe_refund = stripe_service::create_refund(
+        &state.stripe_client,
+        &payment_intent_id,
+        plan.amount,
+        order_id,
+        &plan.idempotency_key(order_id),
+    ).await.map_err(|e| (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ApiError { error: format!("Erreur remboursement: {}", e) })
+    ))?;
+    
+    let refund = OrderRefund {
+        id: Uuid::new_v4().to_string(),
+        stripe_refund_id: stripe_refund.id.to_string(),
+        amount: plan.amount,
+        items: plan.items,
+        restocked: restock,
+        reason,
+        created_at: Utc::now(),
+    };
+    
+    let mut order = state.orders.get_mut(order_id)
+        .ok_or_else(|| (
+            StatusCode::NOT_FOUND,
+            Json(ApiError { error: "Commande non trouvée".to_string() })
+        ))?;
+    
+    order.refunds.push(refund.clone());
+    order.updated_at = Utc::now();
+    
+    let new_status = if plan.fully_refunded {
+        OrderStatus::Refunded
+    } else {
+        OrderStatus::PartiallyRefunded
+    };
+    if order.status != new_status {
+        let reason = format!("Remboursement de {}€", refund.amount as f64 / 100.0);
+        if let Err(e) = order_state::transition(&mut order, new_status, StatusChangeSource::Admin, &reason) {
+            // Le remboursement Stripe est fait: on l'enregistre quoi qu'il arrive
+            tracing::warn!("Statut de la commande {} non mis à jour: {}", order_id, e);
+        }
+    }
+    
+    // Remise en stock des articles retournés
+    if refund.restocked {
+        for item in &refund.items {
+            if let Some(stock) = inventory::adjust_stock(
+                &state.products,
+                &item.product_id,
+                item.variant_sku.as_deref(),
+                item.quantity,
+            ) {
+                tracing::info!("Remise en stock: {} - nouveau stock: {}", item.product_id, stock);
+            }
+        }
+    }
+    
+    tracing::info!("💸 Remboursement {} sur commande {} - Montant: {}€", 
+                  refund.stripe_refund_id, order_id, refund.amount as f64 / 100.0);
+    println!("\n NOTIFICATION CLIENT: Un remboursement de {}€ a été effectué sur votre commande {}", 
+            refund.amount as f64 / 100.0, order_id);
+    
+    Ok(refund)
+}
@@ -1,80 +1,94 @@
 // EXERCICE 2: Routes pour les abonnements récurrents
 
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     Json,
 };
-use chrono::Utc;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
 use uuid::Uuid;
 
 use crate::models::*;
-use crate::services::stripe_service;
+use crate::routes::validation::ValidatedJson;
+use crate::services::{stripe_service, subscriptions, usage};
 use crate::state::AppState;
 
 /// Créer un nouvel abonnement
 pub async fn create_subscription(
     State(state): State<AppState>,
-    Json(req): Json<CreateSubscriptionRequest>,
+    ValidatedJson(req): ValidatedJson<CreateSubscriptionRequest>,
 ) -> Result<Json<SubscriptionResponse>, (StatusCode, Json<ApiError>)> {
     // Vérifier que le plan existe
     let plan = state.subscription_plans.get(&req.plan_id)
+        .map(|plan| plan.clone())
         .ok_or_else(|| (
             StatusCode::NOT_FOUND,
             Json(ApiError { error: "Plan d'abonnement non trouvé".to_string() })
         ))?;
     
-    tracing::info!("Création abonnement {} pour user {}", plan.name, req.user_id);
+    let amount = subscriptions::plan_price(&plan, req.interval)?;
+    let metered = subscriptions::metered_option(&plan, req.metered)?;
     
-    // Créer un client Stripe
-    let customer = stripe_service::create_customer(
-        &state.stripe_client,
-        &req.email,
-        &req.user_id,
-    ).await.map_err(|e| (
-        StatusCode::INTERNAL_SERVER_ERROR,
-        Json(ApiError { error: format!("Erreur création client: {}", e) })
-    ))?;
+    tracing::info!("Création abonnement {} ({:?}) pour user {}", plan.name, req.interval, req.user_id);
     
-    // Créer un produit Stripe pour l'abonnement
-    let price_id = stripe_service::create_subscription_price(
-        &state.stripe_client,
-        &plan.name,
-        plan.price,
-    ).await.map_err(|e| (
-        StatusCode::INTERNAL_SERVER_ERROR,
-        Json(ApiError { error: format!("Erreur création prix: {}", e) })
-    ))?;
+    // Un seul essai gratuit par utilisateur: réservé avant les appels Stripe pour
+    // que deux créations simultanées n'en obtiennent pas chacune un
+    let user_subscriptions: Vec<UserSubscription> = state.subscriptions.iter()
+        .filter(|sub| sub.user_id == req.user_id)
+        .map(|sub| sub.clone())
+        .collect();
+    let trial_days = subscriptions::trial_days_for(&plan, &user_subscriptions)
+        .filter(|_| state.reserve_trial(&req.user_id));
     
-    // Créer l'abonnement Stripe
-    let subscription = stripe_service::create_subscription(
-        &state.stripe_client,
-        &customer.id.to_string(),
-        &price_id,
-        Some(&req.payment_method),
-    ).await.map_err(|e| (
-        StatusCode::INTERNAL_SERVER_ERROR,
-        Json(ApiError { error: format!("Erreur création abonnement: {}", e) })
-    ))?;
+    let subscription = create_stripe_subscription(&state, &req, &plan, amount, metered.as_ref(), trial_days).await
+        .inspect_err(|_| {
+            // Rien n'a été créé: l'essai reste disponible
+            if trial_days.is_some() {
+                state.release_trial(&req.user_id);
+            }
+        })?;
     
-    // Enregistrer l'abonnement
+    // Enregistrer l'abonnement avec les période et statut renvoyés par Stripe
     let sub_id = Uuid::new_v4().to_string();
-    let user_sub = UserSubscription {
+    let snapshot = subscriptions::snapshot_from_stripe(&subscription);
+    let now = Utc::now();
+    let mut user_sub = UserSubscription {
         id: sub_id.clone(),
         user_id: req.user_id.clone(),
         plan_id: req.plan_id.clone(),
         stripe_subscription_id: subscription.id.to_string(),
-        status: SubscriptionStatus::Active,
-        current_period_end: Utc::now() + chrono::Duration::days(30),
+        status: snapshot.status.clone(),
+        current_period_start: snapshot.current_period_start,
+        current_period_end: snapshot.current_period_end,
         cancel_at_period_end: false,
-        created_at: Utc::now(),
+        created_at: now,
         payment_method_id: None,
+        plan_history: vec![],
+        trial_end: None,
+        cancellation: None,
+        dunning: None,
+        latest_invoice: None,
+        synced_at: None,
+        interval: req.interval,
+        scheduled_interval: None,
+        interval_schedule_id: None,
+        pause: None,
+        metered: metered.zip(snapshot.metered_item_id.clone()).map(|(price, stripe_item_id)| MeteredItem {
+            stripe_item_id,
+            unit_amount: price.unit_amount,
+            unit_label: price.unit_label,
+        }),
     };
+    subscriptions::apply_snapshot(&mut user_sub, &snapshot, now);
     
     state.subscriptions.insert(sub_id.clone(), user_sub);
     
-    tracing::info!("Abonnement créé: {} - Plan: {} ({}€/mois)", 
-                  sub_id, plan.name, plan.price as f64 / 100.0);
+    tracing::info!("Abonnement créé: {} - Plan: {} ({}€/{})", 
+                  sub_id, plan.name, amount as f64 / 100.0, req.interval.label());
+    if let Some(days) = trial_days {
+        tracing::info!("Essai gratuit de {} jours pour l'abonnement {}", days, sub_id);
+    }
     
     // Récupérer le client secret pour confirmer le paiement
     let client_secret = subscription.latest_invoice
@@ -96,10 +110,68 @@ pub async fn create_subscription(
     Ok(Json(SubscriptionResponse {
         subscription_id: sub_id,
         client_secret,
-        status: "active".to_string(),
+        status: snapshot.stripe_status,
     }))
 }
 
+/// Créer le client, les prix et l'abonnement Stripe
+async fn create_stripe_subscription(
+    state: &AppState,
+    req: &CreateSubscriptionRequest,
+    plan: &SubscriptionPlan,
+    amount: i64,
+    metered: Option<&MeteredPrice>,
+    trial_days: Option<i64>,
+) -> Result<stripe::Subscription, (StatusCode, Json<ApiError>)> {
+    // Créer un client Stripe
+    let customer = stripe_service::create_customer(
+        &state.stripe_client,
+        &req.email,
+        &req.user_id,
+    ).await.map_err(|e| (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ApiError { error: format!("Erreur création client: {}", e) })
+    ))?;
+    
+    // Créer un produit Stripe pour l'abonnement
+    let price_id = stripe_service::create_subscription_price(
+        &state.stripe_client,
+        &plan.name,
+        amount,
+        req.interval,
+    ).await.map_err(|e| (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ApiError { error: format!("Erreur création prix: {}", e) })
+    ))?;
+    
+    // Option facturée à l'usage, si souscrite, sur la même période que l'abonnement
+    let metered_price_id = match metered {
+        Some(metered) => Some(stripe_service::create_metered_price(
+            &state.stripe_client,
+            &format!("{} - {}", plan.name, metered.unit_label),
+            metered.unit_amount,
+            req.interval,
+        ).await.map_err(|e| (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiError { error: format!("Erreur création prix à l'usage: {}", e) })
+        ))?),
+        None => None,
+    };
+    
+    // Créer l'abonnement Stripe
+    stripe_service::create_subscription(
+        &state.stripe_client,
+        customer.id.as_ref(),
+        &price_id,
+        metered_price_id.as_deref(),
+        Some(&req.payment_method),
+        trial_days,
+    ).await.map_err(|e| (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ApiError { error: format!("Erreur création abonnement: {}", e) })
+    ))
+}
+
 /// Récupérer un abonnement
 pub async fn get_subscription(
     State(state): State<AppState>,
@@ -114,33 +186,693 @@ pub async fn get_subscription(
     Ok(Json(subscription.clone()))
 }
 
-/// Annuler un abonnement
-pub async fn cancel_subscription(
+#[derive(Debug, Deserialize)]
+pub struct CancelSubscriptionQuery {
+    #[serde(default)]
+    pub mode: SubscriptionCancelMode,
+}
+
+#[derive(Deserialize)]
+pub struct ListSubscriptionsQuery {
+    user_id: String,
+}
+
+/// Lister les abonnements d'un utilisateur (plus récents d'abord)
+pub async fn list_subscriptions(
+    State(state): State<AppState>,
+    Query(query): Query<ListSubscriptionsQuery>,
+) -> Result<Json<Vec<UserSubscription>>, (StatusCode, Json<ApiError>)> {
+    let mut subscriptions: Vec<UserSubscription> = state.subscriptions
+        .iter()
+        .filter(|entry| entry.value().user_id == query.user_id)
+        .map(|entry| entry.value().clone())
+        .collect();
+    subscriptions.sort_by_key(|subscription| std::cmp::Reverse(subscription.created_at));
+    
+    Ok(Json(subscriptions))
+}
+
+/// Recharger un abonnement depuis Stripe (période, statut, dernière facture)
+pub async fn refresh_subscription(
     State(state): State<AppState>,
     Path(sub_id): Path<String>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<ApiError>)> {
+) -> Result<Json<UserSubscription>, (StatusCode, Json<ApiError>)> {
+    let stripe_subscription_id = state.subscriptions.get(&sub_id)
+        .map(|subscription| subscription.stripe_subscription_id.clone())
+        .ok_or_else(|| (
+            StatusCode::NOT_FOUND,
+            Json(ApiError { error: "Abonnement non trouvé".to_string() })
+        ))?;
+    
+    let remote = stripe_service::retrieve_subscription(&state.stripe_client, &stripe_subscription_id)
+        .await
+        .map_err(|e| (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiError { error: format!("Erreur récupération abonnement: {}", e) })
+        ))?;
+    let snapshot = subscriptions::snapshot_from_stripe(&remote);
+    
     let mut subscription = state.subscriptions.get_mut(&sub_id)
         .ok_or_else(|| (
             StatusCode::NOT_FOUND,
             Json(ApiError { error: "Abonnement non trouvé".to_string() })
         ))?;
+    subscriptions::apply_snapshot(&mut subscription, &snapshot, Utc::now());
+    
+    tracing::info!("Abonnement {} resynchronisé: {:?} jusqu'au {}",
+                  sub_id, subscription.status, subscription.current_period_end);
+    
+    Ok(Json(subscription.clone()))
+}
+
+/// Annuler un abonnement
+///
+/// Par défaut en fin de période: l'abonnement reste actif jusqu'à
+/// `current_period_end` (période déjà payée) et passe en `Cancelled` à la
+/// suppression côté Stripe. Les modes immédiats coupent l'accès tout de suite.
+pub async fn cancel_subscription(
+    State(state): State<AppState>,
+    Path(sub_id): Path<String>,
+    Query(query): Query<CancelSubscriptionQuery>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ApiError>)> {
+    let (stripe_subscription_id, interval_schedule_id) = {
+        let subscription = state.subscriptions.get(&sub_id)
+            .ok_or_else(|| (
+                StatusCode::NOT_FOUND,
+                Json(ApiError { error: "Abonnement non trouvé".to_string() })
+            ))?;
+        match query.mode {
+            SubscriptionCancelMode::AtPeriodEnd => subscriptions::check_cancel_at_period_end(&subscription)?,
+            _ => subscriptions::check_cancel_now(&subscription)?,
+        }
+        (subscription.stripe_subscription_id.clone(), subscription.interval_schedule_id.clone())
+    };
+    
+    if query.mode != SubscriptionCancelMode::AtPeriodEnd {
+        return cancel_now(&state, &sub_id, &stripe_subscription_id, query.mode).await;
+    }
+    
+    // Un abonnement piloté par un calendrier ne peut pas être annulé directement:
+    // le changement de période programmé est abandonné
+    if let Some(schedule_id) = interval_schedule_id {
+        release_interval_schedule(&state, &sub_id, &schedule_id).await?;
+    }
     
     // Annuler sur Stripe
     stripe_service::cancel_subscription(
         &state.stripe_client,
-        &subscription.stripe_subscription_id,
+        &stripe_subscription_id,
     ).await.map_err(|e| (
         StatusCode::INTERNAL_SERVER_ERROR,
         Json(ApiError { error: format!("Erreur annulation: {}", e) })
     ))?;
     
-    subscription.status = SubscriptionStatus::Cancelled;
-    subscription.cancel_at_period_end = true;
+    let active_until = {
+        let mut subscription = state.subscriptions.get_mut(&sub_id)
+            .ok_or_else(|| (
+                StatusCode::NOT_FOUND,
+                Json(ApiError { error: "Abonnement non trouvé".to_string() })
+            ))?;
+        subscription.cancel_at_period_end = true;
+        subscription.cancellation = Some(SubscriptionCancellation {
+            mode: SubscriptionCancelMode::AtPeriodEnd,
+            requested_at: Utc::now(),
+            refund_amount: 0,
+            stripe_refund_id: None,
+            refunded_at: None,
+            pending_refund: None,
+        });
+        subscription.current_period_end
+    };
+    
+    tracing::info!("Abonnement {} annulé en fin de période ({})", sub_id, active_until);
+    
+    Ok(Json(serde_json::json!({
+        "message": format!("Abonnement actif jusqu'au {}, puis annulé", active_until.format("%d/%m/%Y")),
+        "subscription_id": sub_id,
+        "active_until": active_until,
+    })))
+}
+
+// Annulation immédiate, avec remboursement au prorata de la dernière facture si demandé
+async fn cancel_now(
+    state: &AppState,
+    sub_id: &str,
+    stripe_subscription_id: &str,
+    mode: SubscriptionCancelMode,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ApiError>)> {
+    let now = Utc::now();
+    
+    // Montant à rembourser, calculé avant la résiliation (période et facture en cours)
+    let refund = if mode == SubscriptionCancelMode::ImmediateWithRefund {
+        let subscription = stripe_service::retrieve_subscription(&state.stripe_client, stripe_subscription_id)
+            .await
+            .map_err(|e| (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiError { error: format!("Erreur récupération abonnement: {}", e) })
+            ))?;
+        latest_invoice_refund(&subscription, now)
+    } else {
+        None
+    };
+    
+    stripe_service::cancel_subscription_now(&state.stripe_client, stripe_subscription_id)
+        .await
+        .map_err(|e| (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiError { error: format!("Erreur annulation: {}", e) })
+        ))?;
+    
+    // L'abonnement est résilié même si le remboursement échoue: il reste dû et peut être retenté
+    let mut refund_amount = 0;
+    let mut stripe_refund_id = None;
+    let mut refund_error = None;
+    if let Some(pending) = &refund {
+        match stripe_service::create_subscription_refund(
+            &state.stripe_client,
+            &pending.payment_intent_id,
+            pending.amount,
+            stripe_subscription_id,
+        ).await {
+            Ok(stripe_refund) => {
+                refund_amount = pending.amount;
+                stripe_refund_id = Some(stripe_refund.id.to_string());
+            }
+            Err(e) => refund_error = Some(e),
+        }
+    }
+    
+    if let Some(mut subscription) = state.subscriptions.get_mut(sub_id) {
+        subscription.status = SubscriptionStatus::Cancelled;
+        subscription.cancel_at_period_end = false;
+        subscription.current_period_end = now;
+        // Les événements antérieurs à la résiliation seront ignorés
+        subscription.synced_at = Some(now);
+        subscription.cancellation = Some(SubscriptionCancellation {
+            mode,
+            requested_at: now,
+            refund_amount,
+            stripe_refund_id: stripe_refund_id.clone(),
+            refunded_at: stripe_refund_id.as_ref().map(|_| now),
+            pending_refund: refund.filter(|_| refund_error.is_some()),
+        });
+    }
+    
+    if let Some(e) = refund_error {
+        tracing::error!("Abonnement {} résilié mais remboursement échoué (à retenter): {}", sub_id, e);
+        return Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiError { error: format!("Abonnement résilié, mais erreur remboursement (à retenter via /cancel/refund): {}", e) })
+        ));
+    }
+    
+    tracing::info!("Abonnement {} résilié immédiatement ({:?}), remboursé: {}€",
+                  sub_id, mode, refund_amount as f64 / 100.0);
+    println!("\n NOTIFICATION CLIENT: Votre abonnement a été résilié{}",
+            if refund_amount > 0 {
+                format!(". {}€ vous seront remboursés", refund_amount as f64 / 100.0)
+            } else {
+                String::new()
+            });
+    
+    Ok(Json(serde_json::json!({
+        "message": "Abonnement résilié immédiatement",
+        "subscription_id": sub_id,
+        "refund_amount": refund_amount,
+        "stripe_refund_id": stripe_refund_id,
+    })))
+}
+
+// PaymentIntent et montant à rembourser pour la facture en cours
+fn latest_invoice_refund(subscription: &stripe::Subscription, now: DateTime<Utc>) -> Option<PendingRefund> {
+    let stripe::Expandable::Object(invoice) = subscription.latest_invoice.as_ref()? else {
+        return None;
+    };
+    let payment_intent_id = invoice.payment_intent.as_ref()?.id().to_string();
+    let period_start = DateTime::<Utc>::from_timestamp(subscription.current_period_start, 0)?;
+    let period_end = DateTime::<Utc>::from_timestamp(subscription.current_period_end, 0)?;
+    let period_amount = subscriptions::period_line_amount(invoice, subscription.current_period_start)?;
+    
+    let amount = subscriptions::prorated_refund(period_amount, period_start, period_end, now);
+    (amount > 0).then_some(PendingRefund { payment_intent_id, amount })
+}
+
+/// Retenter le remboursement d'une résiliation immédiate
+///
+/// Même paiement, même montant et même clé d'idempotence: Stripe ne rembourse
+/// qu'une fois, même si la première demande avait abouti sans réponse.
+pub async fn retry_cancellation_refund(
+    State(state): State<AppState>,
+    Path(sub_id): Path<String>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ApiError>)> {
+    let (stripe_subscription_id, pending) = {
+        let subscription = state.subscriptions.get(&sub_id)
+            .ok_or_else(|| (
+                StatusCode::NOT_FOUND,
+                Json(ApiError { error: "Abonnement non trouvé".to_string() })
+            ))?;
+        let pending = subscription.cancellation.as_ref()
+            .and_then(|cancellation| cancellation.pending_refund.clone())
+            .ok_or(subscriptions::SubscriptionError::NoPendingRefund)?;
+        (subscription.stripe_subscription_id.clone(), pending)
+    };
+    
+    let stripe_refund = stripe_service::create_subscription_refund(
+        &state.stripe_client,
+        &pending.payment_intent_id,
+        pending.amount,
+        &stripe_subscription_id,
+    ).await.map_err(|e| (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ApiError { error: format!("Erreur remboursement: {}", e) })
+    ))?;
+    
+    if let Some(mut subscription) = state.subscriptions.get_mut(&sub_id) {
+        if let Some(cancellation) = subscription.cancellation.as_mut() {
+            cancellation.refund_amount = pending.amount;
+            cancellation.stripe_refund_id = Some(stripe_refund.id.to_string());
+            cancellation.refunded_at = Some(Utc::now());
+            cancellation.pending_refund = None;
+        }
+    }
     
-    tracing::info!("Abonnement annulé: {}", sub_id);
+    tracing::info!("Remboursement de résiliation {} effectué pour l'abonnement {}", stripe_refund.id, sub_id);
+    println!("\n NOTIFICATION CLIENT: {}€ vous seront remboursés suite à la résiliation de votre abonnement",
+            pending.amount as f64 / 100.0);
     
     Ok(Json(serde_json::json!({
-        "message": "Abonnement annulé avec succès",
+        "message": "Remboursement effectué",
         "subscription_id": sub_id,
+        "refund_amount": pending.amount,
+        "stripe_refund_id": stripe_refund.id.to_string(),
     })))
 }
+
+/// Reprendre un abonnement dont l'annulation est programmée
+pub async fn resume_subscription(
+    State(state): State<AppState>,
+    Path(sub_id): Path<String>,
+) -> Result<Json<UserSubscription>, (StatusCode, Json<ApiError>)> {
+    let stripe_subscription_id = {
+        let subscription = state.subscriptions.get(&sub_id)
+            .ok_or_else(|| (
+                StatusCode::NOT_FOUND,
+                Json(ApiError { error: "Abonnement non trouvé".to_string() })
+            ))?;
+        subscriptions::check_resume(&subscription, Utc::now())?;
+        subscription.stripe_subscription_id.clone()
+    };
+    
+    stripe_service::resume_subscription(
+        &state.stripe_client,
+        &stripe_subscription_id,
+    ).await.map_err(|e| (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ApiError { error: format!("Erreur reprise abonnement: {}", e) })
+    ))?;
+    
+    let mut subscription = state.subscriptions.get_mut(&sub_id)
+        .ok_or_else(|| (
+            StatusCode::NOT_FOUND,
+            Json(ApiError { error: "Abonnement non trouvé".to_string() })
+        ))?;
+    subscription.cancel_at_period_end = false;
+    subscription.cancellation = None;
+    
+    tracing::info!("Abonnement {} repris", sub_id);
+    
+    Ok(Json(subscription.clone()))
+}
+
+/// Suspendre les prélèvements (vacances, ...) sans résilier
+///
+/// Sans date de reprise, l'abonnement reste suspendu jusqu'à `unpause_subscription`.
+pub async fn pause_subscription(
+    State(state): State<AppState>,
+    Path(sub_id): Path<String>,
+    ValidatedJson(req): ValidatedJson<PauseSubscriptionRequest>,
+) -> Result<Json<UserSubscription>, (StatusCode, Json<ApiError>)> {
+    let now = Utc::now();
+    let stripe_subscription_id = {
+        let subscription = state.subscriptions.get(&sub_id)
+            .ok_or_else(|| (
+                StatusCode::NOT_FOUND,
+                Json(ApiError { error: "Abonnement non trouvé".to_string() })
+            ))?;
+        subscriptions::check_pause(&subscription, req.resumes_at, now)?;
+        subscription.stripe_subscription_id.clone()
+    };
+    
+    stripe_service::pause_subscription_collection(
+        &state.stripe_client,
+        &stripe_subscription_id,
+        req.resumes_at,
+    ).await.map_err(|e| (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ApiError { error: format!("Erreur suspension abonnement: {}", e) })
+    ))?;
+    
+    let mut subscription = state.subscriptions.get_mut(&sub_id)
+        .ok_or_else(|| (
+            StatusCode::NOT_FOUND,
+            Json(ApiError { error: "Abonnement non trouvé".to_string() })
+        ))?;
+    subscription.status = SubscriptionStatus::Paused;
+    subscription.pause = Some(SubscriptionPause { paused_at: now, resumes_at: req.resumes_at });
+    
+    match req.resumes_at {
+        Some(date) => tracing::info!("Abonnement {} suspendu jusqu'au {}", sub_id, date),
+        None => tracing::info!("Abonnement {} suspendu", sub_id),
+    }
+    
+    Ok(Json(subscription.clone()))
+}
+
+/// Reprendre les prélèvements d'un abonnement suspendu
+pub async fn unpause_subscription(
+    State(state): State<AppState>,
+    Path(sub_id): Path<String>,
+) -> Result<Json<UserSubscription>, (StatusCode, Json<ApiError>)> {
+    let stripe_subscription_id = {
+        let subscription = state.subscriptions.get(&sub_id)
+            .ok_or_else(|| (
+                StatusCode::NOT_FOUND,
+                Json(ApiError { error: "Abonnement non trouvé".to_string() })
+            ))?;
+        subscriptions::check_unpause(&subscription)?;
+        subscription.stripe_subscription_id.clone()
+    };
+    
+    let remote = stripe_service::resume_subscription_collection(
+        &state.stripe_client,
+        &stripe_subscription_id,
+    ).await.map_err(|e| (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ApiError { error: format!("Erreur reprise des prélèvements: {}", e) })
+    ))?;
+    let snapshot = subscriptions::snapshot_from_stripe(&remote);
+    
+    // Le statut repris est celui de Stripe (actif, ou en essai si l'essai court encore)
+    let mut subscription = state.subscriptions.get_mut(&sub_id)
+        .ok_or_else(|| (
+            StatusCode::NOT_FOUND,
+            Json(ApiError { error: "Abonnement non trouvé".to_string() })
+        ))?;
+    subscriptions::apply_snapshot(&mut subscription, &snapshot, Utc::now());
+    
+    tracing::info!("Abonnement {}: prélèvements repris ({:?})", sub_id, subscription.status);
+    
+    Ok(Json(subscription.clone()))
+}
+
+/// Changer de formule (montée ou descente en gamme)
+pub async fn change_plan(
+    State(state): State<AppState>,
+    Path(sub_id): Path<String>,
+    ValidatedJson(req): ValidatedJson<ChangePlanRequest>,
+) -> Result<Json<UserSubscription>, (StatusCode, Json<ApiError>)> {
+    let plan = state.subscription_plans.get(&req.plan_id)
+        .map(|plan| plan.clone())
+        .ok_or_else(|| (
+            StatusCode::NOT_FOUND,
+            Json(ApiError { error: "Plan d'abonnement non trouvé".to_string() })
+        ))?;
+    
+    // Vérifier sans garder le verrou pendant les appels Stripe
+    let (stripe_subscription_id, amount, interval, metered) = {
+        let subscription = state.subscriptions.get(&sub_id)
+            .ok_or_else(|| (
+                StatusCode::NOT_FOUND,
+                Json(ApiError { error: "Abonnement non trouvé".to_string() })
+            ))?;
+        subscriptions::check_plan_change(&subscription, &plan.id)?;
+        // Même période de facturation, au prix de la nouvelle formule
+        let amount = subscriptions::plan_price(&plan, subscription.interval)?;
+        // L'option à l'usage souscrite suit le tarif de la nouvelle formule
+        let metered = plan.metered.clone().filter(|_| subscription.metered.is_some());
+        (subscription.stripe_subscription_id.clone(), amount, subscription.interval, metered)
+    };
+    
+    let price_id = stripe_service::create_subscription_price(
+        &state.stripe_client,
+        &plan.name,
+        amount,
+        interval,
+    ).await.map_err(|e| (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ApiError { error: format!("Erreur création prix: {}", e) })
+    ))?;
+    
+    let metered_price_id = match &metered {
+        Some(metered) => Some(stripe_service::create_metered_price(
+            &state.stripe_client,
+            &format!("{} - {}", plan.name, metered.unit_label),
+            metered.unit_amount,
+            interval,
+        ).await.map_err(|e| (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiError { error: format!("Erreur création prix à l'usage: {}", e) })
+        ))?),
+        None => None,
+    };
+    
+    let updated = stripe_service::change_subscription_price(
+        &state.stripe_client,
+        &stripe_subscription_id,
+        &price_id,
+        metered_price_id.as_deref(),
+        req.proration,
+    ).await.map_err(|e| (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ApiError { error: format!("Erreur changement de formule: {}", e) })
+    ))?;
+    let metered_item_id = subscriptions::snapshot_from_stripe(&updated).metered_item_id;
+    
+    let mut subscription = state.subscriptions.get_mut(&sub_id)
+        .ok_or_else(|| (
+            StatusCode::NOT_FOUND,
+            Json(ApiError { error: "Abonnement non trouvé".to_string() })
+        ))?;
+    let from_plan_id = subscription.plan_id.clone();
+    subscriptions::apply_plan_change(&mut subscription, &plan.id, req.proration, Utc::now());
+    subscription.metered = metered.zip(metered_item_id).map(|(price, stripe_item_id)| MeteredItem {
+        stripe_item_id,
+        unit_amount: price.unit_amount,
+        unit_label: price.unit_label,
+    });
+    
+    tracing::info!("Abonnement {}: formule {} → {} (prorata {:?})",
+                  sub_id, from_plan_id, plan.id, req.proration);
+    
+    Ok(Json(subscription.clone()))
+}
+
+/// Changer de période de facturation au prochain renouvellement
+pub async fn change_interval(
+    State(state): State<AppState>,
+    Path(sub_id): Path<String>,
+    ValidatedJson(req): ValidatedJson<ChangeIntervalRequest>,
+) -> Result<Json<UserSubscription>, (StatusCode, Json<ApiError>)> {
+    let (stripe_subscription_id, plan, amount, metered) = {
+        let subscription = state.subscriptions.get(&sub_id)
+            .ok_or_else(|| (
+                StatusCode::NOT_FOUND,
+                Json(ApiError { error: "Abonnement non trouvé".to_string() })
+            ))?;
+        let plan = state.subscription_plans.get(&subscription.plan_id)
+            .map(|plan| plan.clone())
+            .ok_or_else(|| (
+                StatusCode::NOT_FOUND,
+                Json(ApiError { error: "Plan d'abonnement non trouvé".to_string() })
+            ))?;
+        let amount = subscriptions::check_interval_change(&subscription, &plan, req.interval)?;
+        (subscription.stripe_subscription_id.clone(), plan, amount, subscription.metered.clone())
+    };
+    
+    let price_id = stripe_service::create_subscription_price(
+        &state.stripe_client,
+        &plan.name,
+        amount,
+        req.interval,
+    ).await.map_err(|e| (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ApiError { error: format!("Erreur création prix: {}", e) })
+    ))?;
+    
+    // L'option à l'usage suit la nouvelle période, au tarif souscrit
+    let metered_price_id = match &metered {
+        Some(metered) => Some(stripe_service::create_metered_price(
+            &state.stripe_client,
+            &format!("{} - {}", plan.name, metered.unit_label),
+            metered.unit_amount,
+            req.interval,
+        ).await.map_err(|e| (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiError { error: format!("Erreur création prix à l'usage: {}", e) })
+        ))?),
+        None => None,
+    };
+    
+    let schedule = stripe_service::schedule_price_at_renewal(
+        &state.stripe_client,
+        &stripe_subscription_id,
+        &price_id,
+        metered_price_id.as_deref(),
+    ).await.map_err(|e| (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ApiError { error: format!("Erreur programmation du changement: {}", e) })
+    ))?;
+    
+    let mut subscription = state.subscriptions.get_mut(&sub_id)
+        .ok_or_else(|| (
+            StatusCode::NOT_FOUND,
+            Json(ApiError { error: "Abonnement non trouvé".to_string() })
+        ))?;
+    subscription.scheduled_interval = Some(req.interval);
+    subscription.interval_schedule_id = Some(schedule.id.to_string());
+    
+    tracing::info!("Abonnement {}: facturation {:?} → {:?} au {} ({}€/{})",
+                  sub_id, subscription.interval, req.interval, subscription.current_period_end,
+                  amount as f64 / 100.0, req.interval.label());
+    
+    Ok(Json(subscription.clone()))
+}
+
+/// Abandonner le changement de période programmé au renouvellement
+pub async fn cancel_interval_change(
+    State(state): State<AppState>,
+    Path(sub_id): Path<String>,
+) -> Result<Json<UserSubscription>, (StatusCode, Json<ApiError>)> {
+    let schedule_id = {
+        let subscription = state.subscriptions.get(&sub_id)
+            .ok_or_else(|| (
+                StatusCode::NOT_FOUND,
+                Json(ApiError { error: "Abonnement non trouvé".to_string() })
+            ))?;
+        subscriptions::check_cancel_interval_change(&subscription)?
+    };
+    
+    release_interval_schedule(&state, &sub_id, &schedule_id).await?;
+    
+    let subscription = state.subscriptions.get(&sub_id)
+        .ok_or_else(|| (
+            StatusCode::NOT_FOUND,
+            Json(ApiError { error: "Abonnement non trouvé".to_string() })
+        ))?;
+    tracing::info!("Abonnement {}: changement de période abandonné, facturation {:?} conservée",
+                  sub_id, subscription.interval);
+    
+    Ok(Json(subscription.clone()))
+}
+
+// Libérer le calendrier Stripe d'un changement de période et l'oublier localement
+async fn release_interval_schedule(
+    state: &AppState,
+    sub_id: &str,
+    schedule_id: &str,
+) -> Result<(), (StatusCode, Json<ApiError>)> {
+    stripe_service::release_subscription_schedule(&state.stripe_client, schedule_id)
+        .await
+        .map_err(|e| (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiError { error: format!("Erreur abandon du changement de période: {}", e) })
+        ))?;
+    
+    if let Some(mut subscription) = state.subscriptions.get_mut(sub_id) {
+        subscription.scheduled_interval = None;
+        subscription.interval_schedule_id = None;
+    }
+    Ok(())
+}
+
+/// Déclarer une consommation (ex: article numérique débloqué)
+///
+/// Idempotent par `event_id`: une déclaration rejouée renvoie l'événement déjà
+/// enregistré sans le compter de nouveau. La consommation est remontée à Stripe
+/// aussitôt; en cas d'échec, la tâche de fond réessaie.
+pub async fn report_usage(
+    State(state): State<AppState>,
+    Path(sub_id): Path<String>,
+    ValidatedJson(req): ValidatedJson<ReportUsageRequest>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ApiError>)> {
+    let now = Utc::now();
+    
+    let known = state.usage_events.get(&sub_id)
+        .and_then(|events| events.iter().find(|event| event.id == req.event_id).cloned());
+    if let Some(event) = known {
+        return Ok(Json(serde_json::json!({ "event": event, "duplicate": true })));
+    }
+    
+    let occurred_at = {
+        let subscription = state.subscriptions.get(&sub_id)
+            .ok_or_else(|| (
+                StatusCode::NOT_FOUND,
+                Json(ApiError { error: "Abonnement non trouvé".to_string() })
+            ))?;
+        usage::check_usage(&subscription, req.occurred_at, now)?
+    };
+    
+    let (event, duplicate) = usage::record_event(
+        &mut state.usage_events.entry(sub_id.clone()).or_default(),
+        UsageEvent {
+            id: req.event_id,
+            quantity: req.quantity,
+            occurred_at,
+            received_at: now,
+            report_status: UsageReportStatus::Pending,
+            stripe_usage_record_id: None,
+        },
+    );
+    if duplicate {
+        return Ok(Json(serde_json::json!({ "event": event, "duplicate": true })));
+    }
+    
+    tracing::info!("Abonnement {}: consommation {} (+{})", sub_id, event.id, event.quantity);
+    if let Err(e) = usage::report_pending(&state, &sub_id).await {
+        tracing::warn!("Remontée de consommation différée pour l'abonnement {}: {}", sub_id, e);
+    }
+    
+    // Statut après la tentative de remontée
+    let event = state.usage_events.get(&sub_id)
+        .and_then(|events| events.iter().find(|known| known.id == event.id).cloned())
+        .unwrap_or(event);
+    
+    Ok(Json(serde_json::json!({ "event": event, "duplicate": false })))
+}
+
+/// Consommation de la période en cours et montant estimé
+pub async fn get_usage(
+    State(state): State<AppState>,
+    Path(sub_id): Path<String>,
+) -> Result<Json<usage::UsageSummary>, (StatusCode, Json<ApiError>)> {
+    let subscription = state.subscriptions.get(&sub_id)
+        .ok_or_else(|| (
+            StatusCode::NOT_FOUND,
+            Json(ApiError { error: "Abonnement non trouvé".to_string() })
+        ))?;
+    let metered = subscription.metered.as_ref().ok_or(usage::UsageError::NotMetered)?;
+    let events = state.usage_events.get(&sub_id)
+        .map(|events| events.clone())
+        .unwrap_or_default();
+    
+    Ok(Json(usage::summarize(&subscription, metered, &events)))
+}
+
+/// Politique de relance en vigueur (admin)
+pub async fn get_dunning_policy(
+    State(state): State<AppState>,
+) -> Json<DunningPolicy> {
+    Json(state.dunning_policy.read().unwrap_or_else(|e| e.into_inner()).clone())
+}
+
+/// Remplacer la politique de relance (admin); s'applique aux prochains échecs
+pub async fn set_dunning_policy(
+    State(state): State<AppState>,
+    ValidatedJson(policy): ValidatedJson<DunningPolicy>,
+) -> Json<DunningPolicy> {
+    *state.dunning_policy.write().unwrap_or_else(|e| e.into_inner()) = policy.clone();
+    tracing::info!("Politique de relance mise à jour: tentatives {:?}, grâce {} jours, action finale {:?}",
+                  policy.retry_schedule_days, policy.grace_period_days, policy.final_action);
+    
+    Json(policy)
+}
@@ -0,0 +1,83 @@
+// Extracteur JSON validé: désérialise puis vérifie les contraintes `#[validate]`
+
+use axum::{
+    async_trait,
+    extract::{FromRequest, Request},
+    http::StatusCode,
+    Json,
+};
+use serde::de::DeserializeOwned;
+use std::collections::BTreeMap;
+use validator::{Validate, ValidationErrors, ValidationErrorsKind};
+
+use crate::models::ValidationErrorResponse;
+
+/// Remplace `Json<T>` pour les corps de requête: une requête invalide est
+/// rejetée avec le détail par champ avant d'atteindre le handler.
+pub struct ValidatedJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ValidatedJson<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, Json<ValidationErrorResponse>);
+    
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(req, state)
+            .await
+            .map_err(|rejection| (
+                StatusCode::BAD_REQUEST,
+                Json(ValidationErrorResponse {
+                    error: format!("JSON invalide: {}", rejection.body_text()),
+                    fields: BTreeMap::new(),
+                })
+            ))?;
+        
+        value.validate().map_err(|errors| (
+            StatusCode::BAD_REQUEST,
+            Json(ValidationErrorResponse {
+                error: "Requête invalide".to_string(),
+                fields: field_errors(&errors),
+            })
+        ))?;
+        
+        Ok(ValidatedJson(value))
+    }
+}
+
+/// Aplatir les erreurs imbriquées en chemins lisibles (`address.country`, `items[0].quantity`)
+pub fn field_errors(errors: &ValidationErrors) -> BTreeMap<String, Vec<String>> {
+    let mut fields = BTreeMap::new();
+    collect_errors(errors, "", &mut fields);
+    fields
+}
+
+fn collect_errors(
+    errors: &ValidationErrors,
+    prefix: &str,
+    fields: &mut BTreeMap<String, Vec<String>>,
+) {
+    for (field, kind) in errors.errors() {
+        let path = if prefix.is_empty() {
+            field.to_string()
+        } else {
+            format!("{}.{}", prefix, field)
+        };
+        
+        match kind {
+            ValidationErrorsKind::Field(errors) => {
+                let messages = errors.iter()
+                    .map(|e| e.message.as_ref().unwrap_or(&e.code).to_string());
+                fields.entry(path).or_default().extend(messages);
+            }
+            ValidationErrorsKind::Struct(nested) => collect_errors(nested, &path, fields),
+            ValidationErrorsKind::List(items) => {
+                for (index, nested) in items {
+                    collect_errors(nested, &format!("{}[{}]", path, index), fields);
+                }
+            }
+        }
+    }
+}
@@ -3,13 +3,16 @@
 use axum::{
     body::Bytes,
     extract::State,
-    http::{StatusCode, HeaderMap},
+    http::StatusCode,
     Json,
 };
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use uuid::Uuid;
 
 use crate::models::*;
+use crate::services::documents::{self, DocumentFormat, DocumentKind};
+use crate::services::dunning::{self, DunningStep, NotificationContext};
+use crate::services::{captures, inventory, invoices, order_state, stripe_service, subscriptions};
 use crate::state::AppState;
 
 /// Handler pour les webhooks Stripe
@@ -80,19 +83,39 @@ pub async fn stripe_webhook(
             handle_payment_success(&state, &event).await?;
         }
         
+        // Paiement autorisé (capture manuelle), en attente de capture
+        "payment_intent.amount_capturable_updated" => {
+            handle_payment_authorized(&state, &event).await?;
+        }
+        
         // Paiement échoué
         "payment_intent.payment_failed" => {
             handle_payment_failed(&state, &event).await?;
         }
         
+        // PaymentIntent annulé (API ou dashboard)
+        "payment_intent.canceled" => {
+            handle_payment_canceled(&state, &event).await?;
+        }
+        
         // SetupIntent réussi (carte enregistrée)
         "setup_intent.succeeded" => {
             handle_setup_success(&state, &event).await?;
         }
         
-        // Abonnement créé
-        "customer.subscription.created" => {
-            tracing::info!("✅ Abonnement créé");
+        // Abonnement créé ou modifié: Stripe fait foi (période, statut, dernière facture)
+        "customer.subscription.created" | "customer.subscription.updated" => {
+            handle_subscription_updated(&state, &event);
+        }
+        
+        // Abonnement terminé (fin de période après annulation, ou annulation immédiate)
+        "customer.subscription.deleted" => {
+            handle_subscription_deleted(&state, &event);
+        }
+        
+        // Fin d'essai gratuit dans 3 jours
+        "customer.subscription.trial_will_end" => {
+            handle_trial_will_end(&state, &event);
         }
         
         // Paiement abonnement réussi
@@ -127,26 +150,147 @@ async fn handle_payment_success(
     let order_id = metadata["order_id"].as_str();
     
     if let Some(order_id) = order_id {
+        let stale = match state.orders.get_mut(order_id) {
+            Some(mut order) => {
+                mark_attempt(&mut order, payment_intent_id, PaymentAttemptStatus::Succeeded);
+                order.payment_intent_id.as_deref() != Some(payment_intent_id)
+            }
+            None => false,
+        };
+        
+        // Un ancien PaymentIntent remplacé a pu être payé avant son annulation:
+        // la commande attend le nouveau montant, rembourser ce paiement
+        if stale {
+            let amount = event["data"]["object"]["amount_received"].as_i64().unwrap_or(0);
+            refund_stale_payment(state, order_id, payment_intent_id, amount).await;
+            return Ok(());
+        }
+        
         if let Some(mut order) = state.orders.get_mut(order_id) {
-            order.status = OrderStatus::Completed;
-            order.updated_at = Utc::now();
+            // Événement rejoué ou incohérent: ne pas décrémenter les stocks deux fois
+            let reason = format!("Paiement confirmé (PI {})", payment_intent_id);
+            if let Err(e) = order_state::transition(
+                &mut order,
+                OrderStatus::Completed,
+                StatusChangeSource::StripeWebhook,
+                &reason,
+            ) {
+                tracing::warn!("Commande {} ignorée: {}", order_id, e);
+                return Ok(());
+            }
+            
+            // Capture manuelle: montant réellement capturé
+            if let Some(authorization) = order.authorization.as_mut() {
+                if authorization.captured_amount.is_none() {
+                    authorization.captured_amount = event["data"]["object"]["amount_received"].as_i64();
+                    authorization.captured_at = Some(Utc::now());
+                }
+            }
             
             let user_id = order.user_id.clone();
             
-            // Décrémenter les stocks
-            for item in &order.items {
-                if let Some(mut product) = state.products.get_mut(&item.product_id) {
-                    product.stock -= item.quantity;
-                    tracing::info!("Stock mis à jour: {} - nouveau stock: {}", 
-                                 product.name, product.stock);
+            // Décrémenter les stocks (déjà fait si réservés au checkout)
+            if !order.stock_reserved {
+                for item in &order.items {
+                    if let Some(stock) = inventory::adjust_stock(
+                        &state.products,
+                        &item.product_id,
+                        item.variant_sku.as_deref(),
+                        -item.quantity,
+                    ) {
+                        tracing::info!("Stock mis à jour: {} - nouveau stock: {}", 
+                                     item.product_name, stock);
+                    }
                 }
+                order.stock_reserved = true;
             }
             
             // Vider le panier MAINTENANT (paiement confirmé)
             state.carts.remove(&user_id);
             
             tracing::info!("Commande {} payée avec succès - PI: {}", order_id, payment_intent_id);
-            println!("\n NOTIFICATION CLIENT: Votre commande {} a été confirmée!", order_id);
+            
+            // Facture numérotée + reçu PDF joint à la confirmation
+            match invoices::issue_invoice(state, &order) {
+                Ok(invoice) => {
+                    let receipt = documents::attachment(&invoice, DocumentKind::Receipt, DocumentFormat::Pdf);
+                    println!("\n NOTIFICATION CLIENT: Votre commande {} a été confirmée! (pièce jointe: {}, {} octets)",
+                            order_id, receipt.filename, receipt.content.len());
+                }
+                Err(e) => {
+                    tracing::error!("Facture non émise pour la commande {}: {}", order_id, e);
+                    println!("\n NOTIFICATION CLIENT: Votre commande {} a été confirmée!", order_id);
+                }
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+// Rembourser intégralement le paiement d'un PaymentIntent remplacé
+async fn refund_stale_payment(state: &AppState, order_id: &str, payment_intent_id: &str, amount: i64) {
+    if amount <= 0 {
+        return;
+    }
+    
+    let idempotency_key = format!("refund-stale-{}", payment_intent_id);
+    match stripe_service::create_refund(&state.stripe_client, payment_intent_id, amount, order_id, &idempotency_key).await {
+        Ok(refund) => {
+            if let Some(mut order) = state.orders.get_mut(order_id) {
+                mark_attempt(&mut order, payment_intent_id, PaymentAttemptStatus::Refunded);
+            }
+            tracing::warn!("Ancien PI {} payé pour la commande {}: remboursé ({})", 
+                         payment_intent_id, order_id, refund.id);
+            println!("\n NOTIFICATION CLIENT: Un paiement en double pour votre commande {} a été remboursé ({}€)", 
+                    order_id, amount as f64 / 100.0);
+        }
+        Err(e) => {
+            // Reste en Succeeded dans l'historique des tentatives: à rembourser manuellement
+            tracing::error!("Ancien PI {} payé pour la commande {}, remboursement impossible: {}", 
+                          payment_intent_id, order_id, e);
+        }
+    }
+}
+
+async fn handle_payment_authorized(
+    state: &AppState,
+    event: &serde_json::Value,
+) -> Result<(), (StatusCode, Json<ApiError>)> {
+    let payment_intent_id = event["data"]["object"]["id"].as_str().unwrap_or("");
+    let amount_capturable = event["data"]["object"]["amount_capturable"].as_i64().unwrap_or(0);
+    let metadata = &event["data"]["object"]["metadata"];
+    let order_id = metadata["order_id"].as_str();
+    
+    // Aussi envoyé à la capture (montant capturable remis à 0)
+    if amount_capturable == 0 {
+        return Ok(());
+    }
+    
+    if let Some(order_id) = order_id {
+        if let Some(mut order) = state.orders.get_mut(order_id) {
+            mark_attempt(&mut order, payment_intent_id, PaymentAttemptStatus::Authorized);
+            
+            let reason = format!("Paiement autorisé (PI {})", payment_intent_id);
+            if let Err(e) = order_state::transition(
+                &mut order,
+                OrderStatus::Authorized,
+                StatusChangeSource::StripeWebhook,
+                &reason,
+            ) {
+                tracing::warn!("Commande {} ignorée: {}", order_id, e);
+                return Ok(());
+            }
+            
+            let authorization = captures::new_authorization(amount_capturable, Utc::now());
+            tracing::info!("Commande {} autorisée ({}€), à capturer avant le {}", 
+                         order_id, amount_capturable as f64 / 100.0, authorization.expires_at);
+            order.authorization = Some(authorization);
+            
+            // Paiement garanti: vider le panier comme pour un paiement immédiat
+            state.carts.remove(&order.user_id);
+            
+            println!("\n NOTIFICATION CLIENT: Votre commande {} est confirmée, vous serez débité à l'expédition", order_id);
         }
     }
     
@@ -163,8 +307,30 @@ async fn handle_payment_failed(
     
     if let Some(order_id) = order_id {
         if let Some(mut order) = state.orders.get_mut(order_id) {
-            order.status = OrderStatus::Failed;
-            order.updated_at = Utc::now();
+            mark_attempt(&mut order, payment_intent_id, PaymentAttemptStatus::Failed);
+            
+            // Un ancien PaymentIntent remplacé ne concerne plus la commande
+            if order.payment_intent_id.as_deref() != Some(payment_intent_id) {
+                return Ok(());
+            }
+            
+            let reason = format!("Paiement échoué (PI {})", payment_intent_id);
+            if let Err(e) = order_state::transition(
+                &mut order,
+                OrderStatus::Failed,
+                StatusChangeSource::StripeWebhook,
+                &reason,
+            ) {
+                tracing::warn!("Commande {} ignorée: {}", order_id, e);
+                return Ok(());
+            }
+            
+            // Le stock ne reste pas bloqué par une commande impayée; un nouvel
+            // essai de paiement réussi le déduira de nouveau
+            if order.stock_reserved {
+                inventory::release_items(&state.products, &order.items);
+                order.stock_reserved = false;
+            }
             
             tracing::error!("Paiement échoué pour commande {} - PI: {}", order_id, payment_intent_id);
             println!("\n NOTIFICATION CLIENT: Le paiement pour votre commande {} a échoué", order_id);
@@ -174,6 +340,55 @@ async fn handle_payment_failed(
     Ok(())
 }
 
+async fn handle_payment_canceled(
+    state: &AppState,
+    event: &serde_json::Value,
+) -> Result<(), (StatusCode, Json<ApiError>)> {
+    let payment_intent_id = event["data"]["object"]["id"].as_str().unwrap_or("");
+    let metadata = &event["data"]["object"]["metadata"];
+    let order_id = metadata["order_id"].as_str();
+    
+    if let Some(order_id) = order_id {
+        if let Some(mut order) = state.orders.get_mut(order_id) {
+            mark_attempt(&mut order, payment_intent_id, PaymentAttemptStatus::Cancelled);
+            
+            // PaymentIntent remplacé lors d'une modification: la commande continue
+            if order.payment_intent_id.as_deref() != Some(payment_intent_id) {
+                return Ok(());
+            }
+            
+            // Déjà annulée par cancel_order dans la plupart des cas
+            let reason = format!("PaymentIntent annulé (PI {})", payment_intent_id);
+            if let Err(e) = order_state::transition(
+                &mut order,
+                OrderStatus::Cancelled,
+                StatusChangeSource::StripeWebhook,
+                &reason,
+            ) {
+                tracing::info!("Commande {} inchangée: {}", order_id, e);
+                return Ok(());
+            }
+            
+            if order.stock_reserved {
+                inventory::release_items(&state.products, &order.items);
+                order.stock_reserved = false;
+            }
+            
+            tracing::info!("Commande {} annulée suite à l'annulation du PI {}", order_id, payment_intent_id);
+        }
+    }
+    
+    Ok(())
+}
+
+// Mettre à jour la tentative de paiement correspondant au PaymentIntent
+fn mark_attempt(order: &mut Order, payment_intent_id: &str, status: PaymentAttemptStatus) {
+    if let Some(attempt) = order.payment_attempts.iter_mut()
+        .find(|a| a.payment_intent_id == payment_intent_id) {
+        attempt.status = status;
+    }
+}
+
 async fn handle_setup_success(
     state: &AppState,
     event: &serde_json::Value,
@@ -212,6 +427,29 @@ async fn handle_invoice_paid(
     let subscription_id = event["data"]["object"]["subscription"].as_str().unwrap_or("");
     let amount = event["data"]["object"]["amount_paid"].as_i64().unwrap_or(0);
     
+    record_subscription_payment(state, &event["data"]["object"], amount, SubscriptionPaymentStatus::Paid);
+    
+    let policy = state.dunning_policy.read().unwrap_or_else(|e| e.into_inner()).clone();
+    for mut sub in state.subscriptions.iter_mut() {
+        if sub.stripe_subscription_id != subscription_id {
+            continue;
+        }
+        
+        // Premier prélèvement après l'essai gratuit
+        if amount > 0 && sub.status == SubscriptionStatus::Trialing {
+            sub.status = SubscriptionStatus::Active;
+            tracing::info!("Fin d'essai: abonnement {} actif", sub.id);
+        }
+        
+        // Facture en relance enfin réglée
+        let plan_name = plan_name(state, &sub.plan_id);
+        let context = NotificationContext { plan_name: &plan_name, amount };
+        if let Some(message) = dunning::on_payment_recovered(&mut sub, &policy, &context) {
+            tracing::info!("Abonnement {} rétabli après relance", sub.id);
+            println!("\n NOTIFICATION CLIENT: {}", message);
+        }
+    }
+    
     tracing::info!("Facture payée pour abonnement {} - Montant: {}€", 
                  subscription_id, amount as f64 / 100.0);
     println!("\n NOTIFICATION CLIENT: Votre abonnement a été renouvelé - Montant: {}€", 
@@ -220,30 +458,194 @@ async fn handle_invoice_paid(
     Ok(())
 }
 
+fn handle_subscription_updated(state: &AppState, event: &serde_json::Value) {
+    let remote: stripe::Subscription = match serde_json::from_value(event["data"]["object"].clone()) {
+        Ok(subscription) => subscription,
+        Err(e) => {
+            tracing::error!("Abonnement illisible dans l'événement {}: {}", event["type"], e);
+            return;
+        }
+    };
+    let snapshot = subscriptions::snapshot_from_stripe(&remote);
+    let stripe_subscription_id = remote.id.to_string();
+    let created = event["created"].as_i64()
+        .and_then(|ts| DateTime::<Utc>::from_timestamp(ts, 0))
+        .unwrap_or_else(Utc::now);
+    
+    for mut sub in state.subscriptions.iter_mut() {
+        if sub.stripe_subscription_id == stripe_subscription_id {
+            // Les événements peuvent arriver dans le désordre: ne pas revenir en arrière
+            if subscriptions::is_stale_event(&sub, created) {
+                tracing::info!("Événement {} antérieur à la dernière synchronisation de {}: ignoré",
+                              event["id"], sub.id);
+                continue;
+            }
+            subscriptions::apply_snapshot(&mut sub, &snapshot, created);
+            tracing::info!("✅ Abonnement {} synchronisé: {:?} jusqu'au {}",
+                          sub.id, sub.status, sub.current_period_end);
+        }
+    }
+}
+
+fn handle_subscription_deleted(state: &AppState, event: &serde_json::Value) {
+    let subscription_id = event["data"]["object"]["id"].as_str().unwrap_or("");
+    
+    for mut sub in state.subscriptions.iter_mut() {
+        if sub.stripe_subscription_id == subscription_id {
+            sub.status = SubscriptionStatus::Cancelled;
+            tracing::info!("Abonnement {} terminé", sub.id);
+            println!("\n NOTIFICATION CLIENT: Votre abonnement est maintenant terminé");
+        }
+    }
+}
+
+fn handle_trial_will_end(state: &AppState, event: &serde_json::Value) {
+    let subscription = &event["data"]["object"];
+    let subscription_id = subscription["id"].as_str().unwrap_or("");
+    let trial_end = subscription["trial_end"].as_i64()
+        .and_then(|ts| DateTime::<Utc>::from_timestamp(ts, 0));
+    
+    let Some(sub) = state.subscriptions.iter()
+        .find(|sub| sub.stripe_subscription_id == subscription_id)
+        .map(|sub| sub.clone()) else {
+        tracing::warn!("Fin d'essai pour un abonnement inconnu: {}", subscription_id);
+        return;
+    };
+    let plan = state.subscription_plans.get(&sub.plan_id);
+    let trial_end = trial_end.or(sub.trial_end).unwrap_or(sub.current_period_end);
+    
+    tracing::info!("Rappel de fin d'essai pour l'abonnement {} (user {})", sub.id, sub.user_id);
+    let amount = plan.as_ref()
+        .and_then(|p| subscriptions::plan_price(p, sub.interval).ok())
+        .unwrap_or(0);
+    println!("\n NOTIFICATION CLIENT: Votre essai gratuit se termine le {}. Votre abonnement {} sera ensuite prélevé de {}€ par {}",
+            trial_end.format("%d/%m/%Y"),
+            plan.as_ref().map(|p| p.name.as_str()).unwrap_or(&sub.plan_id),
+            amount as f64 / 100.0,
+            sub.interval.label());
+}
+
+// Historiser le paiement d'une facture d'abonnement (une ligne par facture Stripe)
+fn record_subscription_payment(
+    state: &AppState,
+    invoice: &serde_json::Value,
+    amount: i64,
+    status: SubscriptionPaymentStatus,
+) {
+    let Some(invoice_id) = invoice["id"].as_str() else {
+        return;
+    };
+    let subscription_id = invoice["subscription"].as_str().unwrap_or("").to_string();
+    
+    // Dernière facture de l'abonnement, et utilisateur concerné
+    let mut user_id = None;
+    if let Some(mut sub) = state.subscriptions.iter_mut().find(|sub| sub.stripe_subscription_id == subscription_id) {
+        sub.latest_invoice = Some(SubscriptionInvoiceSummary {
+            stripe_invoice_id: invoice_id.to_string(),
+            status: invoice["status"].as_str().map(str::to_string),
+            amount_due: invoice["amount_due"].as_i64().unwrap_or(0),
+            amount_paid: invoice["amount_paid"].as_i64().unwrap_or(0),
+            hosted_invoice_url: invoice["hosted_invoice_url"].as_str().map(str::to_string),
+        });
+        user_id = Some(sub.user_id.clone());
+    }
+    
+    // Un échec rejoué après le paiement ne doit pas écraser le paiement
+    if status == SubscriptionPaymentStatus::Failed {
+        if let Some(existing) = state.subscription_payments.get(invoice_id) {
+            if existing.status == SubscriptionPaymentStatus::Paid {
+                return;
+            }
+        }
+    }
+    
+    state.subscription_payments.insert(invoice_id.to_string(), SubscriptionPayment {
+        stripe_invoice_id: invoice_id.to_string(),
+        stripe_subscription_id: subscription_id,
+        stripe_payment_intent_id: invoice["payment_intent"].as_str().map(str::to_string),
+        user_id,
+        amount,
+        tax: invoice["tax"].as_i64().unwrap_or(0),
+        currency: invoice["currency"].as_str().unwrap_or("eur").to_string(),
+        occurred_at: payment_date(invoice, &status),
+        status,
+    });
+}
+
+// Date comptable du paiement: encaissement pour une facture payée, émission sinon
+// (un webhook rejoué ou en retard ne décale pas le paiement)
+fn payment_date(invoice: &serde_json::Value, status: &SubscriptionPaymentStatus) -> DateTime<Utc> {
+    let paid_at = match status {
+        SubscriptionPaymentStatus::Paid => invoice["status_transitions"]["paid_at"].as_i64(),
+        SubscriptionPaymentStatus::Failed => None,
+    };
+    paid_at
+        .or_else(|| invoice["created"].as_i64())
+        .and_then(|ts| DateTime::<Utc>::from_timestamp(ts, 0))
+        .unwrap_or_else(Utc::now)
+}
+
 async fn handle_invoice_failed(
     state: &AppState,
     event: &serde_json::Value,
 ) -> Result<(), (StatusCode, Json<ApiError>)> {
-    let subscription_id = event["data"]["object"]["subscription"].as_str().unwrap_or("");
-    let attempt_count = event["data"]["object"]["attempt_count"].as_i64().unwrap_or(0);
+    let invoice = &event["data"]["object"];
+    let invoice_id = invoice["id"].as_str().unwrap_or("");
+    let subscription_id = invoice["subscription"].as_str().unwrap_or("");
+    let attempt_count = invoice["attempt_count"].as_i64().unwrap_or(0);
+    let amount_due = invoice["amount_due"].as_i64().unwrap_or(0);
     
-    tracing::warn!("Échec paiement abonnement {} - Tentative {}/3", 
-                  subscription_id, attempt_count);
+    record_subscription_payment(state, invoice, amount_due, SubscriptionPaymentStatus::Failed);
     
-    if attempt_count >= 3 {
-        // Suspendre l'abonnement
-        for mut sub in state.subscriptions.iter_mut() {
-            if sub.stripe_subscription_id == subscription_id {
-                sub.status = SubscriptionStatus::PastDue;
-                tracing::error!("Abonnement {} suspendu après 3 échecs", subscription_id);
-                println!("\n NOTIFICATION CLIENT: Votre abonnement a été suspendu suite à des échecs de paiement");
-                break;
+    let policy = state.dunning_policy.read().unwrap_or_else(|e| e.into_inner()).clone();
+    let (sub_id, step) = {
+        let Some(mut sub) = state.subscriptions.iter_mut()
+            .find(|sub| sub.stripe_subscription_id == subscription_id) else {
+            tracing::warn!("Échec de paiement pour un abonnement inconnu: {}", subscription_id);
+            return Ok(());
+        };
+        let plan_name = plan_name(state, &sub.plan_id);
+        let context = NotificationContext { plan_name: &plan_name, amount: amount_due };
+        let step = dunning::on_payment_failed(&mut sub, &policy, invoice_id, attempt_count, &context, Utc::now());
+        (sub.id.clone(), step)
+    };
+    
+    match step {
+        DunningStep::Retry { attempt, next_retry_at, message } => {
+            tracing::warn!("Échec paiement abonnement {} - Tentative {}/{}, prochaine le {}",
+                          sub_id, attempt, dunning::max_attempts(&policy), next_retry_at);
+            println!("\n NOTIFICATION CLIENT: {}", message);
+        }
+        DunningStep::Final { action, message } => {
+            tracing::error!("Abonnement {}: relances épuisées, action finale {:?}", sub_id, action);
+            
+            // Répercuter l'action sur Stripe (l'état local est déjà à jour)
+            let result = match action {
+                DunningFinalAction::Cancel => stripe_service::cancel_subscription_now(&state.stripe_client, subscription_id)
+                    .await
+                    .map(|_| ()),
+                DunningFinalAction::Pause => stripe_service::pause_subscription_collection(&state.stripe_client, subscription_id, None)
+                    .await
+                    .map(|_| ()),
+                DunningFinalAction::MarkUnpaid => Ok(()),
+            };
+            if let Err(e) = result {
+                tracing::error!("Action finale {:?} non appliquée sur Stripe pour {}: {}", action, sub_id, e);
             }
+            
+            println!("\n NOTIFICATION CLIENT: {}", message);
+        }
+        DunningStep::AlreadyHandled => {
+            tracing::info!("Échec de paiement déjà traité pour la facture {}", invoice_id);
         }
-    } else {
-        println!("\n NOTIFICATION CLIENT: Le paiement de votre abonnement a échoué. Nouvelle tentative dans {} jour(s)", 
-                3 - attempt_count);
     }
     
     Ok(())
 }
+
+// Nom commercial d'une formule (identifiant si elle n'existe plus)
+fn plan_name(state: &AppState, plan_id: &str) -> String {
+    state.subscription_plans.get(plan_id)
+        .map(|plan| plan.name.clone())
+        .unwrap_or_else(|| plan_id.to_string())
+}
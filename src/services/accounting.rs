@@ -0,0 +1,353 @@
+// Export comptable: commandes payées, remboursements et paiements d'abonnements
+
+use axum::{body::Bytes, http::StatusCode, Json};
+use chrono::{DateTime, Utc};
+use futures_util::stream::{self, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+
+use crate::models::{
+    ApiError, Invoice, Order, OrderRefund, OrderStatus, SubscriptionCancellation, SubscriptionPayment, UserSubscription,
+};
+use crate::services::{invoices, refunds};
+use crate::state::AppState;
+
+// Lignes rendues par morceau envoyé au client
+const ROWS_PER_CHUNK: usize = 200;
+
+/// Colonnes de l'export, toujours dans cet ordre
+pub const COLUMNS: &[&str] = &[
+    "record_type",
+    "id",
+    "order_id",
+    "user_id",
+    "amount",
+    "currency",
+    "tax",
+    "status",
+    "stripe_payment_intent_id",
+    "stripe_refund_id",
+    "stripe_invoice_id",
+    "stripe_subscription_id",
+    "occurred_at",
+];
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum ExportError {
+    #[error("Intervalle invalide: from postérieur à to")]
+    InvalidRange,
+}
+
+impl From<ExportError> for (StatusCode, Json<ApiError>) {
+    fn from(err: ExportError) -> Self {
+        (StatusCode::BAD_REQUEST, Json(ApiError { error: err.to_string() }))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Jsonl, // JSON Lines: un objet par ligne
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Jsonl => "application/x-ndjson",
+        }
+    }
+    
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Jsonl => "jsonl",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordType {
+    Order,
+    Refund,
+    SubscriptionPayment,
+    SubscriptionRefund, // Remboursement au prorata d'une résiliation immédiate
+}
+
+/// Une ligne de l'export (mêmes champs que `COLUMNS`)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AccountingRow {
+    pub record_type: RecordType,
+    pub id: String,
+    pub order_id: Option<String>,
+    pub user_id: Option<String>,
+    pub amount: i64, // En centimes, TTC
+    pub currency: String,
+    pub tax: i64,
+    pub status: String,
+    pub stripe_payment_intent_id: Option<String>,
+    pub stripe_refund_id: Option<String>,
+    pub stripe_invoice_id: Option<String>,
+    pub stripe_subscription_id: Option<String>,
+    pub occurred_at: DateTime<Utc>,
+}
+
+fn status_label<T: Serialize>(status: &T) -> String {
+    serde_json::to_value(status).ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+/// Date d'encaissement d'une commande: confirmation du paiement par Stripe
+///
+/// `None` tant que rien n'a été encaissé (en attente, échec, annulation).
+pub fn paid_at(order: &Order) -> Option<DateTime<Utc>> {
+    order.status_history.iter()
+        .find(|change| change.to == OrderStatus::Completed)
+        .map(|change| change.at)
+}
+
+/// Ligne d'une commande payée, datée de l'encaissement
+///
+/// TVA de la facture si elle porte sur le montant encaissé, sinon calculée sur ce montant.
+pub fn order_row(order: &Order, paid_at: DateTime<Utc>, invoice: Option<&Invoice>, vat_rate_percent: i64) -> AccountingRow {
+    let amount = refunds::captured_amount(order);
+    let tax = invoice
+        .filter(|invoice| invoice.total == amount)
+        .map(|invoice| invoice.tax_amount)
+        .unwrap_or_else(|| invoices::tax_included(amount, vat_rate_percent));
+    
+    AccountingRow {
+        record_type: RecordType::Order,
+        id: order.id.clone(),
+        order_id: Some(order.id.clone()),
+        user_id: Some(order.user_id.clone()),
+        amount,
+        currency: "eur".to_string(),
+        tax,
+        status: status_label(&order.status),
+        stripe_payment_intent_id: order.payment_intent_id.clone(),
+        stripe_refund_id: None,
+        stripe_invoice_id: None,
+        stripe_subscription_id: None,
+        occurred_at: paid_at,
+    }
+}
+
+pub fn refund_row(order: &Order, refund: &OrderRefund, vat_rate_percent: i64) -> AccountingRow {
+    AccountingRow {
+        record_type: RecordType::Refund,
+        id: refund.id.clone(),
+        order_id: Some(order.id.clone()),
+        user_id: Some(order.user_id.clone()),
+        amount: refund.amount,
+        currency: "eur".to_string(),
+        tax: invoices::tax_included(refund.amount, vat_rate_percent),
+        status: "Refunded".to_string(),
+        stripe_payment_intent_id: order.payment_intent_id.clone(),
+        stripe_refund_id: Some(refund.stripe_refund_id.clone()),
+        stripe_invoice_id: None,
+        stripe_subscription_id: None,
+        occurred_at: refund.created_at,
+    }
+}
+
+pub fn subscription_payment_row(payment: &SubscriptionPayment) -> AccountingRow {
+    AccountingRow {
+        record_type: RecordType::SubscriptionPayment,
+        id: payment.stripe_invoice_id.clone(),
+        order_id: None,
+        user_id: payment.user_id.clone(),
+        amount: payment.amount,
+        currency: payment.currency.clone(),
+        tax: payment.tax,
+        status: status_label(&payment.status),
+        stripe_payment_intent_id: payment.stripe_payment_intent_id.clone(),
+        stripe_refund_id: None,
+        stripe_invoice_id: Some(payment.stripe_invoice_id.clone()),
+        stripe_subscription_id: Some(payment.stripe_subscription_id.clone()),
+        occurred_at: payment.occurred_at,
+    }
+}
+
+/// Date d'un remboursement de résiliation accepté par Stripe
+pub fn subscription_refunded_at(cancellation: &SubscriptionCancellation) -> Option<DateTime<Utc>> {
+    cancellation.stripe_refund_id.as_ref()
+        .filter(|_| cancellation.refund_amount > 0)
+        .map(|_| cancellation.refunded_at.unwrap_or(cancellation.requested_at))
+}
+
+pub fn subscription_refund_row(
+    subscription: &UserSubscription,
+    cancellation: &SubscriptionCancellation,
+    refunded_at: DateTime<Utc>,
+    vat_rate_percent: i64,
+) -> AccountingRow {
+    let stripe_refund_id = cancellation.stripe_refund_id.clone().unwrap_or_default();
+    AccountingRow {
+        record_type: RecordType::SubscriptionRefund,
+        id: stripe_refund_id.clone(),
+        order_id: None,
+        user_id: Some(subscription.user_id.clone()),
+        amount: cancellation.refund_amount,
+        currency: "eur".to_string(),
+        tax: invoices::tax_included(cancellation.refund_amount, vat_rate_percent),
+        status: "Refunded".to_string(),
+        stripe_payment_intent_id: None,
+        stripe_refund_id: Some(stripe_refund_id),
+        stripe_invoice_id: None,
+        stripe_subscription_id: Some(subscription.stripe_subscription_id.clone()),
+        occurred_at: refunded_at,
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn csv_header() -> String {
+    format!("{}\n", COLUMNS.join(","))
+}
+
+pub fn to_csv(row: &AccountingRow) -> String {
+    let optional = |value: &Option<String>| value.as_deref().map(csv_field).unwrap_or_default();
+    let record_type = status_label(&row.record_type);
+    
+    format!(
+        "{},{},{},{},{},{},{},{},{},{},{},{},{}\n",
+        record_type,
+        csv_field(&row.id),
+        optional(&row.order_id),
+        optional(&row.user_id),
+        row.amount,
+        csv_field(&row.currency),
+        row.tax,
+        csv_field(&row.status),
+        optional(&row.stripe_payment_intent_id),
+        optional(&row.stripe_refund_id),
+        optional(&row.stripe_invoice_id),
+        optional(&row.stripe_subscription_id),
+        row.occurred_at.to_rfc3339(),
+    )
+}
+
+pub fn to_jsonl(row: &AccountingRow) -> String {
+    let mut line = serde_json::to_string(row).unwrap_or_default();
+    line.push('\n');
+    line
+}
+
+// Référence vers un enregistrement à exporter (les données sont lues au fil du flux)
+enum RecordKey {
+    Order(String),
+    Refund { order_id: String, refund_id: String },
+    SubscriptionPayment(String),
+    SubscriptionRefund(String),
+}
+
+// Sélectionner les enregistrements de la période, triés chronologiquement
+fn collect_keys(state: &AppState, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<RecordKey> {
+    let in_range = |at: &DateTime<Utc>| *at >= from && *at < to;
+    let mut keys: Vec<(DateTime<Utc>, RecordKey)> = Vec::new();
+    
+    for order in state.orders.iter() {
+        // Seules les commandes encaissées, à la date du paiement
+        if let Some(paid_at) = paid_at(&order).filter(in_range) {
+            keys.push((paid_at, RecordKey::Order(order.id.clone())));
+        }
+        for refund in order.refunds.iter().filter(|r| in_range(&r.created_at)) {
+            keys.push((refund.created_at, RecordKey::Refund {
+                order_id: order.id.clone(),
+                refund_id: refund.id.clone(),
+            }));
+        }
+    }
+    for payment in state.subscription_payments.iter() {
+        if in_range(&payment.occurred_at) {
+            keys.push((payment.occurred_at, RecordKey::SubscriptionPayment(payment.stripe_invoice_id.clone())));
+        }
+    }
+    for subscription in state.subscriptions.iter() {
+        let refunded_at = subscription.cancellation.as_ref().and_then(subscription_refunded_at);
+        if let Some(refunded_at) = refunded_at.filter(in_range) {
+            keys.push((refunded_at, RecordKey::SubscriptionRefund(subscription.id.clone())));
+        }
+    }
+    
+    keys.sort_by_key(|(at, _)| *at);
+    keys.into_iter().map(|(_, key)| key).collect()
+}
+
+fn load_row(state: &AppState, key: &RecordKey) -> Option<AccountingRow> {
+    let vat_rate_percent = state.config.vat_rate_percent;
+    match key {
+        RecordKey::Order(order_id) => {
+            let order = state.orders.get(order_id)?;
+            let paid_at = paid_at(&order)?;
+            let invoice = state.invoices.get(order_id);
+            Some(order_row(&order, paid_at, invoice.as_deref(), vat_rate_percent))
+        }
+        RecordKey::Refund { order_id, refund_id } => {
+            let order = state.orders.get(order_id)?;
+            let refund = order.refunds.iter().find(|r| &r.id == refund_id)?;
+            Some(refund_row(&order, refund, vat_rate_percent))
+        }
+        RecordKey::SubscriptionPayment(invoice_id) => {
+            let payment = state.subscription_payments.get(invoice_id)?;
+            Some(subscription_payment_row(&payment))
+        }
+        RecordKey::SubscriptionRefund(sub_id) => {
+            let subscription = state.subscriptions.get(sub_id)?;
+            let cancellation = subscription.cancellation.as_ref()?;
+            let refunded_at = subscription_refunded_at(cancellation)?;
+            Some(subscription_refund_row(&subscription, cancellation, refunded_at, vat_rate_percent))
+        }
+    }
+}
+
+/// Flux de l'export pour `[from, to[`
+///
+/// Seules les références des enregistrements sont gardées en mémoire; les
+/// lignes sont lues et rendues par paquets au fur et à mesure de l'envoi.
+pub fn export_stream(
+    state: AppState,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    format: ExportFormat,
+) -> Result<impl Stream<Item = Result<Bytes, Infallible>>, ExportError> {
+    if from > to {
+        return Err(ExportError::InvalidRange);
+    }
+    
+    let keys = collect_keys(&state, from, to);
+    let header = match format {
+        ExportFormat::Csv => Some(Bytes::from(csv_header())),
+        ExportFormat::Jsonl => None,
+    };
+    
+    let chunks = stream::unfold((keys.into_iter(), state), move |(mut keys, state)| async move {
+        let mut chunk = String::new();
+        for key in keys.by_ref().take(ROWS_PER_CHUNK) {
+            // Enregistrement supprimé depuis le début de l'export: ignoré
+            if let Some(row) = load_row(&state, &key) {
+                match format {
+                    ExportFormat::Csv => chunk.push_str(&to_csv(&row)),
+                    ExportFormat::Jsonl => chunk.push_str(&to_jsonl(&row)),
+                }
+            }
+        }
+        if chunk.is_empty() && keys.len() == 0 {
+            return None;
+        }
+        Some((Ok(Bytes::from(chunk)), (keys, state)))
+    });
+    
+    Ok(stream::iter(header.map(Ok)).chain(chunks))
+}
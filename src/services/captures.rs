@@ -0,0 +1,83 @@
+// Capture manuelle: autorisations, montants capturables et expiration
+
+use axum::{http::StatusCode, Json};
+use chrono::{DateTime, Duration, Utc};
+
+use crate::models::{ApiError, CaptureMethod, Order, OrderStatus, PaymentAuthorization};
+
+/// Durée de validité d'une autorisation carte chez Stripe
+pub const AUTHORIZATION_VALIDITY_DAYS: i64 = 7;
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum CaptureError {
+    #[error("Commande en débit immédiat: rien à capturer")]
+    AutomaticCapture,
+    #[error("Seul un paiement autorisé peut être capturé (statut: {0:?})")]
+    NotAuthorized(OrderStatus),
+    #[error("Montant à capturer ({requested}) supérieur au montant autorisé ({authorized})")]
+    AmountExceeded { requested: i64, authorized: i64 },
+    #[error("Paiement déjà capturé ({0}), en attente de confirmation Stripe")]
+    AlreadyCaptured(i64),
+}
+
+impl From<CaptureError> for (StatusCode, Json<ApiError>) {
+    fn from(err: CaptureError) -> Self {
+        let status = match err {
+            CaptureError::AmountExceeded { .. } => StatusCode::BAD_REQUEST,
+            _ => StatusCode::CONFLICT,
+        };
+        (status, Json(ApiError { error: err.to_string() }))
+    }
+}
+
+/// Autorisation reçue de Stripe pour un montant capturable
+pub fn new_authorization(amount: i64, authorized_at: DateTime<Utc>) -> PaymentAuthorization {
+    PaymentAuthorization {
+        amount,
+        authorized_at,
+        expires_at: authorized_at + Duration::days(AUTHORIZATION_VALIDITY_DAYS),
+        expiring_soon: false,
+        captured_amount: None,
+        captured_at: None,
+    }
+}
+
+/// Vérifier une demande de capture et retourner le montant à capturer
+pub fn plan_capture(order: &Order, amount: Option<i64>) -> Result<i64, CaptureError> {
+    if order.capture_method == CaptureMethod::Automatic {
+        return Err(CaptureError::AutomaticCapture);
+    }
+    let authorization = match (&order.status, &order.authorization) {
+        (OrderStatus::Authorized, Some(authorization)) => authorization,
+        _ => return Err(CaptureError::NotAuthorized(order.status.clone())),
+    };
+    // Le statut ne passe en `Completed` qu'au webhook: ne pas capturer deux fois entre-temps
+    if let Some(captured) = authorization.captured_amount {
+        return Err(CaptureError::AlreadyCaptured(captured));
+    }
+    
+    let requested = amount.unwrap_or(authorization.amount);
+    if requested > authorization.amount {
+        return Err(CaptureError::AmountExceeded { requested, authorized: authorization.amount });
+    }
+    Ok(requested)
+}
+
+/// Signaler une autorisation qui expire dans moins de `warning_hours`
+///
+/// Retourne `true` la première fois seulement, pour n'alerter qu'une fois.
+pub fn flag_if_expiring(order: &mut Order, now: DateTime<Utc>, warning_hours: i64) -> bool {
+    if order.status != OrderStatus::Authorized {
+        return false;
+    }
+    match order.authorization.as_mut() {
+        Some(authorization)
+            if !authorization.expiring_soon
+                && authorization.expires_at - Duration::hours(warning_hours) <= now =>
+        {
+            authorization.expiring_soon = true;
+            true
+        }
+        _ => false,
+    }
+}
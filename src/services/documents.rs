@@ -0,0 +1,293 @@
+// Rendu des reçus et factures en HTML et PDF
+
+use serde::Deserialize;
+
+use crate::models::Invoice;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DocumentKind {
+    Receipt, // Reçu client envoyé après paiement
+    Invoice, // Facture (clients professionnels)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DocumentFormat {
+    #[default]
+    Html,
+    Pdf,
+}
+
+impl DocumentFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            DocumentFormat::Html => "text/html; charset=utf-8",
+            DocumentFormat::Pdf => "application/pdf",
+        }
+    }
+}
+
+/// Document prêt à être joint à une notification
+#[derive(Debug, Clone)]
+pub struct Attachment {
+    pub filename: String,
+    pub content_type: &'static str,
+    pub content: Vec<u8>,
+}
+
+/// Montant en centimes au format français: 1234 → "12,34 €"
+pub fn format_eur(cents: i64) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+    let cents = cents.abs();
+    format!("{}{},{:02} €", sign, cents / 100, cents % 100)
+}
+
+fn title(invoice: &Invoice, kind: DocumentKind) -> String {
+    match kind {
+        DocumentKind::Receipt => format!("Reçu de paiement - commande {}", invoice.order_id),
+        DocumentKind::Invoice => format!("Facture {}", invoice.number),
+    }
+}
+
+pub fn render(invoice: &Invoice, kind: DocumentKind, format: DocumentFormat) -> Vec<u8> {
+    match format {
+        DocumentFormat::Html => render_html(invoice, kind).into_bytes(),
+        DocumentFormat::Pdf => render_pdf(invoice, kind),
+    }
+}
+
+pub fn attachment(invoice: &Invoice, kind: DocumentKind, format: DocumentFormat) -> Attachment {
+    let prefix = match kind {
+        DocumentKind::Receipt => "recu",
+        DocumentKind::Invoice => "facture",
+    };
+    let extension = match format {
+        DocumentFormat::Html => "html",
+        DocumentFormat::Pdf => "pdf",
+    };
+    
+    Attachment {
+        filename: format!("{}-{}.{}", prefix, invoice.number, extension),
+        content_type: format.content_type(),
+        content: render(invoice, kind, format),
+    }
+}
+
+// ========== HTML ==========
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn render_html(invoice: &Invoice, kind: DocumentKind) -> String {
+    let mut html = String::new();
+    let title = escape_html(&title(invoice, kind));
+    let merchant = &invoice.merchant;
+    
+    html.push_str("<!DOCTYPE html>\n<html lang=\"fr\">\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!("<title>{}</title>\n", title));
+    html.push_str("<style>body{font-family:sans-serif;max-width:720px;margin:auto}\
+table{width:100%;border-collapse:collapse}td,th{padding:4px;border-bottom:1px solid #ddd}\
+.num{text-align:right}</style>\n</head>\n<body>\n");
+    html.push_str(&format!("<h1>{}</h1>\n", title));
+    
+    html.push_str(&format!(
+        "<p><strong>{}</strong><br>{}<br>{}",
+        escape_html(&merchant.name), escape_html(&merchant.address), escape_html(&merchant.email),
+    ));
+    if kind == DocumentKind::Invoice {
+        html.push_str(&format!("<br>TVA: {}", escape_html(&merchant.vat_number)));
+    }
+    html.push_str("</p>\n");
+    
+    html.push_str(&format!(
+        "<p>Date: {}<br>Commande: {}",
+        invoice.issued_at.format("%d/%m/%Y"), escape_html(&invoice.order_id),
+    ));
+    if kind == DocumentKind::Receipt {
+        html.push_str(&format!("<br>Facture: {}", escape_html(&invoice.number)));
+    }
+    html.push_str("</p>\n");
+    
+    if let Some(address) = &invoice.billing_address {
+        html.push_str(&format!(
+            "<p><strong>{}</strong><br>{}<br>{}{} {}<br>{}</p>\n",
+            escape_html(&address.name),
+            escape_html(&address.line1),
+            address.line2.as_ref().map(|l| format!("{}<br>", escape_html(l))).unwrap_or_default(),
+            escape_html(&address.postal_code),
+            escape_html(&address.city),
+            escape_html(&address.country),
+        ));
+    }
+    
+    html.push_str("<table>\n<tr><th>Article</th><th class=\"num\">Qté</th>\
+<th class=\"num\">Prix unitaire TTC</th><th class=\"num\">Total TTC</th></tr>\n");
+    for line in &invoice.lines {
+        html.push_str(&format!(
+            "<tr><td>{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td></tr>\n",
+            escape_html(&line.description), line.quantity, format_eur(line.unit_price), format_eur(line.total),
+        ));
+    }
+    if let Some(shipping) = &invoice.shipping {
+        html.push_str(&format!(
+            "<tr><td>Livraison ({})</td><td class=\"num\">1</td><td class=\"num\">{}</td><td class=\"num\">{}</td></tr>\n",
+            escape_html(&shipping.method_name), format_eur(shipping.cost), format_eur(shipping.cost),
+        ));
+    }
+    html.push_str("</table>\n");
+    
+    html.push_str(&format!(
+        "<p class=\"num\">Total HT: {}<br>TVA {}%: {}<br><strong>Total TTC: {}</strong></p>\n",
+        format_eur(invoice.total_excl_tax),
+        invoice.vat_rate_percent,
+        format_eur(invoice.tax_amount),
+        format_eur(invoice.total),
+    ));
+    if kind == DocumentKind::Receipt {
+        html.push_str("<p>Payé par carte bancaire. Merci pour votre commande !</p>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+    
+    html
+}
+
+// ========== PDF ==========
+
+// Lignes de texte du document (une par ligne PDF)
+fn text_lines(invoice: &Invoice, kind: DocumentKind) -> Vec<String> {
+    let merchant = &invoice.merchant;
+    let mut lines = vec![
+        title(invoice, kind),
+        String::new(),
+        merchant.name.clone(),
+        merchant.address.clone(),
+        merchant.email.clone(),
+    ];
+    if kind == DocumentKind::Invoice {
+        lines.push(format!("TVA: {}", merchant.vat_number));
+    }
+    lines.push(String::new());
+    lines.push(format!("Date: {}", invoice.issued_at.format("%d/%m/%Y")));
+    lines.push(format!("Commande: {}", invoice.order_id));
+    if kind == DocumentKind::Receipt {
+        lines.push(format!("Facture: {}", invoice.number));
+    }
+    
+    if let Some(address) = &invoice.billing_address {
+        lines.push(String::new());
+        lines.push(address.name.clone());
+        lines.push(address.line1.clone());
+        if let Some(line2) = &address.line2 {
+            lines.push(line2.clone());
+        }
+        lines.push(format!("{} {} {}", address.postal_code, address.city, address.country));
+    }
+    
+    lines.push(String::new());
+    for line in &invoice.lines {
+        lines.push(format!(
+            "{} - {} x {} = {}",
+            line.description, line.quantity, format_eur(line.unit_price), format_eur(line.total),
+        ));
+    }
+    if let Some(shipping) = &invoice.shipping {
+        lines.push(format!("Livraison ({}) = {}", shipping.method_name, format_eur(shipping.cost)));
+    }
+    
+    lines.push(String::new());
+    lines.push(format!("Total HT: {}", format_eur(invoice.total_excl_tax)));
+    lines.push(format!("TVA {}%: {}", invoice.vat_rate_percent, format_eur(invoice.tax_amount)));
+    lines.push(format!("Total TTC: {}", format_eur(invoice.total)));
+    if kind == DocumentKind::Receipt {
+        lines.push(String::new());
+        lines.push("Payé par carte bancaire. Merci pour votre commande !".to_string());
+    }
+    
+    lines
+}
+
+// Chaîne PDF littérale en WinAnsiEncoding (accents et € compris)
+fn pdf_string(text: &str) -> Vec<u8> {
+    let mut bytes = vec![b'('];
+    for c in text.chars() {
+        let byte = match c {
+            '(' | ')' | '\\' => {
+                bytes.push(b'\\');
+                c as u8
+            }
+            '€' => 0x80,
+            '’' => 0x92,
+            '–' => 0x96,
+            '—' => 0x97,
+            c if (c as u32) < 0x80 || (0xA0..=0xFF).contains(&(c as u32)) => c as u32 as u8,
+            _ => b'?',
+        };
+        bytes.push(byte);
+    }
+    bytes.push(b')');
+    bytes
+}
+
+const LINES_PER_PAGE: usize = 50;
+
+/// PDF A4 minimal (police Helvetica standard, sans dépendance externe)
+pub fn render_pdf(invoice: &Invoice, kind: DocumentKind) -> Vec<u8> {
+    let lines = text_lines(invoice, kind);
+    let pages: Vec<&[String]> = lines.chunks(LINES_PER_PAGE).collect();
+    
+    // Objets: 1 catalogue, 2 arbre des pages, 3 police, puis (page, contenu) par page
+    let mut objects: Vec<Vec<u8>> = Vec::new();
+    objects.push(b"<< /Type /Catalog /Pages 2 0 R >>".to_vec());
+    let kids: Vec<String> = (0..pages.len()).map(|i| format!("{} 0 R", 4 + i * 2)).collect();
+    objects.push(format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), pages.len()).into_bytes());
+    objects.push(b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>".to_vec());
+    
+    for (index, page_lines) in pages.iter().enumerate() {
+        let mut content = b"BT\n14 TL\n50 800 Td\n".to_vec();
+        for (line_index, line) in page_lines.iter().enumerate() {
+            // Titre en plus gros sur la première page
+            let size = if index == 0 && line_index == 0 { 16 } else { 10 };
+            content.extend_from_slice(format!("/F1 {} Tf\n", size).as_bytes());
+            content.extend(pdf_string(line));
+            content.extend_from_slice(b" Tj T*\n");
+        }
+        content.extend_from_slice(b"ET");
+        
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 595 842] \
+/Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+            5 + index * 2,
+        ).into_bytes());
+        
+        let mut stream = format!("<< /Length {} >>\nstream\n", content.len()).into_bytes();
+        stream.extend(content);
+        stream.extend_from_slice(b"\nendstream");
+        objects.push(stream);
+    }
+    
+    let mut pdf = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend_from_slice(format!("{} 0 obj\n", index + 1).as_bytes());
+        pdf.extend_from_slice(object);
+        pdf.extend_from_slice(b"\nendobj\n");
+    }
+    
+    let xref_offset = pdf.len();
+    pdf.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes());
+    for offset in offsets {
+        pdf.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    pdf.extend_from_slice(format!(
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref_offset,
+    ).as_bytes());
+    
+    pdf
+}
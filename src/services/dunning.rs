@@ -0,0 +1,144 @@
+// Relances des paiements d'abonnement échoués (dunning)
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::models::{DunningFinalAction, DunningPolicy, DunningState, SubscriptionStatus, UserSubscription};
+use crate::services::documents::format_eur;
+
+/// Suite donnée à un échec de paiement
+#[derive(Debug, Clone, PartialEq)]
+pub enum DunningStep {
+    // Nouvelle tentative programmée, accès maintenu pendant la période de grâce
+    Retry { attempt: i64, next_retry_at: DateTime<Utc>, message: String },
+    // Tentatives épuisées: action finale à appliquer (aussi côté Stripe)
+    Final { action: DunningFinalAction, message: String },
+    // Événement déjà traité (webhook rejoué)
+    AlreadyHandled,
+}
+
+/// Informations reprises dans les notifications
+pub struct NotificationContext<'a> {
+    pub plan_name: &'a str,
+    pub amount: i64,
+}
+
+/// Nombre total de tentatives (la première comprise)
+pub fn max_attempts(policy: &DunningPolicy) -> i64 {
+    policy.retry_schedule_days.len() as i64 + 1
+}
+
+fn action_label(action: DunningFinalAction) -> &'static str {
+    match action {
+        DunningFinalAction::Cancel => "résilié",
+        DunningFinalAction::Pause => "suspendu",
+        DunningFinalAction::MarkUnpaid => "impayé, l'accès est suspendu jusqu'au règlement",
+    }
+}
+
+/// Remplir un modèle de notification
+pub fn render_template(template: &str, vars: &[(&str, String)]) -> String {
+    vars.iter().fold(template.to_string(), |text, (name, value)| {
+        text.replace(&format!("{{{}}}", name), value)
+    })
+}
+
+/// Enregistrer un échec de paiement et déterminer l'étape de relance
+///
+/// `attempt_count` est le compteur de tentatives de la facture Stripe (0 si
+/// inconnu: on compte alors localement).
+pub fn on_payment_failed(
+    subscription: &mut UserSubscription,
+    policy: &DunningPolicy,
+    invoice_id: &str,
+    attempt_count: i64,
+    context: &NotificationContext,
+    now: DateTime<Utc>,
+) -> DunningStep {
+    // Même facture: on poursuit les relances, sinon on repart de zéro
+    let previous = subscription.dunning.as_ref().filter(|d| d.stripe_invoice_id == invoice_id);
+    let previous_attempts = previous.map(|d| d.attempts).unwrap_or(0);
+    let started_at = previous.map(|d| d.started_at).unwrap_or(now);
+    let attempt = if attempt_count > 0 { attempt_count } else { previous_attempts + 1 };
+    
+    if attempt <= previous_attempts {
+        return DunningStep::AlreadyHandled;
+    }
+    
+    let grace_until = started_at + Duration::days(policy.grace_period_days);
+    let mut vars = vec![
+        ("plan", context.plan_name.to_string()),
+        ("amount", format_eur(context.amount)),
+        ("attempt", attempt.to_string()),
+        ("max_attempts", max_attempts(policy).to_string()),
+        ("grace_until", grace_until.format("%d/%m/%Y").to_string()),
+    ];
+    
+    let retry_delay = policy.retry_schedule_days.get(attempt as usize - 1);
+    let next_retry_at = retry_delay.map(|days| now + Duration::days(*days));
+    
+    subscription.dunning = Some(DunningState {
+        stripe_invoice_id: invoice_id.to_string(),
+        attempts: attempt,
+        started_at,
+        next_retry_at,
+        grace_until,
+        technical_failures: 0,
+    });
+    
+    match next_retry_at {
+        Some(next_retry_at) => {
+            subscription.status = SubscriptionStatus::PastDue;
+            vars.push(("next_retry", next_retry_at.format("%d/%m/%Y").to_string()));
+            
+            let index = (attempt as usize - 1).min(policy.step_templates.len().saturating_sub(1));
+            let template = policy.step_templates.get(index).map(String::as_str).unwrap_or_default();
+            DunningStep::Retry { attempt, next_retry_at, message: render_template(template, &vars) }
+        }
+        None => {
+            let action = policy.final_action;
+            subscription.status = match action {
+                DunningFinalAction::Cancel => SubscriptionStatus::Cancelled,
+                DunningFinalAction::Pause => SubscriptionStatus::Paused,
+                DunningFinalAction::MarkUnpaid => SubscriptionStatus::Unpaid,
+            };
+            vars.push(("action", action_label(action).to_string()));
+            
+            DunningStep::Final { action, message: render_template(&policy.final_template, &vars) }
+        }
+    }
+}
+
+/// Délai avant de relancer une tentative qui n'a pas atteint la banque
+///
+/// Erreur réseau, Stripe indisponible, ...: le délai double à chaque erreur
+/// consécutive, sans dépasser une journée.
+pub fn technical_retry_delay(technical_failures: u32) -> Duration {
+    Duration::minutes(15 * 2i64.pow(technical_failures.min(7))).min(Duration::days(1))
+}
+
+/// Paiement reçu: sortir des relances et réactiver l'abonnement
+///
+/// Retourne le message de rétablissement si l'abonnement était en relance.
+pub fn on_payment_recovered(
+    subscription: &mut UserSubscription,
+    policy: &DunningPolicy,
+    context: &NotificationContext,
+) -> Option<String> {
+    subscription.dunning.take()?;
+    
+    // Un abonnement résilié par les relances ne revient pas
+    if subscription.status != SubscriptionStatus::Cancelled {
+        subscription.status = SubscriptionStatus::Active;
+    }
+    
+    Some(render_template(&policy.recovered_template, &[
+        ("plan", context.plan_name.to_string()),
+        ("amount", format_eur(context.amount)),
+    ]))
+}
+
+/// L'accès est maintenu pendant la période de grâce d'un abonnement en relance
+pub fn in_grace_period(subscription: &UserSubscription, now: DateTime<Utc>) -> bool {
+    subscription.status == SubscriptionStatus::PastDue
+        && subscription.dunning.as_ref().is_some_and(|d| now < d.grace_until)
+}
@@ -0,0 +1,148 @@
+// Droits d'accès au contenu accordés par les abonnements
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::models::{SubscriptionStatus, UserSubscription};
+use crate::services::dunning;
+
+/// Motif de la décision d'accès
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessReason {
+    Active,
+    Trialing,
+    GracePeriod,    // Paiement en échec, accès maintenu pendant les relances
+    PastDue,        // Période de grâce écoulée
+    Paused,
+    PeriodEnded,    // Annulation en fin de période arrivée à échéance
+    Cancelled,
+    Unpaid,
+    Incomplete,
+    NotIncluded,    // La formule ne comprend pas ce droit
+    NoSubscription,
+}
+
+/// Accès donné par un abonnement, quelle que soit sa formule
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubscriptionAccess {
+    pub granted: bool,
+    pub reason: AccessReason,
+    pub valid_until: Option<DateTime<Utc>>, // Prochaine date où la décision peut changer
+}
+
+fn denied(reason: AccessReason) -> SubscriptionAccess {
+    SubscriptionAccess { granted: false, reason, valid_until: None }
+}
+
+/// Évaluer l'accès d'un abonnement à une date
+///
+/// Une annulation programmée coupe l'accès à la fin de la période payée; sans
+/// annulation, la fin de période n'est qu'un renouvellement.
+pub fn subscription_access(subscription: &UserSubscription, now: DateTime<Utc>) -> SubscriptionAccess {
+    let reason = match subscription.status {
+        SubscriptionStatus::Active => AccessReason::Active,
+        SubscriptionStatus::Trialing => AccessReason::Trialing,
+        SubscriptionStatus::PastDue if dunning::in_grace_period(subscription, now) => {
+            return SubscriptionAccess {
+                granted: true,
+                reason: AccessReason::GracePeriod,
+                valid_until: subscription.dunning.as_ref().map(|d| d.grace_until),
+            };
+        }
+        SubscriptionStatus::PastDue => return denied(AccessReason::PastDue),
+        // Le refus ne dure que jusqu'à la reprise programmée
+        SubscriptionStatus::Paused => {
+            return SubscriptionAccess {
+                valid_until: subscription.pause.as_ref().and_then(|pause| pause.resumes_at),
+                ..denied(AccessReason::Paused)
+            };
+        }
+        SubscriptionStatus::Cancelled => return denied(AccessReason::Cancelled),
+        SubscriptionStatus::Unpaid => return denied(AccessReason::Unpaid),
+        SubscriptionStatus::Incomplete => return denied(AccessReason::Incomplete),
+    };
+    
+    if subscription.cancel_at_period_end && now >= subscription.current_period_end {
+        return denied(AccessReason::PeriodEnded);
+    }
+    let valid_until = (now < subscription.current_period_end).then_some(subscription.current_period_end);
+    
+    SubscriptionAccess { granted: true, reason, valid_until }
+}
+
+/// Réponse à « l'utilisateur X peut-il lire Y ? »
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct AccessCheck {
+    pub user_id: String,
+    pub entitlement: String,
+    pub granted: bool,
+    pub reason: AccessReason,
+    pub subscription_id: Option<String>,
+    pub plan_id: Option<String>,
+    pub valid_until: Option<DateTime<Utc>>,
+    pub checked_at: DateTime<Utc>,
+}
+
+/// Vérifier un droit parmi les abonnements d'un utilisateur (avec les droits de leur formule)
+///
+/// Un abonnement qui donne accès l'emporte (le plus durable d'abord); sinon le
+/// refus explique la situation de l'abonnement le plus récent concerné.
+pub fn check_access<'a>(
+    user_id: &str,
+    entitlement: &str,
+    subscriptions: impl IntoIterator<Item = (&'a UserSubscription, &'a [String])>,
+    now: DateTime<Utc>,
+) -> AccessCheck {
+    let mut best: Option<(&UserSubscription, SubscriptionAccess)> = None;
+    let mut other_plan: Option<&UserSubscription> = None;
+    
+    for (subscription, entitlements) in subscriptions {
+        if !entitlements.iter().any(|name| name == entitlement) {
+            if other_plan.is_none_or(|known| subscription.created_at > known.created_at) {
+                other_plan = Some(subscription);
+            }
+            continue;
+        }
+        
+        let access = subscription_access(subscription, now);
+        let better = match &best {
+            None => true,
+            Some((known, known_access)) => match (access.granted, known_access.granted) {
+                (true, false) => true,
+                (false, true) => false,
+                // Sans échéance, l'accès dure au moins autant qu'avec une échéance
+                (true, true) => access.valid_until.is_none_or(|until| known_access.valid_until.is_some_and(|k| until > k)),
+                (false, false) => subscription.created_at > known.created_at,
+            },
+        };
+        if better {
+            best = Some((subscription, access));
+        }
+    }
+    
+    let (subscription, access) = match (best, other_plan) {
+        (Some((subscription, access)), _) => (Some(subscription), access),
+        (None, Some(subscription)) => (Some(subscription), denied(AccessReason::NotIncluded)),
+        (None, None) => (None, denied(AccessReason::NoSubscription)),
+    };
+    
+    AccessCheck {
+        user_id: user_id.to_string(),
+        entitlement: entitlement.to_string(),
+        granted: access.granted,
+        reason: access.reason,
+        subscription_id: subscription.map(|s| s.id.clone()),
+        plan_id: subscription.map(|s| s.plan_id.clone()),
+        valid_until: access.valid_until,
+        checked_at: now,
+    }
+}
+
+/// Durée de mise en cache d'une réponse: jamais au-delà du prochain changement possible
+pub fn cache_max_age(check: &AccessCheck, max_secs: i64) -> i64 {
+    match check.valid_until {
+        Some(until) => (until - check.checked_at).num_seconds().clamp(0, max_secs),
+        None => max_secs,
+    }
+}
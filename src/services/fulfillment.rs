@@ -0,0 +1,105 @@
+// Workflow logistique: préparation, expédition, livraison, retour
+
+use axum::{http::StatusCode, Json};
+use chrono::Utc;
+
+use crate::models::{ApiError, FulfillmentChange, FulfillmentStatus, Order, OrderStatus, Shipment};
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum FulfillmentError {
+    #[error("La commande doit être payée ou autorisée avant préparation (statut: {0:?})")]
+    NotPaid(OrderStatus),
+    #[error("La commande est déjà au stade logistique {0:?}")]
+    AlreadyInState(FulfillmentStatus),
+    #[error("Étape logistique interdite: {from:?} → {to:?}")]
+    Forbidden { from: FulfillmentStatus, to: FulfillmentStatus },
+    #[error("Transporteur et numéro de suivi requis pour expédier")]
+    MissingTracking,
+    #[error("Préparation commencée ({0:?}): la commande ne peut plus être modifiée")]
+    EditLocked(FulfillmentStatus),
+}
+
+impl From<FulfillmentError> for (StatusCode, Json<ApiError>) {
+    fn from(err: FulfillmentError) -> Self {
+        let status = match err {
+            FulfillmentError::MissingTracking => StatusCode::BAD_REQUEST,
+            _ => StatusCode::CONFLICT,
+        };
+        (status, Json(ApiError { error: err.to_string() }))
+    }
+}
+
+/// Étapes logistiques autorisées
+pub fn can_advance(from: FulfillmentStatus, to: FulfillmentStatus) -> bool {
+    use FulfillmentStatus::*;
+    matches!(
+        (from, to),
+        (Unfulfilled, Picking)
+            | (Picking, Shipped)
+            | (Shipped, Delivered)
+            | (Shipped, Returned) // Colis refusé ou non distribué
+            | (Delivered, Returned)
+    )
+}
+
+/// Une commande est modifiable tant que la préparation n'a pas commencé
+pub fn ensure_editable(order: &Order) -> Result<(), FulfillmentError> {
+    match order.fulfillment_status {
+        FulfillmentStatus::Unfulfilled => Ok(()),
+        status => Err(FulfillmentError::EditLocked(status)),
+    }
+}
+
+/// Vérifier qu'une étape est possible (avant toute capture de paiement)
+pub fn check_advance(order: &Order, to: FulfillmentStatus) -> Result<(), FulfillmentError> {
+    if order.fulfillment_status == to {
+        return Err(FulfillmentError::AlreadyInState(to));
+    }
+    if !can_advance(order.fulfillment_status, to) {
+        return Err(FulfillmentError::Forbidden { from: order.fulfillment_status, to });
+    }
+    // Pas de préparation sans paiement (encaissé, ou autorisé en capture manuelle)
+    if to == FulfillmentStatus::Picking
+        && !matches!(order.status, OrderStatus::Completed | OrderStatus::Authorized)
+    {
+        return Err(FulfillmentError::NotPaid(order.status.clone()));
+    }
+    Ok(())
+}
+
+/// Faire avancer la logistique et l'historiser
+///
+/// `shipment` est obligatoire pour passer en `Shipped`.
+pub fn advance(
+    order: &mut Order,
+    to: FulfillmentStatus,
+    shipment: Option<Shipment>,
+    reason: &str,
+) -> Result<(), FulfillmentError> {
+    check_advance(order, to)?;
+    
+    let now = Utc::now();
+    match to {
+        FulfillmentStatus::Shipped => {
+            let shipment = shipment.ok_or(FulfillmentError::MissingTracking)?;
+            order.shipments.push(shipment);
+        }
+        FulfillmentStatus::Delivered => {
+            if let Some(shipment) = order.shipments.last_mut() {
+                shipment.delivered_at = Some(now);
+            }
+        }
+        _ => {}
+    }
+    
+    order.fulfillment_history.push(FulfillmentChange {
+        from: order.fulfillment_status,
+        to,
+        at: now,
+        reason: reason.to_string(),
+    });
+    order.fulfillment_status = to;
+    order.updated_at = now;
+    
+    Ok(())
+}
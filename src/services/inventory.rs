@@ -0,0 +1,99 @@
+// Mouvements de stock (produit simple ou variante)
+
+use axum::{http::StatusCode, Json};
+use dashmap::DashMap;
+
+use crate::models::{ApiError, OrderItem, Product};
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum StockError {
+    #[error("Stock insuffisant pour {name}. Disponible: {available}")]
+    Insufficient { name: String, available: i32 },
+    #[error("Produit {0} introuvable")]
+    UnknownProduct(String),
+}
+
+impl From<StockError> for (StatusCode, Json<ApiError>) {
+    fn from(err: StockError) -> Self {
+        let status = match err {
+            StockError::Insufficient { .. } => StatusCode::CONFLICT,
+            StockError::UnknownProduct(_) => StatusCode::NOT_FOUND,
+        };
+        (status, Json(ApiError { error: err.to_string() }))
+    }
+}
+
+/// Ajuster le stock d'un produit ou d'une de ses variantes
+///
+/// `delta` est négatif pour une sortie (paiement) et positif pour une remise
+/// en stock (remboursement, annulation). Retourne le nouveau stock, ou `None`
+/// si le produit ou la variante n'existe plus.
+pub fn adjust_stock(
+    products: &DashMap<String, Product>,
+    product_id: &str,
+    variant_sku: Option<&str>,
+    delta: i32,
+) -> Option<i32> {
+    let mut product = products.get_mut(product_id)?;
+    
+    let stock = match variant_sku {
+        Some(sku) => {
+            let variant = product.variants.iter_mut().find(|v| v.sku == sku)?;
+            variant.stock += delta;
+            variant.stock
+        }
+        None => {
+            product.stock += delta;
+            product.stock
+        }
+    };
+    
+    Some(stock)
+}
+
+/// Réserver le stock des articles d'une commande
+pub fn reserve_items(products: &DashMap<String, Product>, items: &[OrderItem]) {
+    for item in items {
+        adjust_stock(products, &item.product_id, item.variant_sku.as_deref(), -item.quantity);
+    }
+}
+
+/// Libérer le stock réservé par une commande
+pub fn release_items(products: &DashMap<String, Product>, items: &[OrderItem]) {
+    for item in items {
+        adjust_stock(products, &item.product_id, item.variant_sku.as_deref(), item.quantity);
+    }
+}
+
+/// Réserver le stock des articles seulement s'il est disponible pour chacun
+///
+/// Le contrôle et la sortie de stock se font sous le verrou du produit; en cas
+/// d'échec, les articles déjà réservés sont remis en stock.
+pub fn try_reserve_items(products: &DashMap<String, Product>, items: &[OrderItem]) -> Result<(), StockError> {
+    for (index, item) in items.iter().enumerate() {
+        if let Err(e) = try_reserve(products, item) {
+            release_items(products, &items[..index]);
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+fn try_reserve(products: &DashMap<String, Product>, item: &OrderItem) -> Result<(), StockError> {
+    let mut product = products.get_mut(&item.product_id)
+        .ok_or_else(|| StockError::UnknownProduct(item.product_id.clone()))?;
+    
+    let stock = match item.variant_sku.as_deref() {
+        Some(sku) => &mut product.variants.iter_mut()
+            .find(|v| v.sku == sku)
+            .ok_or_else(|| StockError::UnknownProduct(format!("{} ({})", item.product_id, sku)))?
+            .stock,
+        None => &mut product.stock,
+    };
+    
+    if *stock < item.quantity {
+        return Err(StockError::Insufficient { name: item.product_name.clone(), available: *stock });
+    }
+    *stock -= item.quantity;
+    Ok(())
+}
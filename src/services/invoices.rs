@@ -0,0 +1,111 @@
+// Émission des factures: numérotation continue et calcul de la TVA
+
+use axum::{http::StatusCode, Json};
+use chrono::{DateTime, Utc};
+
+use crate::models::{ApiError, Invoice, InvoiceLine, Merchant, Order, OrderStatus};
+use crate::services::refunds;
+use crate::state::AppState;
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum InvoiceError {
+    #[error("Facture disponible une fois la commande payée (statut: {0:?})")]
+    NotPaid(OrderStatus),
+}
+
+impl From<InvoiceError> for (StatusCode, Json<ApiError>) {
+    fn from(err: InvoiceError) -> Self {
+        (StatusCode::CONFLICT, Json(ApiError { error: err.to_string() }))
+    }
+}
+
+/// Numéro affiché sur la facture
+pub fn format_number(sequence: u64) -> String {
+    format!("FAC-{:06}", sequence)
+}
+
+/// TVA comprise dans un montant TTC (arrondie au centime)
+pub fn tax_included(amount: i64, vat_rate_percent: i64) -> i64 {
+    let divisor = 100 + vat_rate_percent;
+    (amount * vat_rate_percent + divisor / 2) / divisor
+}
+
+/// Construire la facture d'une commande (les prix du catalogue sont TTC)
+pub fn build_invoice(
+    order: &Order,
+    number: String,
+    merchant: &Merchant,
+    vat_rate_percent: i64,
+    issued_at: DateTime<Utc>,
+) -> Invoice {
+    let mut lines: Vec<InvoiceLine> = order.items.iter()
+        .map(|item| InvoiceLine {
+            description: match &item.variant_sku {
+                Some(sku) => format!("{} ({})", item.product_name, sku),
+                None => item.product_name.clone(),
+            },
+            quantity: item.quantity,
+            unit_price: item.price,
+            total: item.price * item.quantity as i64,
+        })
+        .collect();
+    
+    // Capture partielle à l'expédition: seul le montant capturé est facturé
+    let total = refunds::captured_amount(order);
+    if total < order.total {
+        let adjustment = total - order.total;
+        lines.push(InvoiceLine {
+            description: "Ajustement à l'expédition".to_string(),
+            quantity: 1,
+            unit_price: adjustment,
+            total: adjustment,
+        });
+    }
+    let tax_amount = tax_included(total, vat_rate_percent);
+    
+    Invoice {
+        number,
+        order_id: order.id.clone(),
+        user_id: order.user_id.clone(),
+        issued_at,
+        merchant: merchant.clone(),
+        billing_address: order.shipping_address.clone(),
+        lines,
+        shipping: order.shipping.clone(),
+        vat_rate_percent,
+        total_excl_tax: total - tax_amount,
+        tax_amount,
+        total,
+    }
+}
+
+/// Facture de la commande, émise au premier appel
+///
+/// Le numéro n'est attribué que sous le verrou de séquence et au moment où
+/// la facture est enregistrée: pas de doublon ni de trou dans la numérotation.
+pub fn issue_invoice(state: &AppState, order: &Order) -> Result<Invoice, InvoiceError> {
+    if !matches!(
+        order.status,
+        OrderStatus::Completed | OrderStatus::PartiallyRefunded | OrderStatus::Refunded
+    ) {
+        return Err(InvoiceError::NotPaid(order.status.clone()));
+    }
+    
+    let mut sequence = state.invoice_sequence.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(invoice) = state.invoices.get(&order.id) {
+        return Ok(invoice.clone());
+    }
+    
+    *sequence += 1;
+    let invoice = build_invoice(
+        order,
+        format_number(*sequence),
+        &state.config.merchant,
+        state.config.vat_rate_percent,
+        Utc::now(),
+    );
+    state.invoices.insert(order.id.clone(), invoice.clone());
+    
+    tracing::info!("Facture {} émise pour la commande {}", invoice.number, order.id);
+    Ok(invoice)
+}
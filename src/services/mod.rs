@@ -1 +1,16 @@
+pub mod accounting;
+pub mod captures;
+pub mod documents;
+pub mod dunning;
+pub mod entitlements;
+pub mod fulfillment;
+pub mod inventory;
+pub mod invoices;
+pub mod order_listing;
+pub mod order_state;
+pub mod policies;
+pub mod refunds;
+pub mod shipping;
 pub mod stripe_service;
+pub mod subscriptions;
+pub mod usage;
@@ -0,0 +1,165 @@
+// Listing des commandes: filtres, tri et pagination par curseur
+
+use axum::{http::StatusCode, Json};
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::Deserialize;
+use std::cmp::Ordering;
+
+use crate::models::{ApiError, FulfillmentStatus, Order, OrderPage, OrderStatus};
+
+pub const DEFAULT_PAGE_SIZE: usize = 20;
+pub const MAX_PAGE_SIZE: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum OrderListError {
+    #[error("Curseur invalide ou incompatible avec le tri demandé")]
+    InvalidCursor,
+    #[error("Intervalle invalide: {0}")]
+    InvalidRange(&'static str),
+}
+
+impl From<OrderListError> for (StatusCode, Json<ApiError>) {
+    fn from(err: OrderListError) -> Self {
+        (StatusCode::BAD_REQUEST, Json(ApiError { error: err.to_string() }))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderSortField {
+    #[default]
+    CreatedAt,
+    Total,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortDirection {
+    Asc,
+    #[default]
+    Desc, // Plus récentes (ou plus chères) d'abord
+}
+
+/// Critères de sélection; `user_id` absent = toutes les commandes (admin)
+#[derive(Debug, Clone, Default)]
+pub struct OrderFilter {
+    pub user_id: Option<String>,
+    pub status: Option<OrderStatus>,
+    pub fulfillment_status: Option<FulfillmentStatus>,
+    pub created_from: Option<DateTime<Utc>>,
+    pub created_to: Option<DateTime<Utc>>,
+    pub min_total: Option<i64>,
+    pub max_total: Option<i64>,
+}
+
+impl OrderFilter {
+    fn matches(&self, order: &Order) -> bool {
+        self.user_id.as_ref().is_none_or(|user_id| &order.user_id == user_id)
+            && self.status.as_ref().is_none_or(|status| &order.status == status)
+            && self.fulfillment_status.is_none_or(|status| order.fulfillment_status == status)
+            && self.created_from.is_none_or(|from| order.created_at >= from)
+            && self.created_to.is_none_or(|to| order.created_at <= to)
+            && self.min_total.is_none_or(|min| order.total >= min)
+            && self.max_total.is_none_or(|max| order.total <= max)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PageRequest {
+    pub sort: OrderSortField,
+    pub direction: SortDirection,
+    pub cursor: Option<String>,
+    pub limit: Option<usize>,
+}
+
+// Clé de tri; l'id de commande départage les égalités pour un ordre stable
+fn sort_key(order: &Order, sort: OrderSortField) -> (i64, String) {
+    let key = match sort {
+        OrderSortField::CreatedAt => order.created_at.timestamp_micros(),
+        OrderSortField::Total => order.total,
+    };
+    (key, order.id.clone())
+}
+
+// Le curseur encode la clé de la dernière commande renvoyée: "<champ>:<valeur>:<id>"
+fn encode_cursor((key, id): &(i64, String), sort: OrderSortField) -> String {
+    let field = match sort {
+        OrderSortField::CreatedAt => "created_at",
+        OrderSortField::Total => "total",
+    };
+    format!("{}:{}:{}", field, key, id)
+}
+
+fn decode_cursor(cursor: &str, sort: OrderSortField) -> Result<(i64, String), OrderListError> {
+    let mut parts = cursor.splitn(3, ':');
+    let (Some(field), Some(key), Some(id)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(OrderListError::InvalidCursor);
+    };
+    let expected = match sort {
+        OrderSortField::CreatedAt => "created_at",
+        OrderSortField::Total => "total",
+    };
+    if field != expected || id.is_empty() {
+        return Err(OrderListError::InvalidCursor);
+    }
+    let key = key.parse().map_err(|_| OrderListError::InvalidCursor)?;
+    Ok((key, id.to_string()))
+}
+
+/// Filtrer, trier et découper une page de commandes
+///
+/// La pagination par curseur reste stable si des commandes sont créées
+/// entre deux pages (contrairement à un offset). Seules les clés de tri sont
+/// relevées sous verrou; le tri se fait sans bloquer les mises à jour, puis
+/// seules les commandes de la page renvoyée sont relues et copiées.
+pub fn list_orders(
+    orders: &DashMap<String, Order>,
+    filter: &OrderFilter,
+    page: &PageRequest,
+) -> Result<OrderPage, OrderListError> {
+    if let (Some(from), Some(to)) = (filter.created_from, filter.created_to) {
+        if from > to {
+            return Err(OrderListError::InvalidRange("created_from postérieur à created_to"));
+        }
+    }
+    if let (Some(min), Some(max)) = (filter.min_total, filter.max_total) {
+        if min > max {
+            return Err(OrderListError::InvalidRange("min_total supérieur à max_total"));
+        }
+    }
+    let after = page.cursor.as_deref()
+        .map(|cursor| decode_cursor(cursor, page.sort))
+        .transpose()?;
+    let limit = page.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    
+    let mut matching: Vec<(i64, String)> = orders.iter()
+        .filter(|order| filter.matches(order))
+        .map(|order| sort_key(&order, page.sort))
+        .collect();
+    let total_count = matching.len();
+    
+    let directed = |ord: Ordering| match page.direction {
+        SortDirection::Asc => ord,
+        SortDirection::Desc => ord.reverse(),
+    };
+    matching.sort_by(|a, b| directed(a.cmp(b)));
+    
+    let start = match &after {
+        Some(after) => matching.partition_point(|key| directed(key.cmp(after)) != Ordering::Greater),
+        None => 0,
+    };
+    
+    let keys = &matching[start..(start + limit).min(matching.len())];
+    // Commande supprimée depuis le relevé des clés: absente de la page
+    let page_orders: Vec<Order> = keys.iter()
+        .filter_map(|(_, id)| orders.get(id).map(|order| order.clone()))
+        .collect();
+    let next_cursor = if matching.len() > start + limit {
+        keys.last().map(|key| encode_cursor(key, page.sort))
+    } else {
+        None
+    };
+    
+    Ok(OrderPage { orders: page_orders, total_count, next_cursor })
+}
@@ -0,0 +1,91 @@
+// Machine à états des commandes: transitions autorisées et historique
+
+use axum::{http::StatusCode, Json};
+use chrono::Utc;
+
+use crate::models::{ApiError, Order, OrderStatus, OrderStatusChange, StatusChangeSource};
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum TransitionError {
+    #[error("La commande est déjà dans l'état {0:?}")]
+    AlreadyInState(OrderStatus),
+    #[error("Transition interdite: {from:?} → {to:?}")]
+    Forbidden { from: OrderStatus, to: OrderStatus },
+}
+
+impl From<TransitionError> for (StatusCode, Json<ApiError>) {
+    fn from(err: TransitionError) -> Self {
+        (StatusCode::CONFLICT, Json(ApiError { error: err.to_string() }))
+    }
+}
+
+/// Transitions autorisées entre statuts de commande
+///
+/// Stripe fait foi: un paiement confirmé ou échoué est accepté depuis
+/// `Pending` (commande modifiée) comme depuis `Failed` (nouvelle tentative).
+/// L'annulation d'une commande `Processing` ou `Authorized` suppose que son
+/// PaymentIntent a d'abord été annulé sur Stripe.
+pub fn can_transition(from: &OrderStatus, to: &OrderStatus) -> bool {
+    use OrderStatus::*;
+    matches!(
+        (from, to),
+        (Pending, Processing)
+            | (Pending, Authorized)
+            | (Pending, Completed)
+            | (Pending, Failed)
+            | (Pending, Cancelled)
+            | (Processing, Authorized)
+            | (Processing, Completed)
+            | (Processing, Failed)
+            | (Processing, Cancelled)
+            | (Failed, Pending)
+            | (Failed, Processing)
+            | (Failed, Authorized)
+            | (Failed, Completed)
+            | (Failed, Cancelled)
+            | (Authorized, Completed)
+            | (Authorized, Cancelled)
+            | (Completed, PartiallyRefunded)
+            | (Completed, Refunded)
+            | (PartiallyRefunded, Refunded)
+    )
+}
+
+/// Appliquer une transition et l'ajouter à l'historique de la commande
+pub fn transition(
+    order: &mut Order,
+    to: OrderStatus,
+    source: StatusChangeSource,
+    reason: &str,
+) -> Result<(), TransitionError> {
+    if order.status == to {
+        return Err(TransitionError::AlreadyInState(to));
+    }
+    if !can_transition(&order.status, &to) {
+        return Err(TransitionError::Forbidden { from: order.status.clone(), to });
+    }
+    
+    let now = Utc::now();
+    order.status_history.push(OrderStatusChange {
+        from: Some(order.status.clone()),
+        to: to.clone(),
+        at: now,
+        source,
+        reason: reason.to_string(),
+    });
+    order.status = to;
+    order.updated_at = now;
+    
+    Ok(())
+}
+
+/// Premier élément de l'historique d'une commande qui vient d'être créée
+pub fn creation_entry(source: StatusChangeSource) -> OrderStatusChange {
+    OrderStatusChange {
+        from: None,
+        to: OrderStatus::Pending,
+        at: Utc::now(),
+        source,
+        reason: "Commande créée".to_string(),
+    }
+}
@@ -0,0 +1,201 @@
+// Politiques d'annulation et de modification des commandes
+
+use axum::{http::StatusCode, Json};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::fmt;
+
+use crate::models::{
+    ApiError, FulfillmentStatus, Order, OrderPolicy, PolicyOverride, PostShipmentCancellation,
+};
+use crate::state::AppState;
+
+/// Politique résolue pour une commande, avec les règles qui y ont contribué
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AppliedPolicy {
+    pub sources: Vec<String>, // "défaut", "niveau vip", "produit cap_003"
+    pub policy: OrderPolicy,
+}
+
+impl fmt::Display for AppliedPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let after_shipment = match self.policy.cancel_after_shipment {
+            PostShipmentCancellation::Refuse => "refusée".to_string(),
+            PostShipmentCancellation::Return => {
+                format!("retour sous {} jours", self.policy.return_window_days)
+            }
+        };
+        write!(
+            f,
+            "politique {}: annulation {}h, modification {}h, après expédition {}",
+            self.sources.join(" + "),
+            self.policy.cancel_window_hours,
+            self.policy.modify_window_hours,
+            after_shipment,
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum PolicyError {
+    #[error("Délai d'annulation dépassé: commande passée il y a {elapsed_hours}h ({policy})")]
+    CancelWindowExpired { elapsed_hours: i64, policy: AppliedPolicy },
+    #[error("Délai de modification dépassé: commande passée il y a {elapsed_hours}h ({policy})")]
+    ModifyWindowExpired { elapsed_hours: i64, policy: AppliedPolicy },
+    #[error("Commande expédiée: annulation impossible ({policy})")]
+    CancelAfterShipmentRefused { policy: AppliedPolicy },
+    #[error("Délai de retour dépassé: expédiée il y a {elapsed_days} jours ({policy})")]
+    ReturnWindowExpired { elapsed_days: i64, policy: AppliedPolicy },
+    #[error("Préparation commencée: la commande ne peut plus être annulée avant son expédition")]
+    PreparationStarted,
+    #[error("Un retour a déjà été demandé pour cette commande")]
+    ReturnAlreadyRequested,
+    #[error("Commande déjà retournée")]
+    AlreadyReturned,
+}
+
+impl From<PolicyError> for (StatusCode, Json<ApiError>) {
+    fn from(err: PolicyError) -> Self {
+        let status = match err {
+            PolicyError::CancelWindowExpired { .. }
+            | PolicyError::ModifyWindowExpired { .. }
+            | PolicyError::ReturnWindowExpired { .. } => StatusCode::BAD_REQUEST,
+            _ => StatusCode::CONFLICT,
+        };
+        (status, Json(ApiError { error: err.to_string() }))
+    }
+}
+
+/// Issue d'une demande d'annulation autorisée
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CancellationOutcome {
+    Cancel, // Annulation classique: paiement annulé, stock libéré
+    Return, // Déjà expédiée: le client renvoie le colis
+}
+
+fn apply(policy: &mut OrderPolicy, rule: &PolicyOverride) {
+    if let Some(hours) = rule.cancel_window_hours {
+        policy.cancel_window_hours = hours;
+    }
+    if let Some(hours) = rule.modify_window_hours {
+        policy.modify_window_hours = hours;
+    }
+    if let Some(behaviour) = rule.cancel_after_shipment {
+        policy.cancel_after_shipment = behaviour;
+    }
+    if let Some(days) = rule.return_window_days {
+        policy.return_window_days = days;
+    }
+}
+
+/// Combiner politique par défaut, niveau client puis produits
+///
+/// Le niveau client remplace les valeurs par défaut; les surcharges produit
+/// passent en dernier (contraintes physiques) et, si plusieurs produits en
+/// ont, la plus restrictive l'emporte pour chaque délai.
+pub fn resolve<'a>(
+    base: &OrderPolicy,
+    tier: Option<(&str, &PolicyOverride)>,
+    products: impl IntoIterator<Item = (&'a str, &'a PolicyOverride)>,
+) -> AppliedPolicy {
+    let mut policy = base.clone();
+    let mut sources = vec!["défaut".to_string()];
+    
+    if let Some((name, rule)) = tier {
+        apply(&mut policy, rule);
+        sources.push(format!("niveau {}", name));
+    }
+    
+    let mut restricted: Option<PolicyOverride> = None;
+    for (product_id, rule) in products {
+        let merged = restricted.get_or_insert_with(PolicyOverride::default);
+        merged.cancel_window_hours = min_option(merged.cancel_window_hours, rule.cancel_window_hours);
+        merged.modify_window_hours = min_option(merged.modify_window_hours, rule.modify_window_hours);
+        merged.return_window_days = min_option(merged.return_window_days, rule.return_window_days);
+        merged.cancel_after_shipment = match (merged.cancel_after_shipment, rule.cancel_after_shipment) {
+            (Some(PostShipmentCancellation::Refuse), _) | (_, Some(PostShipmentCancellation::Refuse)) => {
+                Some(PostShipmentCancellation::Refuse)
+            }
+            (current, other) => current.or(other),
+        };
+        sources.push(format!("produit {}", product_id));
+    }
+    if let Some(rule) = restricted {
+        apply(&mut policy, &rule);
+    }
+    
+    AppliedPolicy { sources, policy }
+}
+
+fn min_option(a: Option<i64>, b: Option<i64>) -> Option<i64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Politique applicable à une commande (niveau du client, produits commandés)
+pub fn policy_for_order(state: &AppState, order: &Order) -> AppliedPolicy {
+    let tier = state.customer_tiers.get(&order.user_id).map(|tier| tier.clone());
+    let tier_rule = tier.as_ref()
+        .and_then(|name| state.tier_policies.get(name).map(|rule| (name.clone(), rule.clone())));
+    
+    let mut product_ids: Vec<&str> = order.items.iter().map(|item| item.product_id.as_str()).collect();
+    product_ids.sort_unstable();
+    product_ids.dedup();
+    let product_rules: Vec<(String, PolicyOverride)> = product_ids.into_iter()
+        .filter_map(|id| state.product_policies.get(id).map(|rule| (id.to_string(), rule.clone())))
+        .collect();
+    
+    resolve(
+        &state.config.order_policy,
+        tier_rule.as_ref().map(|(name, rule)| (name.as_str(), rule)),
+        product_rules.iter().map(|(id, rule)| (id.as_str(), rule)),
+    )
+}
+
+/// Vérifier qu'une commande peut encore être modifiée
+pub fn check_modify(order: &Order, applied: &AppliedPolicy, now: DateTime<Utc>) -> Result<(), PolicyError> {
+    let elapsed_hours = (now - order.created_at).num_hours();
+    if now - order.created_at > Duration::hours(applied.policy.modify_window_hours) {
+        return Err(PolicyError::ModifyWindowExpired { elapsed_hours, policy: applied.clone() });
+    }
+    Ok(())
+}
+
+/// Décider du sort d'une demande d'annulation
+///
+/// Avant expédition: délai d'annulation. Après: refus ou retour selon la
+/// politique, dans le délai de retour compté depuis l'expédition.
+pub fn check_cancel(
+    order: &Order,
+    applied: &AppliedPolicy,
+    now: DateTime<Utc>,
+) -> Result<CancellationOutcome, PolicyError> {
+    match order.fulfillment_status {
+        FulfillmentStatus::Unfulfilled => {
+            let elapsed_hours = (now - order.created_at).num_hours();
+            if now - order.created_at > Duration::hours(applied.policy.cancel_window_hours) {
+                return Err(PolicyError::CancelWindowExpired { elapsed_hours, policy: applied.clone() });
+            }
+            Ok(CancellationOutcome::Cancel)
+        }
+        // Colis en préparation: ni annulation ni retour possible à ce stade
+        FulfillmentStatus::Picking => Err(PolicyError::PreparationStarted),
+        FulfillmentStatus::Shipped | FulfillmentStatus::Delivered => {
+            if applied.policy.cancel_after_shipment == PostShipmentCancellation::Refuse {
+                return Err(PolicyError::CancelAfterShipmentRefused { policy: applied.clone() });
+            }
+            if order.return_requested_at.is_some() {
+                return Err(PolicyError::ReturnAlreadyRequested);
+            }
+            let shipped_at = order.shipments.last().map(|s| s.shipped_at).unwrap_or(order.updated_at);
+            if now - shipped_at > Duration::days(applied.policy.return_window_days) {
+                let elapsed_days = (now - shipped_at).num_days();
+                return Err(PolicyError::ReturnWindowExpired { elapsed_days, policy: applied.clone() });
+            }
+            Ok(CancellationOutcome::Return)
+        }
+        FulfillmentStatus::Returned => Err(PolicyError::AlreadyReturned),
+    }
+}
@@ -0,0 +1,153 @@
+// Calcul des remboursements: montants, quantités restantes et plafond capturé
+
+use axum::{http::StatusCode, Json};
+
+use crate::models::{ApiError, Order, OrderStatus, RefundItem};
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum RefundError {
+    #[error("Seules les commandes payées peuvent être remboursées (statut: {0:?})")]
+    NotRefundable(OrderStatus),
+    #[error("Article {0} absent de la commande")]
+    UnknownItem(String),
+    #[error("{product}: {requested} demandé(s), {remaining} remboursable(s)")]
+    QuantityExceeded { product: String, requested: i32, remaining: i32 },
+    #[error("Montant remboursable dépassé: {requested} demandé, {remaining} restant")]
+    AmountExceeded { requested: i64, remaining: i64 },
+    #[error("Rien à rembourser sur cette commande")]
+    NothingToRefund,
+}
+
+impl From<RefundError> for (StatusCode, Json<ApiError>) {
+    fn from(err: RefundError) -> Self {
+        let status = match err {
+            RefundError::NotRefundable(_) => StatusCode::CONFLICT,
+            _ => StatusCode::BAD_REQUEST,
+        };
+        (status, Json(ApiError { error: err.to_string() }))
+    }
+}
+
+/// Remboursement validé, prêt à être envoyé à Stripe
+#[derive(Debug, Clone, PartialEq)]
+pub struct RefundPlan {
+    pub amount: i64,
+    pub items: Vec<RefundItem>,
+    pub fully_refunded: bool, // La commande sera entièrement remboursée après celui-ci
+    pub sequence: usize,      // Numéro de ce remboursement sur la commande
+}
+
+impl RefundPlan {
+    /// Clé d'idempotence Stripe: même commande, même remboursement, même clé
+    ///
+    /// Un envoi rejoué (réponse perdue, double clic) retrouve le remboursement
+    /// déjà créé; le remboursement suivant de la commande change de numéro.
+    pub fn idempotency_key(&self, order_id: &str) -> String {
+        let mut content = self.amount.to_string();
+        for item in &self.items {
+            content.push_str(&format!("|{}:{}:{}", item.product_id, item.variant_sku.as_deref().unwrap_or(""), item.quantity));
+        }
+        format!("refund-{}-{}-{:016x}", order_id, self.sequence, fnv1a(content.as_bytes()))
+    }
+}
+
+// Empreinte FNV-1a 64 bits: stable d'une version de Rust à l'autre, contrairement à DefaultHasher
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+/// Montant effectivement encaissé sur la commande
+///
+/// En capture manuelle, seul le montant capturé à l'expédition est encaissé.
+pub fn captured_amount(order: &Order) -> i64 {
+    order.authorization.as_ref()
+        .and_then(|authorization| authorization.captured_amount)
+        .unwrap_or(order.total)
+}
+
+/// Montant déjà remboursé
+pub fn refunded_amount(order: &Order) -> i64 {
+    order.refunds.iter().map(|r| r.amount).sum()
+}
+
+/// Quantité déjà remboursée pour une ligne de commande
+pub fn refunded_quantity(order: &Order, product_id: &str, variant_sku: Option<&str>) -> i32 {
+    order.refunds.iter()
+        .flat_map(|r| r.items.iter())
+        .filter(|i| i.product_id == product_id && i.variant_sku.as_deref() == variant_sku)
+        .map(|i| i.quantity)
+        .sum()
+}
+
+/// Préparer un remboursement total (`items = None`) ou partiel par article
+pub fn plan_refund(order: &Order, items: Option<&[RefundItem]>) -> Result<RefundPlan, RefundError> {
+    if !matches!(order.status, OrderStatus::Completed | OrderStatus::PartiallyRefunded) {
+        return Err(RefundError::NotRefundable(order.status.clone()));
+    }
+    
+    let remaining = captured_amount(order) - refunded_amount(order);
+    if remaining <= 0 {
+        return Err(RefundError::NothingToRefund);
+    }
+    
+    let (amount, items) = match items {
+        // Total: le solde restant (port compris) et tous les articles non remboursés
+        None => {
+            let items = order.items.iter()
+                .filter_map(|item| {
+                    let quantity = item.quantity
+                        - refunded_quantity(order, &item.product_id, item.variant_sku.as_deref());
+                    (quantity > 0).then(|| RefundItem {
+                        product_id: item.product_id.clone(),
+                        variant_sku: item.variant_sku.clone(),
+                        quantity,
+                    })
+                })
+                .collect();
+            (remaining, items)
+        }
+        // Partiel: prix unitaire payé × quantité, ligne par ligne
+        Some(requested) => {
+            let mut amount = 0i64;
+            let mut planned: Vec<RefundItem> = vec![];
+            
+            for line in requested {
+                let item = order.items.iter()
+                    .find(|i| i.product_id == line.product_id && i.variant_sku == line.variant_sku)
+                    .ok_or_else(|| RefundError::UnknownItem(line.product_id.clone()))?;
+                
+                // Les lignes en double dans la requête se cumulent
+                let already_planned: i32 = planned.iter()
+                    .filter(|p| p.product_id == line.product_id && p.variant_sku == line.variant_sku)
+                    .map(|p| p.quantity)
+                    .sum();
+                let available = item.quantity
+                    - refunded_quantity(order, &item.product_id, item.variant_sku.as_deref())
+                    - already_planned;
+                
+                if line.quantity > available {
+                    return Err(RefundError::QuantityExceeded {
+                        product: item.product_name.clone(),
+                        requested: line.quantity,
+                        remaining: available.max(0),
+                    });
+                }
+                
+                amount += item.price * line.quantity as i64;
+                planned.push(line.clone());
+            }
+            
+            if amount > remaining {
+                return Err(RefundError::AmountExceeded { requested: amount, remaining });
+            }
+            (amount, planned)
+        }
+    };
+    
+    Ok(RefundPlan {
+        amount,
+        items,
+        fully_refunded: amount == remaining,
+        sequence: order.refunds.len() + 1,
+    })
+}
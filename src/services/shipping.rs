@@ -0,0 +1,41 @@
+// Calcul des frais de port (zones, tranches de poids, franco de port)
+
+use crate::models::{ShippingMethod, ShippingZone};
+
+// Pays de l'Union européenne (hors France)
+const EUROPE_COUNTRIES: &[&str] = &[
+    "AT", "BE", "BG", "CY", "CZ", "DE", "DK", "EE", "ES", "FI", "GR", "HR", "HU",
+    "IE", "IT", "LT", "LU", "LV", "MT", "NL", "PL", "PT", "RO", "SE", "SI", "SK",
+];
+
+/// Déterminer la zone de livraison à partir du code pays ISO
+pub fn zone_for_country(country: &str) -> ShippingZone {
+    let country = country.trim().to_uppercase();
+    match country.as_str() {
+        "FR" | "MC" => ShippingZone::France,
+        c if EUROPE_COUNTRIES.contains(&c) => ShippingZone::Europe,
+        _ => ShippingZone::International,
+    }
+}
+
+/// Calculer les frais de port d'une méthode pour un colis
+///
+/// Retourne `None` si la méthode ne dessert pas la zone ou si le colis
+/// dépasse la tranche de poids la plus élevée.
+pub fn quote(
+    method: &ShippingMethod,
+    zone: ShippingZone,
+    weight_grams: i32,
+    subtotal: i64,
+) -> Option<i64> {
+    let price = method.rates.iter()
+        .filter(|rate| rate.zone == zone && weight_grams <= rate.max_weight_grams)
+        .min_by_key(|rate| rate.max_weight_grams)?
+        .price;
+
+    // Franco de port au-delà du seuil
+    match method.free_shipping_threshold {
+        Some(threshold) if subtotal >= threshold => Some(0),
+        _ => Some(price),
+    }
+}
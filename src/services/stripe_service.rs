@@ -1,30 +1,128 @@
 // Service pour interagir avec l'API Stripe
 
+use crate::models::{BillingInterval, CaptureMethod, ProrationBehavior, ShippingAddress};
+use crate::services::subscriptions;
+
+use chrono::{DateTime, Utc};
+use stripe::generated::billing::subscription::SubscriptionProrationBehavior;
 use stripe::{
-    Client, CreateCustomer, CreatePaymentIntent, CreatePrice, CreateProduct, 
+    CancelPaymentIntent, CapturePaymentIntent, Client, CreateCustomer, CreatePaymentIntent, CreatePaymentIntentShipping,
+    CreatePaymentIntentShippingAddress, CreatePrice, CreateProduct, 
     CreateSetupIntent, CreateSubscription, Currency, Customer, PaymentIntent, 
-    PaymentIntentCaptureMethod, Price, Product, SetupIntent, Subscription,
-    UpdateSubscription, PaymentMethod, StripeError, PaymentIntentOffSession,
+    PaymentIntentCancellationReason, PaymentIntentCaptureMethod, Price, Product, SetupIntent, Subscription, CreateRefund, Refund,
+    UpdatePaymentIntent, UpdateSubscription, PaymentMethod, StripeError,
 };
 
 /// Créer un PaymentIntent pour un paiement unique
+///
+/// En capture manuelle, la confirmation ne fait qu'autoriser le paiement:
+/// il faut ensuite appeler `capture_payment_intent`.
 pub async fn create_payment_intent(
     client: &Client,
     amount: i64,
     order_id: &str,
+    shipping: Option<&ShippingAddress>,
+    capture_method: CaptureMethod,
 ) -> Result<PaymentIntent, StripeError> {
     let mut params = CreatePaymentIntent::new(amount, Currency::EUR);
-    params.capture_method = Some(PaymentIntentCaptureMethod::Automatic);
+    params.capture_method = Some(match capture_method {
+        CaptureMethod::Automatic => PaymentIntentCaptureMethod::Automatic,
+        CaptureMethod::Manual => PaymentIntentCaptureMethod::Manual,
+    });
     params.metadata = Some(
         [("order_id".to_string(), order_id.to_string())]
             .iter()
             .cloned()
             .collect(),
     );
+    params.shipping = shipping.map(|address| CreatePaymentIntentShipping {
+        name: address.name.clone(),
+        address: CreatePaymentIntentShippingAddress {
+            line1: Some(address.line1.clone()),
+            line2: address.line2.clone(),
+            postal_code: Some(address.postal_code.clone()),
+            city: Some(address.city.clone()),
+            country: Some(address.country.clone()),
+            ..Default::default()
+        },
+        ..Default::default()
+    });
     
     PaymentIntent::create(client, params).await
 }
 
+/// Modifier le montant d'un PaymentIntent pas encore payé
+pub async fn update_payment_intent_amount(
+    client: &Client,
+    payment_intent_id: &str,
+    amount: i64,
+) -> Result<PaymentIntent, StripeError> {
+    let payment_intent_id = payment_intent_id.parse().unwrap();
+    let mut params = UpdatePaymentIntent::new();
+    params.amount = Some(amount);
+    
+    PaymentIntent::update(client, &payment_intent_id, params).await
+}
+
+/// Récupérer un PaymentIntent (statut à jour)
+pub async fn retrieve_payment_intent(
+    client: &Client,
+    payment_intent_id: &str,
+) -> Result<PaymentIntent, StripeError> {
+    let payment_intent_id = payment_intent_id.parse().unwrap();
+    PaymentIntent::retrieve(client, &payment_intent_id, &[]).await
+}
+
+/// Annuler un PaymentIntent non encore payé
+pub async fn cancel_payment_intent(
+    client: &Client,
+    payment_intent_id: &str,
+) -> Result<PaymentIntent, StripeError> {
+    let params = CancelPaymentIntent {
+        cancellation_reason: Some(PaymentIntentCancellationReason::RequestedByCustomer),
+    };
+    
+    PaymentIntent::cancel(client, payment_intent_id, params).await
+}
+
+/// Capturer tout (`None`) ou partie d'un paiement autorisé; le reste est libéré
+pub async fn capture_payment_intent(
+    client: &Client,
+    payment_intent_id: &str,
+    amount: Option<i64>,
+) -> Result<PaymentIntent, StripeError> {
+    let params = CapturePaymentIntent {
+        amount_to_capture: amount.map(|a| a as u64),
+        ..Default::default()
+    };
+    
+    PaymentIntent::capture(client, payment_intent_id, params).await
+}
+
+/// Rembourser tout ou partie d'un PaymentIntent
+///
+/// Même clé d'idempotence, même remboursement: un appel rejoué ne rembourse pas deux fois.
+pub async fn create_refund(
+    client: &Client,
+    payment_intent_id: &str,
+    amount: i64,
+    order_id: &str,
+    idempotency_key: &str,
+) -> Result<Refund, StripeError> {
+    let mut params = CreateRefund::new();
+    params.payment_intent = Some(payment_intent_id.parse().unwrap());
+    params.amount = Some(amount);
+    params.metadata = Some(
+        [("order_id".to_string(), order_id.to_string())]
+            .iter()
+            .cloned()
+            .collect(),
+    );
+    let client = client.clone().with_strategy(stripe::RequestStrategy::Idempotent(idempotency_key.to_string()));
+    
+    Refund::create(&client, params).await
+}
+
 /// Créer un client Stripe
 pub async fn create_customer(
     client: &Client,
@@ -48,6 +146,7 @@ pub async fn create_subscription_price(
     client: &Client,
     product_name: &str,
     amount: i64,
+    interval: BillingInterval,
 ) -> Result<String, StripeError> {
     // Créer le produit
     let mut product_params = CreateProduct::new(product_name);
@@ -64,11 +163,56 @@ pub async fn create_subscription_price(
     let mut price_params = CreatePrice::new(Currency::EUR);
     price_params.product = Some(stripe::IdOrCreate::Id(&product.id));
     price_params.unit_amount = Some(amount);
+    price_params.recurring = Some(recurring_for(interval));
+    
+    let price = Price::create(client, price_params).await?;
     
-    // Configurer recurring pour un abonnement mensuel
-    let mut recurring = stripe::CreatePriceRecurring::default();
-    recurring.interval = stripe::CreatePriceRecurringInterval::Month;
-    price_params.recurring = Some(recurring);
+    Ok(price.id.to_string())
+}
+
+// Récurrence: mensuelle, trimestrielle (tous les 3 mois) ou annuelle
+fn recurring_for(interval: BillingInterval) -> stripe::CreatePriceRecurring {
+    match interval {
+        BillingInterval::Annual => stripe::CreatePriceRecurring {
+            interval: stripe::CreatePriceRecurringInterval::Year,
+            ..Default::default()
+        },
+        _ => stripe::CreatePriceRecurring {
+            interval: stripe::CreatePriceRecurringInterval::Month,
+            interval_count: Some(interval.months()),
+            ..Default::default()
+        },
+    }
+}
+
+/// Créer un prix facturé à l'usage (somme des quantités déclarées sur la période)
+///
+/// La période doit être celle du prix de l'abonnement: Stripe refuse des
+/// articles aux récurrences différentes.
+pub async fn create_metered_price(
+    client: &Client,
+    product_name: &str,
+    unit_amount: i64,
+    interval: BillingInterval,
+) -> Result<String, StripeError> {
+    let mut product_params = CreateProduct::new(product_name);
+    product_params.metadata = Some(
+        [("type".to_string(), "metered".to_string())]
+            .iter()
+            .cloned()
+            .collect(),
+    );
+    
+    let product = Product::create(client, product_params).await?;
+    
+    let mut price_params = CreatePrice::new(Currency::EUR);
+    price_params.product = Some(stripe::IdOrCreate::Id(&product.id));
+    price_params.unit_amount = Some(unit_amount);
+    price_params.recurring = Some(stripe::CreatePriceRecurring {
+        usage_type: Some(stripe::CreatePriceRecurringUsageType::Metered),
+        aggregate_usage: Some(stripe::CreatePriceRecurringAggregateUsage::Sum),
+        ..recurring_for(interval)
+    });
     
     let price = Price::create(client, price_params).await?;
     
@@ -80,23 +224,41 @@ pub async fn create_subscription(
     client: &Client,
     customer_id: &str,
     price_id: &str,
+    metered_price_id: Option<&str>,
     payment_method_id: Option<&str>,
+    trial_days: Option<i64>,
 ) -> Result<Subscription, StripeError> {
     let customer_id: stripe::CustomerId = customer_id.parse().unwrap();
     let price_id: stripe::PriceId = price_id.parse().unwrap();
     
     let mut params = CreateSubscription::new(customer_id);
     
-    // Ajouter le price via items
-    let mut item = stripe::CreateSubscriptionItems::default();
-    item.price = Some(price_id.to_string());
-    params.items = Some(vec![item]);
+    // Ajouter le price via items (et l'option à l'usage, sans quantité)
+    let mut items = vec![stripe::CreateSubscriptionItems {
+        price: Some(price_id.to_string()),
+        ..Default::default()
+    }];
+    if let Some(metered_price_id) = metered_price_id {
+        items.push(stripe::CreateSubscriptionItems {
+            price: Some(metered_price_id.to_string()),
+            ..Default::default()
+        });
+    }
+    params.items = Some(items);
     
     // Ajouter le payment method si fourni
     if let Some(pm_id) = payment_method_id {
         params.default_payment_method = Some(pm_id);
     }
     
+    // Essai gratuit: premier prélèvement à la fin de l'essai
+    if let Some(days) = trial_days {
+        params.trial_period_days = Some(days as u32);
+    }
+    
+    // Dernière facture et son PaymentIntent (statut et client_secret)
+    params.expand = &["latest_invoice.payment_intent"];
+    
     Subscription::create(client, params).await
 }
 
@@ -112,6 +274,249 @@ pub async fn cancel_subscription(
     Subscription::update(client, &subscription_id, params).await
 }
 
+/// Récupérer un abonnement avec sa dernière facture
+pub async fn retrieve_subscription(
+    client: &Client,
+    subscription_id: &str,
+) -> Result<Subscription, StripeError> {
+    let subscription_id: stripe::SubscriptionId = subscription_id.parse().unwrap();
+    Subscription::retrieve(client, &subscription_id, &["latest_invoice"]).await
+}
+
+/// Terminer un abonnement immédiatement (sans prorata côté Stripe)
+pub async fn cancel_subscription_now(
+    client: &Client,
+    subscription_id: &str,
+) -> Result<Subscription, StripeError> {
+    let subscription_id: stripe::SubscriptionId = subscription_id.parse().unwrap();
+    let mut params = stripe::CancelSubscription::new();
+    params.prorate = Some(false);
+    
+    Subscription::cancel(client, &subscription_id, params).await
+}
+
+/// Rembourser une partie de la facture d'un abonnement
+///
+/// Un seul remboursement par abonnement et par paiement: la clé d'idempotence
+/// permet de retenter sans risquer de rembourser deux fois.
+pub async fn create_subscription_refund(
+    client: &Client,
+    payment_intent_id: &str,
+    amount: i64,
+    subscription_id: &str,
+) -> Result<Refund, StripeError> {
+    let mut params = CreateRefund::new();
+    params.payment_intent = Some(payment_intent_id.parse().unwrap());
+    params.amount = Some(amount);
+    params.metadata = Some(
+        [("subscription_id".to_string(), subscription_id.to_string())]
+            .iter()
+            .cloned()
+            .collect(),
+    );
+    let idempotency_key = format!("subscription-refund-{}-{}", subscription_id, payment_intent_id);
+    let client = client.clone().with_strategy(stripe::RequestStrategy::Idempotent(idempotency_key));
+    
+    Refund::create(&client, params).await
+}
+
+/// Suspendre les prélèvements d'un abonnement (factures annulées pendant la pause)
+///
+/// Avec `resumes_at`, Stripe reprend lui-même les prélèvements à cette date.
+pub async fn pause_subscription_collection(
+    client: &Client,
+    subscription_id: &str,
+    resumes_at: Option<DateTime<Utc>>,
+) -> Result<Subscription, StripeError> {
+    let subscription_id: stripe::SubscriptionId = subscription_id.parse().unwrap();
+    let mut params = UpdateSubscription::new();
+    params.pause_collection = Some(stripe::UpdateSubscriptionPauseCollection {
+        behavior: stripe::UpdateSubscriptionPauseCollectionBehavior::Void,
+        resumes_at: resumes_at.map(|date| date.timestamp()),
+    });
+    
+    Subscription::update(client, &subscription_id, params).await
+}
+
+// `UpdateSubscription` ne sait pas effacer `pause_collection`: Stripe attend une chaîne vide
+#[derive(serde::Serialize)]
+struct ClearPauseCollection {
+    pause_collection: &'static str,
+}
+
+/// Reprendre les prélèvements d'un abonnement suspendu
+pub async fn resume_subscription_collection(
+    client: &Client,
+    subscription_id: &str,
+) -> Result<Subscription, StripeError> {
+    client.post_form(
+        &format!("/subscriptions/{}", subscription_id),
+        ClearPauseCollection { pause_collection: "" },
+    ).await
+}
+
+/// Relancer le paiement d'une facture impayée
+pub async fn pay_invoice(
+    client: &Client,
+    invoice_id: &str,
+) -> Result<stripe::Invoice, StripeError> {
+    let invoice_id: stripe::InvoiceId = invoice_id.parse().unwrap();
+    stripe::Invoice::pay(client, &invoice_id).await
+}
+
+/// Programmer un nouveau prix au prochain renouvellement (changement de période de facturation)
+///
+/// L'abonnement passe sous un calendrier Stripe: la phase en cours garde son
+/// prix jusqu'à la fin de période, puis le nouveau prix s'applique et le
+/// calendrier est libéré.
+pub async fn schedule_price_at_renewal(
+    client: &Client,
+    subscription_id: &str,
+    price_id: &str,
+    metered_price_id: Option<&str>,
+) -> Result<stripe::SubscriptionSchedule, StripeError> {
+    let mut params = stripe::CreateSubscriptionSchedule::new();
+    params.from_subscription = Some(subscription_id);
+    let schedule = stripe::SubscriptionSchedule::create(client, params).await?;
+    
+    let current = schedule.phases.first();
+    let current_items = current
+        .map(|phase| phase.items.iter()
+            .map(|item| stripe::UpdateSubscriptionSchedulePhasesItems {
+                price: Some(item.price.id().to_string()),
+                quantity: item.quantity,
+                ..Default::default()
+            })
+            .collect())
+        .unwrap_or_default();
+    
+    let mut next_items = vec![stripe::UpdateSubscriptionSchedulePhasesItems {
+        price: Some(price_id.to_string()),
+        ..Default::default()
+    }];
+    if let Some(metered_price_id) = metered_price_id {
+        next_items.push(stripe::UpdateSubscriptionSchedulePhasesItems {
+            price: Some(metered_price_id.to_string()),
+            ..Default::default()
+        });
+    }
+    
+    let mut params = stripe::UpdateSubscriptionSchedule::new();
+    params.end_behavior = Some(stripe::SubscriptionScheduleEndBehavior::Release);
+    params.phases = Some(vec![
+        stripe::UpdateSubscriptionSchedulePhases {
+            items: current_items,
+            start_date: current.map(|phase| stripe::Scheduled::Timestamp(phase.start_date)),
+            end_date: current.map(|phase| stripe::Scheduled::Timestamp(phase.end_date)),
+            ..Default::default()
+        },
+        stripe::UpdateSubscriptionSchedulePhases {
+            items: next_items,
+            iterations: Some(1),
+            ..Default::default()
+        },
+    ]);
+    
+    stripe::SubscriptionSchedule::update(client, &schedule.id, params).await
+}
+
+#[derive(serde::Serialize)]
+struct ReleaseSchedule {
+    preserve_cancel_date: bool,
+}
+
+/// Libérer l'abonnement de son calendrier: les phases à venir sont abandonnées
+///
+/// L'abonnement continue avec ses prix actuels.
+pub async fn release_subscription_schedule(
+    client: &Client,
+    schedule_id: &str,
+) -> Result<stripe::SubscriptionSchedule, StripeError> {
+    client.post_form(
+        &format!("/subscription_schedules/{}/release", schedule_id),
+        ReleaseSchedule { preserve_cancel_date: false },
+    ).await
+}
+
+/// Reprendre un abonnement dont l'annulation en fin de période était programmée
+pub async fn resume_subscription(
+    client: &Client,
+    subscription_id: &str,
+) -> Result<Subscription, StripeError> {
+    let subscription_id = subscription_id.parse().unwrap();
+    let mut params = UpdateSubscription::new();
+    params.cancel_at_period_end = Some(false);
+    
+    Subscription::update(client, &subscription_id, params).await
+}
+
+/// Remplacer le prix d'un abonnement (changement de formule)
+pub async fn change_subscription_price(
+    client: &Client,
+    subscription_id: &str,
+    price_id: &str,
+    metered_price_id: Option<&str>,
+    proration: ProrationBehavior,
+) -> Result<Subscription, StripeError> {
+    let subscription_id: stripe::SubscriptionId = subscription_id.parse().unwrap();
+    let subscription = Subscription::retrieve(client, &subscription_id, &[]).await?;
+    
+    // Remplacer l'article existant plutôt que d'en ajouter un second
+    let mut items = vec![stripe::UpdateSubscriptionItems {
+        id: subscription.items.data.iter()
+            .find(|item| !subscriptions::is_metered(item))
+            .map(|item| item.id.to_string()),
+        price: Some(price_id.to_string()),
+        ..Default::default()
+    }];
+    // L'option à l'usage passe au tarif de la nouvelle formule sur un nouvel
+    // article; l'ancien est retiré sans effacer la consommation déclarée
+    for metered in subscription.items.data.iter().filter(|item| subscriptions::is_metered(item)) {
+        items.push(stripe::UpdateSubscriptionItems {
+            id: Some(metered.id.to_string()),
+            deleted: Some(true),
+            ..Default::default()
+        });
+    }
+    if let Some(metered_price_id) = metered_price_id {
+        items.push(stripe::UpdateSubscriptionItems {
+            price: Some(metered_price_id.to_string()),
+            ..Default::default()
+        });
+    }
+    
+    let mut params = UpdateSubscription::new();
+    params.items = Some(items);
+    params.proration_behavior = Some(match proration {
+        ProrationBehavior::ImmediateInvoice => SubscriptionProrationBehavior::AlwaysInvoice,
+        ProrationBehavior::NextInvoice => SubscriptionProrationBehavior::CreateProrations,
+        ProrationBehavior::None => SubscriptionProrationBehavior::None,
+    });
+    
+    Subscription::update(client, &subscription_id, params).await
+}
+
+/// Déclarer une consommation sur l'article à l'usage d'un abonnement
+///
+/// La clé d'idempotence évite de compter deux fois un envoi rejoué.
+pub async fn report_usage(
+    client: &Client,
+    subscription_item_id: &str,
+    quantity: i64,
+    timestamp: DateTime<Utc>,
+    idempotency_key: &str,
+) -> Result<stripe::UsageRecord, StripeError> {
+    let subscription_item_id: stripe::SubscriptionItemId = subscription_item_id.parse().unwrap();
+    let params = stripe::CreateUsageRecord {
+        quantity: quantity as u64,
+        action: Some(stripe::UsageRecordAction::Increment),
+        timestamp: Some(timestamp.timestamp()),
+    };
+    let client = client.clone().with_strategy(stripe::RequestStrategy::Idempotent(idempotency_key.to_string()));
+    
+    stripe::UsageRecord::create(&client, &subscription_item_id, params).await
+}
+
 /// Créer un SetupIntent pour enregistrer un moyen de paiement
 pub async fn create_setup_intent(
     client: &Client,
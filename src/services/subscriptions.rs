@@ -0,0 +1,427 @@
+// Règles de gestion des abonnements (changement de formule, ...)
+
+use axum::{http::StatusCode, Json};
+use chrono::{DateTime, Utc};
+
+use crate::models::{
+    ApiError, BillingInterval, MeteredPrice, PlanChange, ProrationBehavior, SubscriptionInvoiceSummary, SubscriptionPause, SubscriptionPlan,
+    SubscriptionStatus, UserSubscription,
+};
+use crate::services::entitlements;
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum SubscriptionError {
+    #[error("L'abonnement n'est pas actif (statut: {0:?})")]
+    NotActive(SubscriptionStatus),
+    #[error("L'abonnement est déjà sur la formule {0}")]
+    SamePlan(String),
+    #[error("L'abonnement est annulé en fin de période: reprenez-le avant de changer de formule")]
+    CancellationScheduled,
+    #[error("L'abonnement est déjà annulé")]
+    AlreadyCancelled,
+    #[error("L'abonnement est déjà programmé pour s'arrêter le {0}")]
+    AlreadyScheduled(String),
+    #[error("Aucune annulation programmée sur cet abonnement")]
+    NotScheduled,
+    #[error("La période payée est terminée: souscrivez un nouvel abonnement")]
+    PeriodEnded,
+    #[error("La formule {plan_id} n'est pas proposée en facturation {interval:?}")]
+    IntervalNotOffered { plan_id: String, interval: BillingInterval },
+    #[error("La formule {0} ne propose pas d'option à l'usage")]
+    MeteredNotOffered(String),
+    #[error("L'abonnement est déjà facturé en {0:?}")]
+    SameInterval(BillingInterval),
+    #[error("Passage en facturation {0:?} déjà programmé au renouvellement")]
+    IntervalChangeScheduled(BillingInterval),
+    #[error("Les prélèvements de l'abonnement sont déjà suspendus")]
+    AlreadyPaused,
+    #[error("Les prélèvements de l'abonnement ne sont pas suspendus")]
+    NotPaused,
+    #[error("Suspension due à un impayé: régularisez la facture {0} pour reprendre les prélèvements")]
+    UnpaidInvoice(String),
+    #[error("La date de reprise doit être dans le futur")]
+    ResumeDateInPast,
+    #[error("Aucun remboursement en attente sur cet abonnement")]
+    NoPendingRefund,
+    #[error("Aucun changement de période de facturation programmé")]
+    NoIntervalChangeScheduled,
+    #[error("Calendrier Stripe du changement de période inconnu: annulez-le depuis le dashboard Stripe")]
+    IntervalScheduleUnknown,
+}
+
+impl From<SubscriptionError> for (StatusCode, Json<ApiError>) {
+    fn from(err: SubscriptionError) -> Self {
+        let status = match err {
+            SubscriptionError::IntervalNotOffered { .. }
+            | SubscriptionError::MeteredNotOffered(_)
+            | SubscriptionError::ResumeDateInPast => StatusCode::BAD_REQUEST,
+            _ => StatusCode::CONFLICT,
+        };
+        (status, Json(ApiError { error: err.to_string() }))
+    }
+}
+
+/// Prix d'une formule pour une période de facturation
+///
+/// Une formule sans grille de prix n'est proposée qu'au mois, à son prix de base.
+pub fn plan_price(plan: &SubscriptionPlan, interval: BillingInterval) -> Result<i64, SubscriptionError> {
+    match plan.prices.get(&interval) {
+        Some(price) => Ok(*price),
+        None if plan.prices.is_empty() && interval == BillingInterval::Monthly => Ok(plan.price),
+        None => Err(SubscriptionError::IntervalNotOffered { plan_id: plan.id.clone(), interval }),
+    }
+}
+
+/// Tarif de l'option à l'usage demandée à la souscription
+pub fn metered_option(plan: &SubscriptionPlan, requested: bool) -> Result<Option<MeteredPrice>, SubscriptionError> {
+    match &plan.metered {
+        _ if !requested => Ok(None),
+        Some(metered) => Ok(Some(metered.clone())),
+        None => Err(SubscriptionError::MeteredNotOffered(plan.id.clone())),
+    }
+}
+
+/// Vérifier qu'un changement de formule est possible
+pub fn check_plan_change(subscription: &UserSubscription, plan_id: &str) -> Result<(), SubscriptionError> {
+    if !matches!(subscription.status, SubscriptionStatus::Active | SubscriptionStatus::Trialing) {
+        return Err(SubscriptionError::NotActive(subscription.status.clone()));
+    }
+    if subscription.cancel_at_period_end {
+        return Err(SubscriptionError::CancellationScheduled);
+    }
+    if subscription.plan_id == plan_id {
+        return Err(SubscriptionError::SamePlan(plan_id.to_string()));
+    }
+    if let Some(interval) = subscription.scheduled_interval {
+        return Err(SubscriptionError::IntervalChangeScheduled(interval));
+    }
+    Ok(())
+}
+
+/// Vérifier qu'un changement de période de facturation peut être programmé
+pub fn check_interval_change(
+    subscription: &UserSubscription,
+    plan: &SubscriptionPlan,
+    interval: BillingInterval,
+) -> Result<i64, SubscriptionError> {
+    if !matches!(subscription.status, SubscriptionStatus::Active | SubscriptionStatus::Trialing) {
+        return Err(SubscriptionError::NotActive(subscription.status.clone()));
+    }
+    if subscription.cancel_at_period_end {
+        return Err(SubscriptionError::CancellationScheduled);
+    }
+    if subscription.interval == interval {
+        return Err(SubscriptionError::SameInterval(interval));
+    }
+    if let Some(scheduled) = subscription.scheduled_interval {
+        return Err(SubscriptionError::IntervalChangeScheduled(scheduled));
+    }
+    plan_price(plan, interval)
+}
+
+/// Vérifier qu'une annulation en fin de période peut être programmée
+///
+/// Un changement de période programmé est abandonné (son calendrier Stripe
+/// libéré) avant l'annulation: il faut donc connaître ce calendrier.
+pub fn check_cancel_at_period_end(subscription: &UserSubscription) -> Result<(), SubscriptionError> {
+    if subscription.status == SubscriptionStatus::Cancelled {
+        return Err(SubscriptionError::AlreadyCancelled);
+    }
+    if subscription.cancel_at_period_end {
+        return Err(SubscriptionError::AlreadyScheduled(
+            subscription.current_period_end.format("%d/%m/%Y").to_string(),
+        ));
+    }
+    if subscription.scheduled_interval.is_some() && subscription.interval_schedule_id.is_none() {
+        return Err(SubscriptionError::IntervalScheduleUnknown);
+    }
+    Ok(())
+}
+
+/// Vérifier qu'un changement de période programmé peut être abandonné et retourner son calendrier Stripe
+pub fn check_cancel_interval_change(subscription: &UserSubscription) -> Result<String, SubscriptionError> {
+    if subscription.scheduled_interval.is_none() {
+        return Err(SubscriptionError::NoIntervalChangeScheduled);
+    }
+    subscription.interval_schedule_id.clone().ok_or(SubscriptionError::IntervalScheduleUnknown)
+}
+
+/// Une annulation immédiate est possible tant que l'abonnement n'est pas terminé
+pub fn check_cancel_now(subscription: &UserSubscription) -> Result<(), SubscriptionError> {
+    if subscription.status == SubscriptionStatus::Cancelled {
+        return Err(SubscriptionError::AlreadyCancelled);
+    }
+    Ok(())
+}
+
+/// Montant facturé pour la période en cours: ligne d'abonnement de la facture
+///
+/// Une facture de changement de formule ne contient que des ajustements au
+/// prorata, et la consommation à l'usage concerne la période précédente: seule
+/// la ligne forfaitaire de la période compte, plafonnée au montant payé.
+pub fn period_line_amount(invoice: &stripe::Invoice, period_start: i64) -> Option<i64> {
+    let amount = invoice.lines.as_ref()?.data.iter()
+        .filter(|line| line.type_ == stripe::InvoiceLineItemType::Subscription && !line.proration)
+        .filter(|line| line.period.as_ref().and_then(|period| period.start) == Some(period_start))
+        .filter(|line| !line.price.as_ref()
+            .and_then(|price| price.recurring.as_ref())
+            .is_some_and(|recurring| recurring.usage_type == stripe::RecurringUsageType::Metered))
+        .map(|line| line.amount)
+        .sum::<i64>();
+    
+    (amount > 0).then(|| amount.min(invoice.amount_paid.unwrap_or(0)))
+}
+
+/// Part non consommée de la dernière facture, au prorata du temps restant (arrondi au centime inférieur)
+pub fn prorated_refund(
+    amount_paid: i64,
+    period_start: DateTime<Utc>,
+    period_end: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> i64 {
+    if amount_paid <= 0 || period_end <= period_start || now >= period_end {
+        return 0;
+    }
+    if now <= period_start {
+        return amount_paid;
+    }
+    
+    let remaining = (period_end - now).num_seconds();
+    let total = (period_end - period_start).num_seconds();
+    amount_paid * remaining / total
+}
+
+/// Une annulation programmée peut être levée tant que la période payée court
+pub fn check_resume(subscription: &UserSubscription, now: DateTime<Utc>) -> Result<(), SubscriptionError> {
+    if subscription.status == SubscriptionStatus::Cancelled {
+        return Err(SubscriptionError::AlreadyCancelled);
+    }
+    if !subscription.cancel_at_period_end {
+        return Err(SubscriptionError::NotScheduled);
+    }
+    if now >= subscription.current_period_end {
+        return Err(SubscriptionError::PeriodEnded);
+    }
+    Ok(())
+}
+
+/// Vérifier qu'une pause des prélèvements peut être demandée
+pub fn check_pause(
+    subscription: &UserSubscription,
+    resumes_at: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> Result<(), SubscriptionError> {
+    if subscription.status == SubscriptionStatus::Paused {
+        return Err(SubscriptionError::AlreadyPaused);
+    }
+    if !matches!(subscription.status, SubscriptionStatus::Active | SubscriptionStatus::Trialing) {
+        return Err(SubscriptionError::NotActive(subscription.status.clone()));
+    }
+    if subscription.cancel_at_period_end {
+        return Err(SubscriptionError::CancellationScheduled);
+    }
+    // Le renouvellement qui applique le changement tomberait pendant la pause
+    if let Some(interval) = subscription.scheduled_interval {
+        return Err(SubscriptionError::IntervalChangeScheduled(interval));
+    }
+    if resumes_at.is_some_and(|date| date <= now) {
+        return Err(SubscriptionError::ResumeDateInPast);
+    }
+    Ok(())
+}
+
+/// Seul un abonnement suspendu peut reprendre ses prélèvements
+///
+/// Une suspension décidée par la relance (action finale) ne se lève qu'au paiement.
+pub fn check_unpause(subscription: &UserSubscription) -> Result<(), SubscriptionError> {
+    if subscription.status != SubscriptionStatus::Paused {
+        return Err(SubscriptionError::NotPaused);
+    }
+    if let Some(dunning) = &subscription.dunning {
+        return Err(SubscriptionError::UnpaidInvoice(dunning.stripe_invoice_id.clone()));
+    }
+    Ok(())
+}
+
+/// Droit d'accès au contenu: abonnement actif ou en essai, ou en relance
+/// pendant la période de grâce. Un abonnement suspendu n'y donne pas droit.
+pub fn has_access(subscription: &UserSubscription, now: DateTime<Utc>) -> bool {
+    entitlements::subscription_access(subscription, now).granted
+}
+
+/// Appliquer le changement localement (après succès côté Stripe) et l'historiser
+pub fn apply_plan_change(
+    subscription: &mut UserSubscription,
+    plan_id: &str,
+    proration: ProrationBehavior,
+    at: DateTime<Utc>,
+) {
+    subscription.plan_history.push(PlanChange {
+        from_plan_id: subscription.plan_id.clone(),
+        to_plan_id: plan_id.to_string(),
+        proration,
+        changed_at: at,
+    });
+    subscription.plan_id = plan_id.to_string();
+}
+
+/// Durée d'essai accordée: un seul essai par utilisateur, toutes formules confondues
+pub fn trial_days_for<'a>(
+    plan: &SubscriptionPlan,
+    user_subscriptions: impl IntoIterator<Item = &'a UserSubscription>,
+) -> Option<i64> {
+    let days = plan.trial_days.filter(|days| *days > 0)?;
+    let already_trialed = user_subscriptions.into_iter().any(|sub| sub.trial_end.is_some());
+    
+    (!already_trialed).then_some(days)
+}
+
+/// État d'un abonnement tel que connu de Stripe (source de vérité)
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubscriptionSnapshot {
+    pub status: SubscriptionStatus,
+    pub stripe_status: String,
+    pub current_period_start: DateTime<Utc>,
+    pub current_period_end: DateTime<Utc>,
+    pub cancel_at_period_end: bool,
+    pub trial_end: Option<DateTime<Utc>>,
+    pub latest_invoice: Option<SubscriptionInvoiceSummary>,
+    pub interval: Option<BillingInterval>, // Période du prix en cours
+    pub collection_paused: bool,
+    pub pause_resumes_at: Option<DateTime<Utc>>,
+    pub metered_item_id: Option<String>, // Article Stripe de l'option à l'usage
+}
+
+/// Événement Stripe antérieur à la dernière synchronisation (livré en retard ou rejoué)
+///
+/// Comparaison à la seconde: c'est la précision de la date des événements.
+pub fn is_stale_event(subscription: &UserSubscription, event_created: DateTime<Utc>) -> bool {
+    subscription.synced_at.is_some_and(|synced| event_created.timestamp() < synced.timestamp())
+}
+
+/// Statut local correspondant au statut Stripe
+pub fn status_from_stripe(status: stripe::SubscriptionStatus, collection_paused: bool) -> SubscriptionStatus {
+    match status {
+        stripe::SubscriptionStatus::Active if collection_paused => SubscriptionStatus::Paused,
+        stripe::SubscriptionStatus::Active => SubscriptionStatus::Active,
+        stripe::SubscriptionStatus::Trialing => SubscriptionStatus::Trialing,
+        stripe::SubscriptionStatus::PastDue => SubscriptionStatus::PastDue,
+        stripe::SubscriptionStatus::Unpaid => SubscriptionStatus::Unpaid,
+        stripe::SubscriptionStatus::Paused => SubscriptionStatus::Paused,
+        stripe::SubscriptionStatus::Canceled => SubscriptionStatus::Cancelled,
+        stripe::SubscriptionStatus::Incomplete | stripe::SubscriptionStatus::IncompleteExpired => {
+            SubscriptionStatus::Incomplete
+        }
+    }
+}
+
+/// Période de facturation d'un prix Stripe récurrent
+pub fn interval_from_stripe(recurring: &stripe::Recurring) -> Option<BillingInterval> {
+    match (recurring.interval, recurring.interval_count) {
+        (stripe::RecurringInterval::Month, 1) => Some(BillingInterval::Monthly),
+        (stripe::RecurringInterval::Month, 3) => Some(BillingInterval::Quarterly),
+        (stripe::RecurringInterval::Year, 1) => Some(BillingInterval::Annual),
+        _ => None,
+    }
+}
+
+/// Article facturé à l'usage (option), par opposition au prix de l'abonnement
+pub fn is_metered(item: &stripe::SubscriptionItem) -> bool {
+    item.price.as_ref()
+        .and_then(|price| price.recurring.as_ref())
+        .is_some_and(|recurring| recurring.usage_type == stripe::RecurringUsageType::Metered)
+}
+
+fn timestamp(ts: i64) -> DateTime<Utc> {
+    DateTime::<Utc>::from_timestamp(ts, 0).unwrap_or_default()
+}
+
+pub fn snapshot_from_stripe(subscription: &stripe::Subscription) -> SubscriptionSnapshot {
+    let latest_invoice = subscription.latest_invoice.as_ref().map(|invoice| match invoice {
+        stripe::Expandable::Id(id) => SubscriptionInvoiceSummary {
+            stripe_invoice_id: id.to_string(),
+            status: None,
+            amount_due: 0,
+            amount_paid: 0,
+            hosted_invoice_url: None,
+        },
+        stripe::Expandable::Object(invoice) => SubscriptionInvoiceSummary {
+            stripe_invoice_id: invoice.id.to_string(),
+            status: invoice.status.map(|status| status.as_str().to_string()),
+            amount_due: invoice.amount_due.unwrap_or(0),
+            amount_paid: invoice.amount_paid.unwrap_or(0),
+            hosted_invoice_url: invoice.hosted_invoice_url.clone(),
+        },
+    });
+    
+    SubscriptionSnapshot {
+        status: status_from_stripe(subscription.status, subscription.pause_collection.is_some()),
+        stripe_status: subscription.status.as_str().to_string(),
+        current_period_start: timestamp(subscription.current_period_start),
+        current_period_end: timestamp(subscription.current_period_end),
+        cancel_at_period_end: subscription.cancel_at_period_end,
+        trial_end: subscription.trial_end.map(timestamp),
+        latest_invoice,
+        interval: subscription.items.data.iter()
+            .find(|item| !is_metered(item))
+            .and_then(|item| item.price.as_ref())
+            .and_then(|price| price.recurring.as_ref())
+            .and_then(interval_from_stripe),
+        collection_paused: subscription.pause_collection.is_some(),
+        pause_resumes_at: subscription.pause_collection.as_ref()
+            .and_then(|pause| pause.resumes_at)
+            .map(timestamp),
+        metered_item_id: subscription.items.data.iter()
+            .find(|item| is_metered(item))
+            .map(|item| item.id.to_string()),
+    }
+}
+
+/// Aligner l'abonnement local sur Stripe
+pub fn apply_snapshot(subscription: &mut UserSubscription, snapshot: &SubscriptionSnapshot, at: DateTime<Utc>) {
+    // Une résiliation est définitive: un instantané (événement en retard,
+    // rejoué) ne rend pas l'abonnement ni sa période
+    if subscription.status == SubscriptionStatus::Cancelled {
+        return;
+    }
+    // L'action finale des relances (impayé, suspendu) est locale:
+    // Stripe peut encore dire `past_due`. Seul un rétablissement la lève.
+    let final_action_applied = subscription.dunning.as_ref().is_some_and(|d| d.next_retry_at.is_none())
+        && matches!(subscription.status, SubscriptionStatus::Unpaid | SubscriptionStatus::Paused);
+    let recovered = matches!(snapshot.status, SubscriptionStatus::Active | SubscriptionStatus::Trialing);
+    if !final_action_applied || recovered {
+        subscription.status = snapshot.status.clone();
+    }
+    subscription.current_period_start = snapshot.current_period_start;
+    subscription.current_period_end = snapshot.current_period_end;
+    subscription.cancel_at_period_end = snapshot.cancel_at_period_end;
+    if snapshot.trial_end.is_some() {
+        subscription.trial_end = snapshot.trial_end;
+    }
+    // Une facture non détaillée ne remplace pas un résumé plus complet de la même facture
+    match (&snapshot.latest_invoice, &subscription.latest_invoice) {
+        (Some(new), Some(old)) if new.status.is_none() && new.stripe_invoice_id == old.stripe_invoice_id => {}
+        (Some(new), _) => subscription.latest_invoice = Some(new.clone()),
+        (None, _) => {}
+    }
+    // Changement de période appliqué au renouvellement
+    if let Some(interval) = snapshot.interval {
+        subscription.interval = interval;
+        if subscription.scheduled_interval == Some(interval) {
+            subscription.scheduled_interval = None;
+            subscription.interval_schedule_id = None;
+        }
+    }
+    // Nouvel article à l'usage après un changement de période au renouvellement
+    if let (Some(metered), Some(item_id)) = (subscription.metered.as_mut(), &snapshot.metered_item_id) {
+        metered.stripe_item_id = item_id.clone();
+    }
+    // Pause levée (manuellement ou à la date de reprise) ou posée depuis le dashboard Stripe
+    if snapshot.collection_paused {
+        let pause = subscription.pause.get_or_insert(SubscriptionPause { paused_at: at, resumes_at: None });
+        pause.resumes_at = snapshot.pause_resumes_at;
+    } else {
+        subscription.pause = None;
+    }
+    subscription.synced_at = Some(at);
+}
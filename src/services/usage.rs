@@ -0,0 +1,177 @@
+// Facturation à l'usage: déclaration des consommations et remontée vers Stripe
+
+use axum::{http::StatusCode, Json};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use stripe::StripeError;
+
+use crate::models::{ApiError, MeteredItem, SubscriptionStatus, UsageEvent, UsageReportStatus, UserSubscription};
+use crate::services::{stripe_service, subscriptions};
+use crate::state::AppState;
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum UsageError {
+    #[error("L'abonnement n'a pas d'option facturée à l'usage")]
+    NotMetered,
+    #[error("L'abonnement ne donne pas accès au contenu (statut: {0:?})")]
+    NoAccess(SubscriptionStatus),
+    #[error("La consommation doit dater de la période en cours (depuis le {0}) et ne peut pas être future")]
+    OutsidePeriod(String),
+}
+
+impl From<UsageError> for (StatusCode, Json<ApiError>) {
+    fn from(err: UsageError) -> Self {
+        let status = match err {
+            UsageError::OutsidePeriod(_) => StatusCode::BAD_REQUEST,
+            _ => StatusCode::CONFLICT,
+        };
+        (status, Json(ApiError { error: err.to_string() }))
+    }
+}
+
+/// Vérifier qu'une consommation peut être déclarée et retourner sa date
+///
+/// Stripe n'accepte que des consommations de la période en cours, jamais futures.
+pub fn check_usage(
+    subscription: &UserSubscription,
+    occurred_at: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> Result<DateTime<Utc>, UsageError> {
+    if subscription.metered.is_none() {
+        return Err(UsageError::NotMetered);
+    }
+    if !subscriptions::has_access(subscription, now) {
+        return Err(UsageError::NoAccess(subscription.status.clone()));
+    }
+    
+    let occurred_at = occurred_at.unwrap_or(now);
+    if occurred_at < subscription.current_period_start || occurred_at > now {
+        return Err(UsageError::OutsidePeriod(subscription.current_period_start.format("%d/%m/%Y").to_string()));
+    }
+    Ok(occurred_at)
+}
+
+/// Enregistrer un événement, sauf si son identifiant est déjà connu
+///
+/// Retourne l'événement retenu et `true` s'il s'agit d'une déclaration rejouée.
+pub fn record_event(events: &mut Vec<UsageEvent>, event: UsageEvent) -> (UsageEvent, bool) {
+    if let Some(existing) = events.iter().find(|known| known.id == event.id) {
+        return (existing.clone(), true);
+    }
+    events.push(event.clone());
+    (event, false)
+}
+
+/// Consommations regroupées dans un seul enregistrement Stripe
+#[derive(Debug, Clone, PartialEq)]
+pub struct UsageBatch {
+    pub event_ids: Vec<String>,
+    pub quantity: i64,
+    pub timestamp: DateTime<Utc>, // Consommation la plus récente du lot
+}
+
+impl UsageBatch {
+    /// Même lot, même clé: Stripe ne compte pas deux fois un envoi rejoué
+    ///
+    /// Un événement n'appartient qu'à un seul lot, le premier suffit à l'identifier.
+    pub fn idempotency_key(&self, subscription_id: &str) -> String {
+        format!("usage-{}-{}-{}", subscription_id, self.event_ids[0], self.event_ids.len())
+    }
+}
+
+/// Lot à envoyer: celui dont l'envoi a échoué, inchangé, sinon les consommations en attente
+///
+/// Les nouvelles consommations attendent que le lot en cours soit remonté,
+/// pour que sa clé d'idempotence reste la même d'un essai à l'autre.
+pub fn take_pending(events: &mut [UsageEvent]) -> Option<UsageBatch> {
+    let in_flight = events.iter().any(|event| event.report_status == UsageReportStatus::Reporting);
+    let status = if in_flight { UsageReportStatus::Reporting } else { UsageReportStatus::Pending };
+    let pending: Vec<&mut UsageEvent> = events.iter_mut()
+        .filter(|event| event.report_status == status)
+        .collect();
+    let timestamp = pending.iter().map(|event| event.occurred_at).max()?;
+    
+    let mut batch = UsageBatch { event_ids: Vec::new(), quantity: 0, timestamp };
+    for event in pending {
+        event.report_status = UsageReportStatus::Reporting;
+        batch.event_ids.push(event.id.clone());
+        batch.quantity += event.quantity;
+    }
+    Some(batch)
+}
+
+/// Clore l'envoi réussi d'un lot avec son enregistrement Stripe
+///
+/// Après un échec, rien ne change: le lot reste en cours d'envoi et sera renvoyé.
+pub fn finish_batch(events: &mut [UsageEvent], batch: &UsageBatch, stripe_usage_record_id: &str) {
+    for event in events.iter_mut().filter(|event| batch.event_ids.contains(&event.id)) {
+        event.report_status = UsageReportStatus::Reported;
+        event.stripe_usage_record_id = Some(stripe_usage_record_id.to_string());
+    }
+}
+
+/// Consommation de la période en cours et montant estimé
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct UsageSummary {
+    pub subscription_id: String,
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+    pub unit_label: String,
+    pub unit_amount: i64,
+    pub quantity: i64,
+    pub reported_quantity: i64, // Déjà remontée à Stripe
+    pub estimated_amount: i64,  // Ajouté à la prochaine facture
+}
+
+pub fn summarize(subscription: &UserSubscription, metered: &MeteredItem, events: &[UsageEvent]) -> UsageSummary {
+    let in_period: Vec<&UsageEvent> = events.iter()
+        .filter(|event| event.occurred_at >= subscription.current_period_start
+            && event.occurred_at < subscription.current_period_end)
+        .collect();
+    let quantity: i64 = in_period.iter().map(|event| event.quantity).sum();
+    let reported_quantity = in_period.iter()
+        .filter(|event| event.report_status == UsageReportStatus::Reported)
+        .map(|event| event.quantity)
+        .sum();
+    
+    UsageSummary {
+        subscription_id: subscription.id.clone(),
+        period_start: subscription.current_period_start,
+        period_end: subscription.current_period_end,
+        unit_label: metered.unit_label.clone(),
+        unit_amount: metered.unit_amount,
+        quantity,
+        reported_quantity,
+        estimated_amount: quantity * metered.unit_amount,
+    }
+}
+
+/// Remonter à Stripe les consommations en attente d'un abonnement
+///
+/// En cas d'échec, le même lot est renvoyé au prochain passage.
+pub async fn report_pending(state: &AppState, subscription_id: &str) -> Result<Option<UsageBatch>, StripeError> {
+    let Some(item_id) = state.subscriptions.get(subscription_id)
+        .and_then(|subscription| subscription.metered.as_ref().map(|metered| metered.stripe_item_id.clone()))
+    else {
+        return Ok(None);
+    };
+    let batch = state.usage_events.get_mut(subscription_id)
+        .and_then(|mut events| take_pending(&mut events));
+    let Some(batch) = batch else {
+        return Ok(None);
+    };
+    
+    let result = stripe_service::report_usage(
+        &state.stripe_client,
+        &item_id,
+        batch.quantity,
+        batch.timestamp,
+        &batch.idempotency_key(subscription_id),
+    ).await;
+    
+    let record = result?;
+    if let Some(mut events) = state.usage_events.get_mut(subscription_id) {
+        finish_batch(&mut events, &batch, record.id.as_str());
+    }
+    Ok(Some(batch))
+}
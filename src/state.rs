@@ -1,7 +1,9 @@
 use crate::config::Config;
 use crate::models::*;
-use dashmap::DashMap;
-use std::sync::Arc;
+use axum::{http::StatusCode, Json};
+use dashmap::{DashMap, DashSet};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, RwLock};
 use stripe::Client as StripeClient;
 
 #[derive(Clone)]
@@ -16,11 +18,33 @@ pub struct AppState {
     pub subscriptions: Arc<DashMap<String, UserSubscription>>,
     pub payment_methods: Arc<DashMap<String, SavedPaymentMethod>>,
     pub subscription_plans: Arc<DashMap<String, SubscriptionPlan>>,
+    pub subscription_payments: Arc<DashMap<String, SubscriptionPayment>>, // Par facture Stripe
+    pub usage_events: Arc<DashMap<String, Vec<UsageEvent>>>, // Par abonnement
+    pub shipping_methods: Arc<DashMap<String, ShippingMethod>>,
+    pub invoices: Arc<DashMap<String, Invoice>>, // Par order_id
+    
+    // Politiques: surcharges par produit / par niveau client, niveau de chaque client
+    pub product_policies: Arc<DashMap<String, PolicyOverride>>,
+    pub tier_policies: Arc<DashMap<String, PolicyOverride>>,
+    pub customer_tiers: Arc<DashMap<String, String>>, // user_id → niveau
+    
+    // Politique de relance en vigueur (initialisée depuis la configuration)
+    pub dunning_policy: Arc<RwLock<DunningPolicy>>,
+    
+    // Dernier numéro de facture attribué (verrou = numérotation sans trou)
+    pub invoice_sequence: Arc<Mutex<u64>>,
+    
+    // Commandes avec une opération en cours (modification, annulation, ...) appelant Stripe
+    pub orders_in_flight: Arc<DashSet<String>>,
+    
+    // Utilisateurs ayant consommé leur essai gratuit (réservé avant la création Stripe)
+    pub trials_consumed: Arc<DashSet<String>>,
 }
 
 impl AppState {
     pub fn new(config: Config) -> Self {
         let stripe_client = StripeClient::new(&config.stripe_secret_key);
+        let dunning_policy = Arc::new(RwLock::new(config.dunning_policy.clone()));
         
         let state = Self {
             config,
@@ -31,6 +55,17 @@ impl AppState {
             subscriptions: Arc::new(DashMap::new()),
             payment_methods: Arc::new(DashMap::new()),
             subscription_plans: Arc::new(DashMap::new()),
+            subscription_payments: Arc::new(DashMap::new()),
+            usage_events: Arc::new(DashMap::new()),
+            shipping_methods: Arc::new(DashMap::new()),
+            invoices: Arc::new(DashMap::new()),
+            product_policies: Arc::new(DashMap::new()),
+            tier_policies: Arc::new(DashMap::new()),
+            customer_tiers: Arc::new(DashMap::new()),
+            dunning_policy,
+            invoice_sequence: Arc::new(Mutex::new(0)),
+            orders_in_flight: Arc::new(DashSet::new()),
+            trials_consumed: Arc::new(DashSet::new()),
         };
         
         // Initialiser les données de démo
@@ -39,6 +74,30 @@ impl AppState {
         state
     }
     
+    /// Marquer une opération en cours sur une commande, refusé si une autre l'est déjà
+    ///
+    /// La commande n'est pas verrouillée pendant les appels Stripe: le marqueur
+    /// empêche deux opérations de s'entrelacer. Il est retiré à la fin de l'opération.
+    pub fn begin_order_operation(&self, order_id: &str) -> Result<OrderOperation, OrderBusy> {
+        if !self.orders_in_flight.insert(order_id.to_string()) {
+            return Err(OrderBusy(order_id.to_string()));
+        }
+        Ok(OrderOperation {
+            orders_in_flight: self.orders_in_flight.clone(),
+            order_id: order_id.to_string(),
+        })
+    }
+    
+    /// Réserver l'essai gratuit d'un utilisateur, refusé s'il est déjà pris
+    pub fn reserve_trial(&self, user_id: &str) -> bool {
+        self.trials_consumed.insert(user_id.to_string())
+    }
+    
+    /// Rendre l'essai réservé quand l'abonnement n'a pas pu être créé
+    pub fn release_trial(&self, user_id: &str) {
+        self.trials_consumed.remove(user_id);
+    }
+    
     fn init_demo_data(&self) {
         // Produits (casquettes)
         let products = vec![
@@ -48,6 +107,9 @@ impl AppState {
                 price: 2500, // 25€
                 stock: 50,
                 description: "Casquette classique rouge, ajustable".to_string(),
+                weight_grams: 120,
+                archived: false,
+                variants: vec![],
             },
             Product {
                 id: "cap_002".to_string(),
@@ -55,6 +117,9 @@ impl AppState {
                 price: 3000, // 30€
                 stock: 30,
                 description: "Casquette sport noire, respirante".to_string(),
+                weight_grams: 110,
+                archived: false,
+                variants: vec![],
             },
             Product {
                 id: "cap_003".to_string(),
@@ -62,6 +127,32 @@ impl AppState {
                 price: 4500, // 45€
                 stock: 20,
                 description: "Casquette premium en coton bio".to_string(),
+                weight_grams: 150,
+                archived: false,
+                // Stock géré par taille
+                variants: vec![
+                    ProductVariant {
+                        sku: "cap_003-s-blanc".to_string(),
+                        size: Some("S".to_string()),
+                        color: Some("Blanc".to_string()),
+                        stock: 5,
+                        price: None,
+                    },
+                    ProductVariant {
+                        sku: "cap_003-m-blanc".to_string(),
+                        size: Some("M".to_string()),
+                        color: Some("Blanc".to_string()),
+                        stock: 10,
+                        price: None,
+                    },
+                    ProductVariant {
+                        sku: "cap_003-xl-blanc".to_string(),
+                        size: Some("XL".to_string()),
+                        color: Some("Blanc".to_string()),
+                        stock: 5,
+                        price: Some(4900), // Grande taille: 49€
+                    },
+                ],
             },
         ];
         
@@ -76,18 +167,42 @@ impl AppState {
                 name: "Normal".to_string(),
                 price: 1000, // 10€
                 description: "Abonnement journal formule normale".to_string(),
+                trial_days: Some(14),
+                prices: BTreeMap::from([
+                    (BillingInterval::Monthly, 1000),
+                    (BillingInterval::Quarterly, 2850), // -5%
+                    (BillingInterval::Annual, 10200),   // -15%
+                ]),
+                metered: Some(MeteredPrice { unit_amount: 50, unit_label: "article".to_string() }), // 0,50€ l'article
+                entitlements: vec!["daily_paper".to_string()],
             },
             SubscriptionPlan {
                 id: "plan_supplement".to_string(),
                 name: "Supplément".to_string(),
                 price: 1500, // 15€
                 description: "Abonnement journal avec suppléments".to_string(),
+                trial_days: Some(14),
+                prices: BTreeMap::from([
+                    (BillingInterval::Monthly, 1500),
+                    (BillingInterval::Quarterly, 4275), // -5%
+                    (BillingInterval::Annual, 15300),   // -15%
+                ]),
+                metered: Some(MeteredPrice { unit_amount: 50, unit_label: "article".to_string() }),
+                entitlements: vec!["daily_paper".to_string(), "supplement".to_string()],
             },
             SubscriptionPlan {
                 id: "plan_complet".to_string(),
                 name: "Complet".to_string(),
                 price: 2000, // 20€
                 description: "Abonnement journal formule complète".to_string(),
+                trial_days: Some(14),
+                prices: BTreeMap::from([
+                    (BillingInterval::Monthly, 2000),
+                    (BillingInterval::Quarterly, 5700), // -5%
+                    (BillingInterval::Annual, 20400),   // -15%
+                ]),
+                metered: Some(MeteredPrice { unit_amount: 30, unit_label: "article".to_string() }), // Tarif réduit
+                entitlements: vec!["daily_paper".to_string(), "supplement".to_string(), "weekend_magazine".to_string()],
             },
         ];
         
@@ -95,7 +210,72 @@ impl AppState {
             self.subscription_plans.insert(plan.id.clone(), plan);
         }
         
-        tracing::info!("✅ Données de démo initialisées: {} produits, {} plans", 
-                      self.products.len(), self.subscription_plans.len());
+        // Méthodes de livraison
+        let shipping_methods = vec![
+            ShippingMethod {
+                id: "standard".to_string(),
+                name: "Colissimo".to_string(),
+                rates: vec![
+                    ShippingRate { zone: ShippingZone::France, max_weight_grams: 1000, price: 490 },
+                    ShippingRate { zone: ShippingZone::France, max_weight_grams: 5000, price: 790 },
+                    ShippingRate { zone: ShippingZone::Europe, max_weight_grams: 1000, price: 990 },
+                    ShippingRate { zone: ShippingZone::Europe, max_weight_grams: 5000, price: 1490 },
+                    ShippingRate { zone: ShippingZone::International, max_weight_grams: 1000, price: 1890 },
+                    ShippingRate { zone: ShippingZone::International, max_weight_grams: 5000, price: 2590 },
+                ],
+                free_shipping_threshold: Some(5000), // Port offert dès 50€
+            },
+            ShippingMethod {
+                id: "express".to_string(),
+                name: "Chronopost 24h".to_string(),
+                rates: vec![
+                    ShippingRate { zone: ShippingZone::France, max_weight_grams: 5000, price: 1290 },
+                    ShippingRate { zone: ShippingZone::Europe, max_weight_grams: 5000, price: 2490 },
+                ],
+                free_shipping_threshold: None,
+            },
+        ];
+        
+        for method in shipping_methods {
+            self.shipping_methods.insert(method.id.clone(), method);
+        }
+        
+        // Politiques: clients VIP plus souples, casquette premium préparée à la commande
+        self.tier_policies.insert("vip".to_string(), PolicyOverride {
+            cancel_window_hours: Some(72),
+            modify_window_hours: Some(72),
+            return_window_days: Some(30),
+            ..Default::default()
+        });
+        self.product_policies.insert("cap_003".to_string(), PolicyOverride {
+            modify_window_hours: Some(2),
+            cancel_after_shipment: Some(PostShipmentCancellation::Refuse),
+            ..Default::default()
+        });
+        
+        tracing::info!("✅ Données de démo initialisées: {} produits, {} plans, {} livraisons", 
+                      self.products.len(), self.subscription_plans.len(), self.shipping_methods.len());
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("Une opération est déjà en cours sur la commande {0}, réessayez plus tard")]
+pub struct OrderBusy(pub String);
+
+impl From<OrderBusy> for (StatusCode, Json<ApiError>) {
+    fn from(err: OrderBusy) -> Self {
+        (StatusCode::CONFLICT, Json(ApiError { error: err.to_string() }))
+    }
+}
+
+/// Opération en cours sur une commande (voir `AppState::begin_order_operation`)
+pub struct OrderOperation {
+    orders_in_flight: Arc<DashSet<String>>,
+    order_id: String,
+}
+
+impl Drop for OrderOperation {
+    fn drop(&mut self) {
+        self.orders_in_flight.remove(&self.order_id);
     }
 }
@@ -0,0 +1,244 @@
+// Tests unitaires pour l'export comptable
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common;
+    use ruststripe::models::*;
+    use ruststripe::routes::webhooks;
+    use ruststripe::services::accounting::{self, ExportError, ExportFormat, COLUMNS};
+    use ruststripe::state::AppState;
+    use chrono::{DateTime, Duration, TimeZone, Utc};
+    use futures_util::StreamExt;
+    use axum::{body::Bytes, extract::State};
+    
+    fn create_test_state() -> AppState {
+        common::test_state()
+    }
+    
+    fn order(id: &str, total: i64, created_at: DateTime<Utc>) -> Order {
+        Order {
+            id: id.to_string(),
+            subtotal: total,
+            total,
+            payment_intent_id: Some(format!("pi_{}", id)),
+            created_at,
+            updated_at: created_at,
+            ..common::order()
+        }
+    }
+    
+    // Paiement confirmé par Stripe à `at`
+    fn paid(mut order: Order, at: DateTime<Utc>) -> Order {
+        order.status_history.push(OrderStatusChange {
+            from: Some(OrderStatus::Processing),
+            to: OrderStatus::Completed,
+            at,
+            source: StatusChangeSource::StripeWebhook,
+            reason: "Paiement confirmé".to_string(),
+        });
+        order
+    }
+    
+    async fn export(state: &AppState, format: ExportFormat) -> String {
+        let from = Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap();
+        let to = Utc.with_ymd_and_hms(2026, 2, 1, 0, 0, 0).unwrap();
+        let stream = accounting::export_stream(state.clone(), from, to, format).unwrap();
+        
+        let chunks: Vec<_> = stream.collect().await;
+        chunks.into_iter()
+            .map(|chunk| String::from_utf8(chunk.unwrap().to_vec()).unwrap())
+            .collect()
+    }
+    
+    fn seed(state: &AppState) {
+        let day = Utc.with_ymd_and_hms(2026, 1, 10, 9, 0, 0).unwrap();
+        
+        let mut paid_order = paid(order("order_1", 6000, day), day + Duration::minutes(5));
+        paid_order.refunds.push(OrderRefund {
+            id: "refund_1".to_string(),
+            stripe_refund_id: "re_1".to_string(),
+            amount: 1200,
+            items: vec![],
+            restocked: false,
+            reason: Some("Casse, livraison".to_string()),
+            created_at: day + Duration::days(2),
+        });
+        state.orders.insert(paid_order.id.clone(), paid_order);
+        
+        // Hors période
+        let old = paid(order("order_old", 2500, day - Duration::days(60)), day - Duration::days(60));
+        state.orders.insert(old.id.clone(), old);
+        
+        state.subscription_payments.insert("in_1".to_string(), SubscriptionPayment {
+            stripe_invoice_id: "in_1".to_string(),
+            stripe_subscription_id: "sub_1".to_string(),
+            stripe_payment_intent_id: Some("pi_sub".to_string()),
+            user_id: Some("user_2".to_string()),
+            amount: 1000,
+            tax: 167,
+            currency: "eur".to_string(),
+            status: SubscriptionPaymentStatus::Paid,
+            occurred_at: day + Duration::days(1),
+        });
+    }
+    
+    #[tokio::test]
+    async fn test_csv_export_has_fixed_columns_in_order() {
+        let state = create_test_state();
+        seed(&state);
+        
+        let csv = export(&state, ExportFormat::Csv).await;
+        let lines: Vec<&str> = csv.lines().collect();
+        
+        assert_eq!(lines[0], COLUMNS.join(","));
+        assert_eq!(lines.len(), 4); // En-tête + commande + abonnement + remboursement
+        assert!(lines[1].starts_with("order,order_1,order_1,user_1,6000,eur,1000,Completed,pi_order_1,,,,"));
+        assert!(lines[2].starts_with("subscription_payment,in_1,,user_2,1000,eur,167,Paid,pi_sub,,in_1,sub_1,"));
+        assert!(lines[3].starts_with("refund,refund_1,order_1,user_1,1200,eur,200,Refunded,pi_order_1,re_1,,,"));
+        for line in &lines {
+            assert_eq!(line.split(',').count(), COLUMNS.len());
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_jsonl_export_one_object_per_line() {
+        let state = create_test_state();
+        seed(&state);
+        
+        let jsonl = export(&state, ExportFormat::Jsonl).await;
+        let rows: Vec<serde_json::Value> = jsonl.lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0]["record_type"], "order");
+        assert_eq!(rows[2]["stripe_refund_id"], "re_1");
+        for row in &rows {
+            assert_eq!(row.as_object().unwrap().len(), COLUMNS.len());
+        }
+    }
+    
+    #[test]
+    fn test_invalid_range_is_rejected() {
+        let state = create_test_state();
+        let now = Utc::now();
+        
+        let result = accounting::export_stream(state, now, now - Duration::days(1), ExportFormat::Csv);
+        assert_eq!(result.err(), Some(ExportError::InvalidRange));
+    }
+    
+    #[tokio::test]
+    async fn test_subscription_payment_is_dated_by_the_invoice() {
+        let state = create_test_state();
+        let created = Utc.with_ymd_and_hms(2026, 3, 1, 8, 0, 0).unwrap();
+        let paid_at = Utc.with_ymd_and_hms(2026, 3, 2, 9, 30, 0).unwrap();
+        
+        // Webhook reçu bien après l'encaissement (rejeu, file d'attente Stripe)
+        for (invoice_id, status_transitions) in [
+            ("in_paid", serde_json::json!({ "paid_at": paid_at.timestamp() })),
+            ("in_no_transition", serde_json::json!({})),
+        ] {
+            let event = serde_json::json!({
+                "type": "invoice.payment_succeeded",
+                "data": { "object": {
+                    "id": invoice_id,
+                    "subscription": "sub_unknown",
+                    "amount_paid": 1000,
+                    "created": created.timestamp(),
+                    "status_transitions": status_transitions,
+                } },
+            });
+            webhooks::stripe_webhook(State(state.clone()), Bytes::from(event.to_string())).await.unwrap();
+        }
+        
+        assert_eq!(state.subscription_payments.get("in_paid").unwrap().occurred_at, paid_at);
+        // Sans date d'encaissement: date d'émission de la facture
+        assert_eq!(state.subscription_payments.get("in_no_transition").unwrap().occurred_at, created);
+    }
+    
+    #[tokio::test]
+    async fn test_only_collected_payments_are_exported_on_their_payment_date() {
+        let state = create_test_state();
+        let day = Utc.with_ymd_and_hms(2026, 1, 10, 9, 0, 0).unwrap();
+        
+        // Jamais encaissées
+        for (id, status) in [
+            ("order_pending", OrderStatus::Pending),
+            ("order_failed", OrderStatus::Failed),
+            ("order_cancelled", OrderStatus::Cancelled),
+        ] {
+            state.orders.insert(id.to_string(), Order { status, ..order(id, 3000, day) });
+        }
+        // Commandée en décembre, payée en janvier; commandée en janvier, payée en février
+        let december = Utc.with_ymd_and_hms(2025, 12, 31, 23, 0, 0).unwrap();
+        let paid_at = Utc.with_ymd_and_hms(2026, 1, 2, 8, 0, 0).unwrap();
+        state.orders.insert("order_dec".to_string(), paid(order("order_dec", 2500, december), paid_at));
+        let february = Utc.with_ymd_and_hms(2026, 2, 1, 0, 30, 0).unwrap();
+        state.orders.insert("order_feb".to_string(), paid(order("order_feb", 2500, day), february));
+        // Capture manuelle partielle: TVA sur le montant capturé
+        let mut captured = paid(order("order_capture", 6000, day), day);
+        captured.capture_method = CaptureMethod::Manual;
+        captured.authorization = Some(PaymentAuthorization {
+            amount: 6000,
+            authorized_at: day,
+            expires_at: day + Duration::days(7),
+            expiring_soon: false,
+            captured_amount: Some(4800),
+            captured_at: Some(day),
+        });
+        state.orders.insert(captured.id.clone(), captured);
+        
+        let jsonl = export(&state, ExportFormat::Jsonl).await;
+        let rows: Vec<serde_json::Value> = jsonl.lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0]["id"], "order_dec");
+        assert_eq!(rows[0]["occurred_at"], serde_json::to_value(paid_at).unwrap());
+        assert_eq!(rows[1]["id"], "order_capture");
+        assert_eq!(rows[1]["amount"], 4800);
+        assert_eq!(rows[1]["tax"], 800);
+    }
+    
+    #[tokio::test]
+    async fn test_subscription_cancellation_refund_is_exported() {
+        let state = create_test_state();
+        let day = Utc.with_ymd_and_hms(2026, 1, 15, 10, 0, 0).unwrap();
+        let cancellation = SubscriptionCancellation {
+            mode: SubscriptionCancelMode::ImmediateWithRefund,
+            requested_at: day,
+            refund_amount: 500,
+            stripe_refund_id: Some("re_sub".to_string()),
+            refunded_at: Some(day),
+            pending_refund: None,
+        };
+        state.subscriptions.insert("sub_local_1".to_string(), UserSubscription {
+            status: SubscriptionStatus::Cancelled,
+            cancellation: Some(cancellation.clone()),
+            ..common::subscription()
+        });
+        // Remboursement refusé par Stripe, encore dû: rien n'a été remboursé
+        state.subscriptions.insert("sub_local_2".to_string(), UserSubscription {
+            id: "sub_local_2".to_string(),
+            status: SubscriptionStatus::Cancelled,
+            cancellation: Some(SubscriptionCancellation {
+                refund_amount: 0,
+                stripe_refund_id: None,
+                refunded_at: None,
+                ..cancellation
+            }),
+            ..common::subscription()
+        });
+        
+        let csv = export(&state, ExportFormat::Csv).await;
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[1],
+            format!("subscription_refund,re_sub,,user_1,500,eur,83,Refunded,,re_sub,,sub_123,{}", day.to_rfc3339())
+        );
+    }
+}
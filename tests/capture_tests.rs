@@ -0,0 +1,97 @@
+// Tests unitaires pour la capture manuelle des paiements
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common;
+    use ruststripe::models::*;
+    use ruststripe::services::captures::{self, CaptureError};
+    use ruststripe::services::{invoices, order_state, refunds};
+    use chrono::{Duration, Utc};
+    
+    // Commande autorisée de 54,90€, pas encore capturée
+    fn authorized_order() -> Order {
+        Order {
+            status: OrderStatus::Authorized,
+            capture_method: CaptureMethod::Manual,
+            authorization: Some(captures::new_authorization(5490, Utc::now())),
+            ..common::order()
+        }
+    }
+    
+    #[test]
+    fn test_plan_capture_full_and_partial() {
+        let order = authorized_order();
+        
+        assert_eq!(captures::plan_capture(&order, None), Ok(5490));
+        assert_eq!(captures::plan_capture(&order, Some(2500)), Ok(2500));
+        assert_eq!(
+            captures::plan_capture(&order, Some(6000)),
+            Err(CaptureError::AmountExceeded { requested: 6000, authorized: 5490 })
+        );
+    }
+    
+    #[test]
+    fn test_capture_requires_authorized_manual_order() {
+        let mut order = authorized_order();
+        order.status = OrderStatus::Processing;
+        assert_eq!(captures::plan_capture(&order, None), Err(CaptureError::NotAuthorized(OrderStatus::Processing)));
+        
+        let mut order = authorized_order();
+        order.capture_method = CaptureMethod::Automatic;
+        assert_eq!(captures::plan_capture(&order, None), Err(CaptureError::AutomaticCapture));
+    }
+    
+    #[test]
+    fn test_capture_is_sent_only_once() {
+        let mut order = authorized_order();
+        // Capture acceptée par Stripe, webhook `payment_intent.succeeded` pas encore reçu
+        order.authorization.as_mut().unwrap().captured_amount = Some(5490);
+        
+        assert_eq!(order.status, OrderStatus::Authorized);
+        assert_eq!(captures::plan_capture(&order, None), Err(CaptureError::AlreadyCaptured(5490)));
+    }
+    
+    #[test]
+    fn test_expiring_authorization_is_flagged_once() {
+        let mut order = authorized_order();
+        let now = Utc::now();
+        
+        // 7 jours de validité: rien à signaler au début
+        assert!(!captures::flag_if_expiring(&mut order, now, 24));
+        
+        let later = now + Duration::days(6) + Duration::hours(1);
+        assert!(captures::flag_if_expiring(&mut order, later, 24));
+        assert!(order.authorization.as_ref().unwrap().expiring_soon);
+        assert!(!captures::flag_if_expiring(&mut order, later, 24));
+    }
+    
+    #[test]
+    fn test_partial_capture_limits_refund_and_invoice() {
+        let mut order = authorized_order();
+        order.authorization.as_mut().unwrap().captured_amount = Some(2500);
+        order_state::transition(&mut order, OrderStatus::Completed, StatusChangeSource::StripeWebhook, "Capturé").unwrap();
+        
+        assert_eq!(refunds::captured_amount(&order), 2500);
+        
+        let merchant = Merchant {
+            name: "Test Shop".to_string(),
+            address: "Paris".to_string(),
+            vat_number: "FR00000000000".to_string(),
+            email: "shop@example.com".to_string(),
+        };
+        let invoice = invoices::build_invoice(&order, "FAC-000001".to_string(), &merchant, 20, Utc::now());
+        assert_eq!(invoice.total, 2500);
+        let shipping = invoice.shipping.as_ref().map(|s| s.cost).unwrap_or(0);
+        assert_eq!(invoice.lines.iter().map(|l| l.total).sum::<i64>() + shipping, 2500);
+    }
+    
+    #[test]
+    fn test_authorized_order_can_be_cancelled() {
+        let mut order = authorized_order();
+        assert!(order_state::can_transition(&OrderStatus::Processing, &OrderStatus::Authorized));
+        order_state::transition(&mut order, OrderStatus::Cancelled, StatusChangeSource::Admin, "Annulée").unwrap();
+        assert_eq!(order.status, OrderStatus::Cancelled);
+    }
+}
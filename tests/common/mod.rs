@@ -0,0 +1,99 @@
+// Données de test partagées par les tests d'intégration
+//
+// Chaque fichier ne garde que ce qui le distingue, par mise à jour de structure:
+// `UserSubscription { plan_id: ..., ..common::subscription() }`, `Order { status: ..., ..common::order() }`.
+
+#![allow(dead_code)]
+
+use chrono::{Duration, Utc};
+use ruststripe::config::Config;
+use ruststripe::models::*;
+use ruststripe::state::AppState;
+
+/// Configuration de test (clé Stripe factice, politiques par défaut)
+pub fn test_config() -> Config {
+    Config {
+        stripe_secret_key: "sk_test_fake".to_string(),
+        stripe_webhook_secret: "whsec_test".to_string(),
+        base_url: "http://localhost:3000".to_string(),
+        abandoned_cart_after_minutes: 60,
+        cart_expiry_hours: 72,
+        cart_sweep_interval_secs: 300,
+        capture_method: CaptureMethod::Automatic,
+        authorization_expiry_warning_hours: 24,
+        authorization_check_interval_secs: 3600,
+        order_policy: OrderPolicy::default(),
+        dunning_policy: DunningPolicy::default(),
+        dunning_check_interval_secs: 900,
+        usage_report_interval_secs: 300,
+        entitlement_cache_secs: 60,
+        merchant: Merchant {
+            name: "Test Shop".to_string(),
+            address: "1 rue du Test, 75001 Paris".to_string(),
+            vat_number: "FR00000000000".to_string(),
+            email: "shop@example.com".to_string(),
+        },
+        vat_rate_percent: 20,
+    }
+}
+
+/// État de test avec les données de démo
+pub fn test_state() -> AppState {
+    AppState::new(test_config())
+}
+
+/// Abonnement mensuel actif à la formule Normal, période commencée il y a 10 jours
+pub fn subscription() -> UserSubscription {
+    UserSubscription {
+        id: "sub_local_1".to_string(),
+        user_id: "user_1".to_string(),
+        plan_id: "plan_normal".to_string(),
+        stripe_subscription_id: "sub_123".to_string(),
+        status: SubscriptionStatus::Active,
+        current_period_start: Utc::now() - Duration::days(10),
+        current_period_end: Utc::now() + Duration::days(20),
+        cancel_at_period_end: false,
+        created_at: Utc::now() - Duration::days(40),
+        payment_method_id: None,
+        plan_history: vec![],
+        trial_end: None,
+        cancellation: None,
+        dunning: None,
+        latest_invoice: None,
+        synced_at: None,
+        interval: BillingInterval::Monthly,
+        scheduled_interval: None,
+        interval_schedule_id: None,
+        pause: None,
+        metered: None,
+    }
+}
+
+/// Commande payée: 2 × 25€ + 4,90€ de port = 54,90€ TTC
+pub fn order() -> Order {
+    Order {
+        id: "order_1".to_string(),
+        user_id: "user_1".to_string(),
+        items: vec![
+            OrderItem { product_id: "cap_001".to_string(), variant_sku: None, product_name: "Casquette Rouge".to_string(), quantity: 2, price: 2500 },
+        ],
+        subtotal: 5000,
+        total: 5490,
+        status: OrderStatus::Completed,
+        shipping_address: None,
+        shipping: Some(OrderShipping { method_id: "standard".to_string(), method_name: "Colissimo".to_string(), cost: 490 }),
+        payment_intent_id: Some("pi_123".to_string()),
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+        status_history: vec![],
+        refunds: vec![],
+        stock_reserved: true,
+        payment_attempts: vec![],
+        capture_method: CaptureMethod::Automatic,
+        authorization: None,
+        fulfillment_status: FulfillmentStatus::Unfulfilled,
+        fulfillment_history: vec![],
+        shipments: vec![],
+        return_requested_at: None,
+    }
+}
@@ -0,0 +1,115 @@
+// Tests unitaires pour la politique de relance des abonnements
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common;
+    use ruststripe::models::*;
+    use ruststripe::services::dunning::{self, DunningStep, NotificationContext};
+    use chrono::{Duration, Utc};
+    use validator::Validate;
+    
+    fn context() -> NotificationContext<'static> {
+        NotificationContext { plan_name: "Normal", amount: 1000 }
+    }
+    
+    #[test]
+    fn test_retries_follow_schedule_then_final_action() {
+        let policy = DunningPolicy { retry_schedule_days: vec![1, 3], ..Default::default() };
+        let mut sub = common::subscription();
+        let now = Utc::now();
+        
+        let step = dunning::on_payment_failed(&mut sub, &policy, "in_1", 1, &context(), now);
+        assert!(matches!(step, DunningStep::Retry { attempt: 1, .. }));
+        assert_eq!(sub.status, SubscriptionStatus::PastDue);
+        assert_eq!(sub.dunning.as_ref().unwrap().next_retry_at, Some(now + Duration::days(1)));
+        
+        let later = now + Duration::days(1);
+        let step = dunning::on_payment_failed(&mut sub, &policy, "in_1", 2, &context(), later);
+        assert!(matches!(step, DunningStep::Retry { attempt: 2, .. }));
+        assert_eq!(sub.dunning.as_ref().unwrap().next_retry_at, Some(later + Duration::days(3)));
+        // La période de grâce part du premier échec
+        assert_eq!(sub.dunning.as_ref().unwrap().grace_until, now + Duration::days(3));
+        
+        let step = dunning::on_payment_failed(&mut sub, &policy, "in_1", 3, &context(), later + Duration::days(3));
+        assert!(matches!(step, DunningStep::Final { action: DunningFinalAction::MarkUnpaid, .. }));
+        assert_eq!(sub.status, SubscriptionStatus::Unpaid);
+        assert_eq!(sub.dunning.as_ref().unwrap().next_retry_at, None);
+    }
+    
+    #[test]
+    fn test_templates_and_final_action_are_configurable() {
+        let policy = DunningPolicy {
+            retry_schedule_days: vec![2],
+            step_templates: vec!["{plan}: échec {attempt}/{max_attempts} de {amount}, retente le {next_retry}".to_string()],
+            final_template: "{plan} {action}".to_string(),
+            final_action: DunningFinalAction::Cancel,
+            ..Default::default()
+        };
+        let mut sub = common::subscription();
+        let now = Utc::now();
+        
+        let DunningStep::Retry { message, .. } = dunning::on_payment_failed(&mut sub, &policy, "in_1", 1, &context(), now) else {
+            panic!("relance attendue");
+        };
+        let expected = format!("Normal: échec 1/2 de 10,00 €, retente le {}", (now + Duration::days(2)).format("%d/%m/%Y"));
+        assert_eq!(message, expected);
+        
+        let step = dunning::on_payment_failed(&mut sub, &policy, "in_1", 2, &context(), now);
+        assert_eq!(step, DunningStep::Final { action: DunningFinalAction::Cancel, message: "Normal résilié".to_string() });
+        assert_eq!(sub.status, SubscriptionStatus::Cancelled);
+    }
+    
+    #[test]
+    fn test_replayed_webhook_is_ignored() {
+        let policy = DunningPolicy::default();
+        let mut sub = common::subscription();
+        
+        dunning::on_payment_failed(&mut sub, &policy, "in_1", 1, &context(), Utc::now());
+        let step = dunning::on_payment_failed(&mut sub, &policy, "in_1", 1, &context(), Utc::now());
+        assert_eq!(step, DunningStep::AlreadyHandled);
+        
+        // Nouvelle facture: les relances repartent de zéro
+        let step = dunning::on_payment_failed(&mut sub, &policy, "in_2", 1, &context(), Utc::now());
+        assert!(matches!(step, DunningStep::Retry { attempt: 1, .. }));
+    }
+    
+    #[test]
+    fn test_recovery_returns_to_active() {
+        let policy = DunningPolicy::default();
+        let mut sub = common::subscription();
+        let now = Utc::now();
+        
+        dunning::on_payment_failed(&mut sub, &policy, "in_1", 1, &context(), now);
+        assert!(dunning::in_grace_period(&sub, now + Duration::days(2)));
+        assert!(!dunning::in_grace_period(&sub, now + Duration::days(4)));
+        
+        let message = dunning::on_payment_recovered(&mut sub, &policy, &context()).unwrap();
+        assert!(message.contains("Normal"));
+        assert_eq!(sub.status, SubscriptionStatus::Active);
+        assert!(sub.dunning.is_none());
+        
+        // Paiement ordinaire: pas de message de rétablissement
+        assert_eq!(dunning::on_payment_recovered(&mut sub, &policy, &context()), None);
+    }
+    
+    #[test]
+    fn test_policy_validation() {
+        assert!(DunningPolicy::default().validate().is_ok());
+        
+        let policy = DunningPolicy { retry_schedule_days: vec![1, 0], ..Default::default() };
+        assert!(policy.validate().is_err());
+        
+        let policy = DunningPolicy { step_templates: vec![], ..Default::default() };
+        assert!(policy.validate().is_err());
+    }
+    
+    #[test]
+    fn test_technical_retry_delay_backs_off_up_to_a_day() {
+        assert_eq!(dunning::technical_retry_delay(0), Duration::minutes(15));
+        assert_eq!(dunning::technical_retry_delay(1), Duration::minutes(30));
+        assert_eq!(dunning::technical_retry_delay(3), Duration::hours(2));
+        assert_eq!(dunning::technical_retry_delay(30), Duration::days(1));
+    }
+}
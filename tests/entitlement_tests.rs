@@ -0,0 +1,146 @@
+// Tests unitaires pour les droits d'accès des abonnements
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common;
+    use ruststripe::models::*;
+    use ruststripe::services::entitlements::{self, AccessReason};
+    use chrono::{Duration, Utc};
+    
+    fn subscription(id: &str, plan_id: &str) -> UserSubscription {
+        UserSubscription {
+            id: id.to_string(),
+            plan_id: plan_id.to_string(),
+            stripe_subscription_id: format!("sub_{}", id),
+            ..common::subscription()
+        }
+    }
+    
+    fn names(list: &[&str]) -> Vec<String> {
+        list.iter().map(|name| name.to_string()).collect()
+    }
+    
+    #[test]
+    fn test_access_follows_status_and_period() {
+        let now = Utc::now();
+        let mut sub = subscription("s1", "plan_normal");
+        
+        let access = entitlements::subscription_access(&sub, now);
+        assert!(access.granted);
+        assert_eq!(access.valid_until, Some(sub.current_period_end));
+        
+        sub.status = SubscriptionStatus::Paused;
+        assert_eq!(entitlements::subscription_access(&sub, now).reason, AccessReason::Paused);
+        
+        // Annulation en fin de période: accès jusqu'à l'échéance, plus après
+        sub.status = SubscriptionStatus::Active;
+        sub.cancel_at_period_end = true;
+        assert!(entitlements::subscription_access(&sub, now).granted);
+        let after = sub.current_period_end + Duration::minutes(1);
+        assert_eq!(entitlements::subscription_access(&sub, after).reason, AccessReason::PeriodEnded);
+    }
+    
+    #[test]
+    fn test_grace_period_keeps_access_until_its_end() {
+        let now = Utc::now();
+        let mut sub = subscription("s1", "plan_normal");
+        sub.status = SubscriptionStatus::PastDue;
+        sub.dunning = Some(DunningState {
+            stripe_invoice_id: "in_1".to_string(),
+            attempts: 1,
+            started_at: now - Duration::days(1),
+            next_retry_at: None,
+            grace_until: now + Duration::days(2),
+            technical_failures: 0,
+        });
+        
+        let access = entitlements::subscription_access(&sub, now);
+        assert!(access.granted);
+        assert_eq!(access.reason, AccessReason::GracePeriod);
+        assert_eq!(access.valid_until, Some(now + Duration::days(2)));
+        
+        let later = entitlements::subscription_access(&sub, now + Duration::days(3));
+        assert!(!later.granted);
+        assert_eq!(later.reason, AccessReason::PastDue);
+    }
+    
+    #[test]
+    fn test_entitlement_must_be_included_in_plan() {
+        let now = Utc::now();
+        let normal = subscription("s1", "plan_normal");
+        let daily = names(&["daily_paper"]);
+        
+        let check = entitlements::check_access("user_1", "supplement", [(&normal, daily.as_slice())], now);
+        assert!(!check.granted);
+        assert_eq!(check.reason, AccessReason::NotIncluded);
+        assert_eq!(check.subscription_id.as_deref(), Some("s1"));
+        
+        let check = entitlements::check_access("user_1", "supplement", [], now);
+        assert_eq!(check.reason, AccessReason::NoSubscription);
+    }
+    
+    #[test]
+    fn test_granting_subscription_wins_over_ended_one() {
+        let now = Utc::now();
+        let mut old = subscription("old", "plan_complet");
+        old.status = SubscriptionStatus::Cancelled;
+        old.created_at = now - Duration::days(5); // Plus récent, mais résilié
+        let current = subscription("current", "plan_supplement");
+        let complet = names(&["daily_paper", "supplement", "weekend_magazine"]);
+        let supplement = names(&["daily_paper", "supplement"]);
+        
+        let check = entitlements::check_access(
+            "user_1",
+            "supplement",
+            [(&old, complet.as_slice()), (&current, supplement.as_slice())],
+            now,
+        );
+        assert!(check.granted);
+        assert_eq!(check.subscription_id.as_deref(), Some("current"));
+        
+        let check = entitlements::check_access(
+            "user_1",
+            "weekend_magazine",
+            [(&old, complet.as_slice()), (&current, supplement.as_slice())],
+            now,
+        );
+        assert!(!check.granted);
+        assert_eq!(check.reason, AccessReason::Cancelled);
+    }
+    
+    #[test]
+    fn test_cache_never_outlives_decision() {
+        let now = Utc::now();
+        let mut sub = subscription("s1", "plan_normal");
+        sub.current_period_end = now + Duration::seconds(30);
+        let daily = names(&["daily_paper"]);
+        
+        let check = entitlements::check_access("user_1", "daily_paper", [(&sub, daily.as_slice())], now);
+        assert_eq!(entitlements::cache_max_age(&check, 60), 30);
+        
+        sub.current_period_end = now + Duration::days(20);
+        let check = entitlements::check_access("user_1", "daily_paper", [(&sub, daily.as_slice())], now);
+        assert_eq!(entitlements::cache_max_age(&check, 60), 60);
+    }
+    
+    #[test]
+    fn test_paused_denial_is_cached_until_resumption() {
+        let now = Utc::now();
+        let mut sub = subscription("s1", "plan_normal");
+        sub.status = SubscriptionStatus::Paused;
+        sub.pause = Some(SubscriptionPause { paused_at: now - Duration::days(3), resumes_at: Some(now + Duration::seconds(20)) });
+        let daily = names(&["daily_paper"]);
+        
+        let check = entitlements::check_access("user_1", "daily_paper", [(&sub, daily.as_slice())], now);
+        assert!(!check.granted);
+        assert_eq!(check.valid_until, Some(now + Duration::seconds(20)));
+        assert_eq!(entitlements::cache_max_age(&check, 60), 20);
+        
+        // Sans date de reprise, le refus dure jusqu'à une action
+        sub.pause = Some(SubscriptionPause { paused_at: now - Duration::days(3), resumes_at: None });
+        let check = entitlements::check_access("user_1", "daily_paper", [(&sub, daily.as_slice())], now);
+        assert_eq!(entitlements::cache_max_age(&check, 60), 60);
+    }
+}
@@ -0,0 +1,185 @@
+// Tests unitaires pour le workflow logistique
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common;
+    use ruststripe::models::*;
+    use ruststripe::routes::fulfillment::{advance_fulfillment, list_return_requests};
+    use ruststripe::routes::validation::ValidatedJson;
+    use ruststripe::services::fulfillment::{self, FulfillmentError};
+    use ruststripe::state::AppState;
+    use axum::{extract::{Path, State}, http::StatusCode};
+    use chrono::Utc;
+    
+    fn shipment() -> Shipment {
+        Shipment {
+            id: "ship_1".to_string(),
+            carrier: "Colissimo".to_string(),
+            tracking_number: "6A12345678901".to_string(),
+            tracking_url: None,
+            shipped_at: Utc::now(),
+            delivered_at: None,
+        }
+    }
+    
+    #[test]
+    fn test_full_fulfillment_flow() {
+        let mut order = common::order();
+        
+        fulfillment::advance(&mut order, FulfillmentStatus::Picking, None, "Préparation").unwrap();
+        fulfillment::advance(&mut order, FulfillmentStatus::Shipped, Some(shipment()), "Remis au transporteur").unwrap();
+        fulfillment::advance(&mut order, FulfillmentStatus::Delivered, None, "Livré").unwrap();
+        
+        assert_eq!(order.fulfillment_status, FulfillmentStatus::Delivered);
+        assert_eq!(order.fulfillment_history.len(), 3);
+        assert_eq!(order.shipments.len(), 1);
+        assert!(order.shipments[0].delivered_at.is_some());
+        // Le statut de paiement n'est pas touché
+        assert_eq!(order.status, OrderStatus::Completed);
+    }
+    
+    #[test]
+    fn test_shipping_requires_tracking_and_order() {
+        let mut order = common::order();
+        
+        // Pas d'expédition sans préparation
+        assert_eq!(
+            fulfillment::advance(&mut order, FulfillmentStatus::Shipped, Some(shipment()), "Expédié"),
+            Err(FulfillmentError::Forbidden { from: FulfillmentStatus::Unfulfilled, to: FulfillmentStatus::Shipped })
+        );
+        
+        fulfillment::advance(&mut order, FulfillmentStatus::Picking, None, "Préparation").unwrap();
+        assert_eq!(
+            fulfillment::advance(&mut order, FulfillmentStatus::Shipped, None, "Expédié"),
+            Err(FulfillmentError::MissingTracking)
+        );
+        assert_eq!(order.fulfillment_status, FulfillmentStatus::Picking);
+    }
+    
+    #[test]
+    fn test_unpaid_order_cannot_be_picked() {
+        let mut order = common::order();
+        order.status = OrderStatus::Processing;
+        
+        assert_eq!(
+            fulfillment::advance(&mut order, FulfillmentStatus::Picking, None, "Préparation"),
+            Err(FulfillmentError::NotPaid(OrderStatus::Processing))
+        );
+        
+        // Capture manuelle: une commande autorisée peut être préparée
+        order.status = OrderStatus::Authorized;
+        assert!(fulfillment::advance(&mut order, FulfillmentStatus::Picking, None, "Préparation").is_ok());
+    }
+    
+    #[test]
+    fn test_edits_locked_once_picking_started() {
+        let mut order = common::order();
+        assert!(fulfillment::ensure_editable(&order).is_ok());
+        
+        fulfillment::advance(&mut order, FulfillmentStatus::Picking, None, "Préparation").unwrap();
+        assert_eq!(
+            fulfillment::ensure_editable(&order),
+            Err(FulfillmentError::EditLocked(FulfillmentStatus::Picking))
+        );
+    }
+    
+    fn create_test_state() -> AppState {
+        common::test_state()
+    }
+    
+    fn advance_request(status: FulfillmentStatus) -> ValidatedJson<AdvanceFulfillmentRequest> {
+        ValidatedJson(AdvanceFulfillmentRequest {
+            status,
+            carrier: None,
+            tracking_number: None,
+            tracking_url: None,
+            reason: None,
+        })
+    }
+    
+    fn delivered_order(status: OrderStatus) -> Order {
+        Order { status, fulfillment_status: FulfillmentStatus::Delivered, ..common::order() }
+    }
+    
+    #[tokio::test]
+    async fn test_advance_waits_for_order_operation() {
+        let state = create_test_state();
+        state.orders.insert("order_1".to_string(), delivered_order(OrderStatus::Completed));
+        
+        // Une capture ou un remboursement est en cours sur la commande
+        let operation = state.begin_order_operation("order_1").unwrap();
+        let result = advance_fulfillment(
+            State(state.clone()),
+            Path("order_1".to_string()),
+            advance_request(FulfillmentStatus::Returned),
+        ).await;
+        
+        assert_eq!(result.unwrap_err().0, StatusCode::CONFLICT);
+        assert_eq!(state.orders.get("order_1").unwrap().fulfillment_status, FulfillmentStatus::Delivered);
+        drop(operation);
+    }
+    
+    #[tokio::test]
+    async fn test_return_requires_a_settled_payment() {
+        let state = create_test_state();
+        // Capture envoyée, mais le webhook n'a pas encore confirmé l'encaissement
+        state.orders.insert("order_1".to_string(), delivered_order(OrderStatus::Authorized));
+        
+        let result = advance_fulfillment(
+            State(state.clone()),
+            Path("order_1".to_string()),
+            advance_request(FulfillmentStatus::Returned),
+        ).await;
+        
+        assert_eq!(result.unwrap_err().0, StatusCode::CONFLICT);
+        assert_eq!(state.orders.get("order_1").unwrap().fulfillment_status, FulfillmentStatus::Delivered);
+    }
+    
+    #[tokio::test]
+    async fn test_return_of_refunded_order_needs_no_refund() {
+        let state = create_test_state();
+        state.orders.insert("order_1".to_string(), delivered_order(OrderStatus::Refunded));
+        
+        let order = advance_fulfillment(
+            State(state.clone()),
+            Path("order_1".to_string()),
+            advance_request(FulfillmentStatus::Returned),
+        ).await.unwrap().0;
+        
+        assert_eq!(order.fulfillment_status, FulfillmentStatus::Returned);
+        assert_eq!(order.status, OrderStatus::Refunded);
+        assert!(order.refunds.is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_pending_returns_are_listed_oldest_first() {
+        let state = create_test_state();
+        let now = Utc::now();
+        
+        let mut recent = delivered_order(OrderStatus::Completed);
+        recent.id = "order_recent".to_string();
+        recent.return_requested_at = Some(now - chrono::Duration::hours(1));
+        let mut old = delivered_order(OrderStatus::Completed);
+        old.id = "order_old".to_string();
+        old.return_requested_at = Some(now - chrono::Duration::days(3));
+        // Colis déjà reçu: le retour est traité
+        let mut received = delivered_order(OrderStatus::Refunded);
+        received.id = "order_received".to_string();
+        received.return_requested_at = Some(now - chrono::Duration::days(5));
+        received.fulfillment_status = FulfillmentStatus::Returned;
+        let mut delivered = delivered_order(OrderStatus::Completed);
+        delivered.id = "order_delivered".to_string();
+        
+        for order in [recent, old, received, delivered] {
+            state.orders.insert(order.id.clone(), order);
+        }
+        
+        let ids: Vec<String> = list_return_requests(State(state.clone())).await.unwrap().0
+            .into_iter()
+            .map(|order| order.id)
+            .collect();
+        assert_eq!(ids, vec!["order_old", "order_recent"]);
+    }
+}
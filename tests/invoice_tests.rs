@@ -0,0 +1,86 @@
+// Tests unitaires pour les factures et leur rendu
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common;
+    use ruststripe::config::Config;
+    use ruststripe::models::*;
+    use ruststripe::services::documents::{self, DocumentFormat, DocumentKind};
+    use ruststripe::services::invoices::{self, InvoiceError};
+    use ruststripe::state::AppState;
+    
+    fn create_test_state() -> AppState {
+        let config = common::test_config();
+        AppState::new(Config {
+            merchant: Merchant { name: "Casquettes & Cie".to_string(), ..config.merchant.clone() },
+            ..config
+        })
+    }
+    
+    // Commande payée: 2 × 25€ + 4,90€ de port = 54,90€ TTC
+    fn paid_order(id: &str) -> Order {
+        Order { id: id.to_string(), ..common::order() }
+    }
+    
+    #[test]
+    fn test_tax_included_in_total() {
+        // 54,90€ TTC à 20% → 9,15€ de TVA
+        assert_eq!(invoices::tax_included(5490, 20), 915);
+        assert_eq!(invoices::tax_included(1200, 20), 200);
+        assert_eq!(invoices::tax_included(1000, 0), 0);
+    }
+    
+    #[test]
+    fn test_invoice_numbers_are_sequential_without_gaps() {
+        let state = create_test_state();
+        
+        // Une commande non payée ne consomme pas de numéro
+        let mut pending = paid_order("order_pending");
+        pending.status = OrderStatus::Processing;
+        assert_eq!(
+            invoices::issue_invoice(&state, &pending).unwrap_err(),
+            InvoiceError::NotPaid(OrderStatus::Processing)
+        );
+        
+        let first = invoices::issue_invoice(&state, &paid_order("order_1")).unwrap();
+        let second = invoices::issue_invoice(&state, &paid_order("order_2")).unwrap();
+        // Redemander la facture renvoie la même, sans nouveau numéro
+        let again = invoices::issue_invoice(&state, &paid_order("order_1")).unwrap();
+        
+        assert_eq!(first.number, "FAC-000001");
+        assert_eq!(second.number, "FAC-000002");
+        assert_eq!(again.number, "FAC-000001");
+        assert_eq!(first.total_excl_tax + first.tax_amount, first.total);
+    }
+    
+    #[test]
+    fn test_html_invoice_contains_details() {
+        let state = create_test_state();
+        let invoice = invoices::issue_invoice(&state, &paid_order("order_1")).unwrap();
+        
+        let html = documents::render_html(&invoice, DocumentKind::Invoice);
+        assert!(html.contains("Facture FAC-000001"));
+        assert!(html.contains("Casquettes &amp; Cie")); // Échappé
+        assert!(html.contains("FR00000000000"));
+        assert!(html.contains("54,90 €"));
+        assert!(html.contains("9,15 €"));
+    }
+    
+    #[test]
+    fn test_pdf_receipt_attachment() {
+        let state = create_test_state();
+        let invoice = invoices::issue_invoice(&state, &paid_order("order_1")).unwrap();
+        
+        let attachment = documents::attachment(&invoice, DocumentKind::Receipt, DocumentFormat::Pdf);
+        assert_eq!(attachment.filename, "recu-FAC-000001.pdf");
+        assert_eq!(attachment.content_type, "application/pdf");
+        assert!(attachment.content.starts_with(b"%PDF-1.4"));
+        assert!(attachment.content.ends_with(b"%%EOF\n"));
+        
+        // "€" encodé en WinAnsi (0x80)
+        let total = b"Total TTC: 54,90 \x80";
+        assert!(attachment.content.windows(total.len()).any(|w| w == total));
+    }
+}
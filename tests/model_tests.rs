@@ -13,6 +13,9 @@ mod tests {
             price: 2500,
             stock: 50,
             description: "A nice cap".to_string(),
+            weight_grams: 100,
+            archived: false,
+            variants: vec![],
         };
         
         assert_eq!(product.id, "prod_123");
@@ -20,10 +23,35 @@ mod tests {
         assert!(product.stock > 0);
     }
     
+    #[test]
+    fn test_product_variants_stock() {
+        let product = Product {
+            id: "cap_v".to_string(),
+            name: "Cap".to_string(),
+            price: 2500,
+            stock: 0,
+            description: "Cap with sizes".to_string(),
+            weight_grams: 100,
+            archived: false,
+            variants: vec![
+                ProductVariant { sku: "cap_v-s".to_string(), size: Some("S".to_string()), color: None, stock: 3, price: None },
+                ProductVariant { sku: "cap_v-l".to_string(), size: Some("L".to_string()), color: None, stock: 4, price: Some(2700) },
+            ],
+        };
+        
+        // Le stock d'un produit décliné est celui de ses variantes
+        assert_eq!(product.total_stock(), 7);
+        assert_eq!(product.variant("cap_v-l").unwrap().price, Some(2700));
+        // Prix payé par variante: celui du produit à défaut
+        assert_eq!(product.unit_prices(), vec![2500, 2700]);
+        assert!(product.variant("cap_v-xl").is_none());
+    }
+    
     #[test]
     fn test_cart_item_serialization() {
         let item = CartItem {
             product_id: "prod_1".to_string(),
+            variant_sku: None,
             quantity: 3,
         };
         
@@ -35,6 +63,7 @@ mod tests {
     fn test_order_item_total() {
         let order_item = OrderItem {
             product_id: "prod_1".to_string(),
+            variant_sku: None,
             product_name: "Cap".to_string(),
             quantity: 2,
             price: 1500,
@@ -74,6 +103,10 @@ mod tests {
             name: "Basic".to_string(),
             price: 1000,
             description: "Basic plan".to_string(),
+            trial_days: None,
+            prices: Default::default(),
+            metered: None,
+            entitlements: vec![],
         };
         
         // Valider qu'un plan a un prix positif
@@ -88,6 +121,10 @@ mod tests {
             user_id: "user_1".to_string(),
             items: vec![],
             created_at: Utc::now(),
+            updated_at: Utc::now(),
+            abandoned_at: None,
+            shipping_address: None,
+            shipping_method_id: None,
         };
         
         assert!(cart.items.is_empty());
@@ -99,11 +136,15 @@ mod tests {
         let cart = Cart {
             user_id: "user_1".to_string(),
             items: vec![
-                CartItem { product_id: "p1".to_string(), quantity: 2 },
-                CartItem { product_id: "p2".to_string(), quantity: 1 },
-                CartItem { product_id: "p3".to_string(), quantity: 5 },
+                CartItem { product_id: "p1".to_string(), variant_sku: None, quantity: 2 },
+                CartItem { product_id: "p2".to_string(), variant_sku: None, quantity: 1 },
+                CartItem { product_id: "p3".to_string(), variant_sku: None, quantity: 5 },
             ],
             created_at: Utc::now(),
+            updated_at: Utc::now(),
+            abandoned_at: None,
+            shipping_address: None,
+            shipping_method_id: None,
         };
         
         assert_eq!(cart.items.len(), 3);
@@ -0,0 +1,123 @@
+// Tests unitaires pour le listing paginé des commandes
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common;
+    use ruststripe::models::*;
+    use ruststripe::services::order_listing::{
+        self, OrderFilter, OrderListError, OrderSortField, PageRequest, SortDirection,
+    };
+    use chrono::{Duration, Utc};
+    use dashmap::DashMap;
+    
+    fn order(id: &str, user_id: &str, total: i64, hours_ago: i64, status: OrderStatus) -> Order {
+        let created_at = Utc::now() - Duration::hours(hours_ago);
+        Order {
+            id: id.to_string(),
+            user_id: user_id.to_string(),
+            subtotal: total,
+            total,
+            status,
+            created_at,
+            updated_at: created_at,
+            ..common::order()
+        }
+    }
+    
+    fn sample_orders() -> DashMap<String, Order> {
+        [
+            order("order_a", "user_1", 2500, 5, OrderStatus::Completed),
+            order("order_b", "user_1", 8000, 4, OrderStatus::Pending),
+            order("order_c", "user_2", 3000, 3, OrderStatus::Completed),
+            order("order_d", "user_1", 3000, 2, OrderStatus::Completed),
+            order("order_e", "user_1", 1200, 1, OrderStatus::Cancelled),
+        ]
+        .into_iter()
+        .map(|order| (order.id.clone(), order))
+        .collect()
+    }
+    
+    fn ids(page: &OrderPage) -> Vec<&str> {
+        page.orders.iter().map(|o| o.id.as_str()).collect()
+    }
+    
+    #[test]
+    fn test_cursor_pagination_walks_all_pages() {
+        let filter = OrderFilter { user_id: Some("user_1".to_string()), ..Default::default() };
+        let mut page = PageRequest { limit: Some(2), ..Default::default() };
+        
+        let first = order_listing::list_orders(&sample_orders(), &filter, &page).unwrap();
+        assert_eq!(first.total_count, 4);
+        assert_eq!(ids(&first), vec!["order_e", "order_d"]); // Plus récentes d'abord
+        
+        page.cursor = first.next_cursor.clone();
+        let second = order_listing::list_orders(&sample_orders(), &filter, &page).unwrap();
+        assert_eq!(ids(&second), vec!["order_b", "order_a"]);
+        assert!(second.next_cursor.is_none());
+    }
+    
+    #[test]
+    fn test_cursor_survives_removal_of_the_last_listed_order() {
+        let orders = sample_orders();
+        let mut page = PageRequest { sort: OrderSortField::Total, limit: Some(2), ..Default::default() };
+        
+        let first = order_listing::list_orders(&orders, &OrderFilter::default(), &page).unwrap();
+        assert_eq!(first.total_count, 5);
+        assert_eq!(ids(&first), vec!["order_b", "order_d"]);
+        
+        // Le curseur ne dépend que de la clé de tri: la suite reste juste
+        orders.remove("order_d");
+        page.cursor = first.next_cursor.clone();
+        let second = order_listing::list_orders(&orders, &OrderFilter::default(), &page).unwrap();
+        assert_eq!(ids(&second), vec!["order_c", "order_a"]);
+    }
+    
+    #[test]
+    fn test_filters_and_sort_by_total() {
+        let filter = OrderFilter {
+            status: Some(OrderStatus::Completed),
+            min_total: Some(2600),
+            ..Default::default()
+        };
+        let page = PageRequest {
+            sort: OrderSortField::Total,
+            direction: SortDirection::Asc,
+            ..Default::default()
+        };
+        
+        // Vue admin: toutes les commandes, égalités départagées par id
+        let result = order_listing::list_orders(&sample_orders(), &filter, &page).unwrap();
+        assert_eq!(result.total_count, 2);
+        assert_eq!(ids(&result), vec!["order_c", "order_d"]);
+    }
+    
+    #[test]
+    fn test_date_range_filter() {
+        let filter = OrderFilter {
+            created_from: Some(Utc::now() - Duration::minutes(270)),
+            created_to: Some(Utc::now() - Duration::minutes(90)),
+            ..Default::default()
+        };
+        
+        let result = order_listing::list_orders(&sample_orders(), &filter, &PageRequest::default()).unwrap();
+        assert_eq!(ids(&result), vec!["order_d", "order_c", "order_b"]);
+    }
+    
+    #[test]
+    fn test_invalid_cursor_and_range_are_rejected() {
+        // Curseur émis pour un autre tri
+        let page = PageRequest {
+            sort: OrderSortField::Total,
+            cursor: Some("created_at:123:order_a".to_string()),
+            ..Default::default()
+        };
+        let err = order_listing::list_orders(&sample_orders(), &OrderFilter::default(), &page).unwrap_err();
+        assert_eq!(err, OrderListError::InvalidCursor);
+        
+        let filter = OrderFilter { min_total: Some(5000), max_total: Some(1000), ..Default::default() };
+        let err = order_listing::list_orders(&sample_orders(), &filter, &PageRequest::default()).unwrap_err();
+        assert!(matches!(err, OrderListError::InvalidRange(_)));
+    }
+}
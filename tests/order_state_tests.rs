@@ -0,0 +1,82 @@
+// Tests unitaires pour la machine à états des commandes
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common;
+    use ruststripe::models::*;
+    use ruststripe::services::order_state::{self, TransitionError};
+    
+    fn pending_order() -> Order {
+        Order {
+            subtotal: 2500,
+            total: 2990,
+            status: OrderStatus::Pending,
+            payment_intent_id: None,
+            status_history: vec![order_state::creation_entry(StatusChangeSource::System)],
+            stock_reserved: false,
+            ..common::order()
+        }
+    }
+    
+    #[test]
+    fn test_transition_records_history() {
+        let mut order = pending_order();
+        
+        order_state::transition(&mut order, OrderStatus::Processing, StatusChangeSource::System, "PI créé").unwrap();
+        order_state::transition(&mut order, OrderStatus::Completed, StatusChangeSource::StripeWebhook, "Payé").unwrap();
+        
+        assert_eq!(order.status, OrderStatus::Completed);
+        assert_eq!(order.status_history.len(), 3);
+        assert_eq!(order.status_history[1].from, Some(OrderStatus::Pending));
+        assert_eq!(order.status_history[2].source, StatusChangeSource::StripeWebhook);
+        assert_eq!(order.status_history[2].reason, "Payé");
+    }
+    
+    #[test]
+    fn test_illegal_transition_is_rejected() {
+        let mut order = pending_order();
+        order_state::transition(&mut order, OrderStatus::Cancelled, StatusChangeSource::Admin, "Annulée").unwrap();
+        
+        let err = order_state::transition(&mut order, OrderStatus::Completed, StatusChangeSource::StripeWebhook, "Payé")
+            .unwrap_err();
+        assert_eq!(err, TransitionError::Forbidden { from: OrderStatus::Cancelled, to: OrderStatus::Completed });
+        
+        // L'historique n'est pas modifié par un refus
+        assert_eq!(order.status, OrderStatus::Cancelled);
+        assert_eq!(order.status_history.len(), 2);
+    }
+    
+    #[test]
+    fn test_replayed_transition_is_detected() {
+        let mut order = pending_order();
+        order_state::transition(&mut order, OrderStatus::Failed, StatusChangeSource::StripeWebhook, "Échec").unwrap();
+        
+        let err = order_state::transition(&mut order, OrderStatus::Failed, StatusChangeSource::StripeWebhook, "Échec")
+            .unwrap_err();
+        assert_eq!(err, TransitionError::AlreadyInState(OrderStatus::Failed));
+    }
+    
+    #[test]
+    fn test_processing_order_can_be_cancelled() {
+        // Le PaymentIntent est annulé sur Stripe avant cette transition
+        let mut order = pending_order();
+        order_state::transition(&mut order, OrderStatus::Processing, StatusChangeSource::System, "PI créé").unwrap();
+        
+        assert!(order_state::can_transition(&OrderStatus::Processing, &OrderStatus::Cancelled));
+        order_state::transition(&mut order, OrderStatus::Cancelled, StatusChangeSource::Customer("user_1".to_string()), "Annulée")
+            .unwrap();
+        assert_eq!(order.status, OrderStatus::Cancelled);
+    }
+    
+    #[test]
+    fn test_failed_order_can_be_repriced() {
+        // Après modification, la commande attend le paiement du nouveau PaymentIntent
+        let mut order = pending_order();
+        order_state::transition(&mut order, OrderStatus::Failed, StatusChangeSource::StripeWebhook, "Échec").unwrap();
+        order_state::transition(&mut order, OrderStatus::Processing, StatusChangeSource::Customer("user_1".to_string()), "Commande modifiée")
+            .unwrap();
+        assert_eq!(order.status, OrderStatus::Processing);
+    }
+}
@@ -0,0 +1,109 @@
+// Tests unitaires pour les politiques d'annulation et de modification
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common;
+    use ruststripe::models::*;
+    use ruststripe::services::policies::{self, CancellationOutcome, PolicyError};
+    use chrono::{Duration, Utc};
+    use validator::Validate;
+    
+    fn order(hours_ago: i64) -> Order {
+        let created_at = Utc::now() - Duration::hours(hours_ago);
+        Order { created_at, updated_at: created_at, ..common::order() }
+    }
+    
+    fn vip() -> PolicyOverride {
+        PolicyOverride { cancel_window_hours: Some(72), modify_window_hours: Some(72), ..Default::default() }
+    }
+    
+    #[test]
+    fn test_tier_then_most_restrictive_product_wins() {
+        let made_to_order = PolicyOverride {
+            modify_window_hours: Some(2),
+            cancel_after_shipment: Some(PostShipmentCancellation::Refuse),
+            ..Default::default()
+        };
+        let fragile = PolicyOverride { modify_window_hours: Some(6), cancel_window_hours: Some(12), ..Default::default() };
+        
+        let applied = policies::resolve(
+            &OrderPolicy::default(),
+            Some(("vip", &vip())),
+            vec![("cap_003", &made_to_order), ("cap_009", &fragile)],
+        );
+        
+        assert_eq!(applied.sources, vec!["défaut", "niveau vip", "produit cap_003", "produit cap_009"]);
+        assert_eq!(applied.policy.cancel_window_hours, 12);
+        assert_eq!(applied.policy.modify_window_hours, 2);
+        assert_eq!(applied.policy.cancel_after_shipment, PostShipmentCancellation::Refuse);
+        assert_eq!(applied.policy.return_window_days, 14);
+    }
+    
+    #[test]
+    fn test_separate_cancel_and_modify_windows() {
+        let base = OrderPolicy { cancel_window_hours: 48, modify_window_hours: 4, ..Default::default() };
+        let applied = policies::resolve(&base, None, vec![]);
+        let order = order(10);
+        
+        assert_eq!(policies::check_cancel(&order, &applied, Utc::now()), Ok(CancellationOutcome::Cancel));
+        
+        let err = policies::check_modify(&order, &applied, Utc::now()).unwrap_err();
+        assert!(matches!(err, PolicyError::ModifyWindowExpired { elapsed_hours: 10, .. }));
+        // Le message cite la politique appliquée
+        assert!(err.to_string().contains("politique défaut: annulation 48h, modification 4h"));
+    }
+    
+    #[test]
+    fn test_cancel_after_shipment_becomes_return() {
+        let mut order = order(100);
+        order.fulfillment_status = FulfillmentStatus::Shipped;
+        order.shipments.push(Shipment {
+            id: "ship_1".to_string(),
+            carrier: "Colissimo".to_string(),
+            tracking_number: "6A1".to_string(),
+            tracking_url: None,
+            shipped_at: Utc::now() - Duration::days(2),
+            delivered_at: None,
+        });
+        
+        let applied = policies::resolve(&OrderPolicy::default(), None, vec![]);
+        assert_eq!(policies::check_cancel(&order, &applied, Utc::now()), Ok(CancellationOutcome::Return));
+        
+        // Hors délai de retour
+        assert!(matches!(
+            policies::check_cancel(&order, &applied, Utc::now() + Duration::days(20)),
+            Err(PolicyError::ReturnWindowExpired { .. })
+        ));
+        
+        let refuse = OrderPolicy { cancel_after_shipment: PostShipmentCancellation::Refuse, ..Default::default() };
+        let applied = policies::resolve(&refuse, None, vec![]);
+        assert!(matches!(
+            policies::check_cancel(&order, &applied, Utc::now()),
+            Err(PolicyError::CancelAfterShipmentRefused { .. })
+        ));
+    }
+    
+    #[test]
+    fn test_cancellation_refused_while_picking() {
+        let mut order = order(1);
+        order.fulfillment_status = FulfillmentStatus::Picking;
+        
+        let applied = policies::resolve(&OrderPolicy::default(), None, vec![]);
+        assert_eq!(policies::check_cancel(&order, &applied, Utc::now()), Err(PolicyError::PreparationStarted));
+    }
+    
+    #[test]
+    fn test_default_policy_has_override_bounds() {
+        assert!(OrderPolicy::default().validate().is_ok());
+        
+        // Une fenêtre négative fermerait toute annulation: refusée comme pour une surcharge
+        let negative = OrderPolicy { cancel_window_hours: -1, ..OrderPolicy::default() };
+        assert!(negative.validate().is_err());
+        
+        let too_long = OrderPolicy { return_window_days: 366, ..OrderPolicy::default() };
+        assert!(too_long.validate().is_err());
+        assert!(PolicyOverride { return_window_days: Some(366), ..Default::default() }.validate().is_err());
+    }
+}
@@ -0,0 +1,206 @@
+// Tests d'intégration pour le catalogue produits
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common;
+    use ruststripe::models::*;
+    use ruststripe::routes::cart::add_to_cart;
+    use ruststripe::routes::products::{
+        archive_product, create_product, list_all_products, list_products, update_product, CatalogQuery,
+    };
+    use ruststripe::routes::validation::ValidatedJson;
+    use ruststripe::state::AppState;
+    use axum::extract::{Path, Query, State};
+    use axum::http::{StatusCode, Uri};
+    
+    // État sans les produits de démo, pour maîtriser le catalogue
+    fn create_test_state() -> AppState {
+        let state = common::test_state();
+        state.products.clear();
+        state
+    }
+    
+    fn variant(sku: &str, stock: i32) -> ProductVariant {
+        ProductVariant { sku: sku.to_string(), size: None, color: None, stock, price: None }
+    }
+    
+    fn create_request(id: &str, name: &str, price: i64, stock: i32) -> CreateProductRequest {
+        CreateProductRequest {
+            id: id.to_string(),
+            name: name.to_string(),
+            price,
+            stock,
+            description: format!("{} ajustable", name),
+            weight_grams: 120,
+            variants: vec![],
+        }
+    }
+    
+    fn update_request() -> UpdateProductRequest {
+        UpdateProductRequest {
+            name: None,
+            price: None,
+            stock: None,
+            description: None,
+            weight_grams: None,
+            variants: None,
+        }
+    }
+    
+    async fn catalog(state: &AppState, query: &str) -> Vec<String> {
+        let uri: Uri = format!("http://localhost/api/products?{}", query).parse().unwrap();
+        let query: Query<CatalogQuery> = Query::try_from_uri(&uri).unwrap();
+        
+        list_products(State(state.clone()), query).await.unwrap().0
+            .into_iter()
+            .map(|product| product.id)
+            .collect()
+    }
+    
+    #[tokio::test]
+    async fn test_create_product_requires_unique_id_and_skus() {
+        let state = create_test_state();
+        
+        let product = create_product(
+            State(state.clone()),
+            ValidatedJson(create_request("cap_100", "Casquette Rouge", 2500, 10)),
+        ).await.unwrap().0;
+        assert!(!product.archived);
+        assert_eq!(state.products.get("cap_100").unwrap().price, 2500);
+        
+        // Même identifiant, y compris une fois le produit archivé
+        assert!(archive_product(State(state.clone()), Path("cap_100".to_string())).await.is_ok());
+        let err = create_product(
+            State(state.clone()),
+            ValidatedJson(create_request("cap_100", "Autre casquette", 1000, 1)),
+        ).await.unwrap_err();
+        assert_eq!(err.0, StatusCode::CONFLICT);
+        assert_eq!(state.products.get("cap_100").unwrap().name, "Casquette Rouge");
+        
+        // Deux variantes avec le même SKU
+        let mut req = create_request("cap_101", "Casquette Bleue", 2500, 0);
+        req.variants = vec![variant("BLEU-M", 3), variant("BLEU-M", 2)];
+        let err = create_product(State(state.clone()), ValidatedJson(req)).await.unwrap_err();
+        assert_eq!(err.0, StatusCode::BAD_REQUEST);
+        assert!(state.products.get("cap_101").is_none());
+    }
+    
+    #[tokio::test]
+    async fn test_update_product_is_all_or_nothing() {
+        let state = create_test_state();
+        assert!(create_product(
+            State(state.clone()),
+            ValidatedJson(create_request("cap_100", "Casquette Rouge", 2500, 10)),
+        ).await.is_ok());
+        
+        // SKU en double: rien n'est modifié, pas même le prix
+        let mut req = update_request();
+        req.price = Some(3000);
+        req.variants = Some(vec![variant("ROUGE-M", 3), variant("ROUGE-M", 2)]);
+        let err = update_product(State(state.clone()), Path("cap_100".to_string()), ValidatedJson(req))
+            .await.unwrap_err();
+        assert_eq!(err.0, StatusCode::BAD_REQUEST);
+        assert_eq!(state.products.get("cap_100").unwrap().price, 2500);
+        assert!(state.products.get("cap_100").unwrap().variants.is_empty());
+        
+        let mut req = update_request();
+        req.price = Some(3000);
+        req.variants = Some(vec![variant("ROUGE-M", 3), variant("ROUGE-L", 2)]);
+        let product = update_product(State(state.clone()), Path("cap_100".to_string()), ValidatedJson(req))
+            .await.unwrap().0;
+        assert_eq!(product.price, 3000);
+        assert_eq!(product.total_stock(), 5);
+        assert_eq!(product.name, "Casquette Rouge");
+        
+        let err = update_product(State(state.clone()), Path("cap_999".to_string()), ValidatedJson(update_request()))
+            .await.unwrap_err();
+        assert_eq!(err.0, StatusCode::NOT_FOUND);
+    }
+    
+    #[tokio::test]
+    async fn test_archived_product_leaves_catalog_and_cart() {
+        let state = create_test_state();
+        assert!(create_product(
+            State(state.clone()),
+            ValidatedJson(create_request("cap_100", "Casquette Rouge", 2500, 10)),
+        ).await.is_ok());
+        assert!(create_product(
+            State(state.clone()),
+            ValidatedJson(create_request("cap_101", "Casquette Bleue", 2500, 10)),
+        ).await.is_ok());
+        
+        let product = archive_product(State(state.clone()), Path("cap_100".to_string())).await.unwrap().0;
+        assert!(product.archived);
+        
+        // Toujours visible côté admin, plus dans le catalogue public
+        let all: Vec<String> = list_all_products(State(state.clone())).await.unwrap().0
+            .into_iter()
+            .map(|product| product.id)
+            .collect();
+        assert_eq!(all, vec!["cap_100", "cap_101"]);
+        assert_eq!(catalog(&state, "").await, vec!["cap_101"]);
+        
+        let err = add_to_cart(
+            State(state.clone()),
+            ValidatedJson(AddToCartRequest {
+                user_id: "user_1".to_string(),
+                product_id: "cap_100".to_string(),
+                variant_sku: None,
+                quantity: 1,
+            }),
+        ).await.unwrap_err();
+        assert_eq!(err.0, StatusCode::BAD_REQUEST);
+        assert!(state.carts.get("user_1").is_none());
+        
+        let err = archive_product(State(state.clone()), Path("cap_999".to_string())).await.unwrap_err();
+        assert_eq!(err.0, StatusCode::NOT_FOUND);
+    }
+    
+    #[tokio::test]
+    async fn test_catalog_search_and_filters() {
+        let state = create_test_state();
+        for req in [
+            create_request("cap_100", "Casquette Rouge", 2500, 10),
+            create_request("cap_101", "Casquette Bleue", 3500, 0),
+            create_request("bob_100", "Bob Rouge", 1500, 5),
+        ] {
+            assert!(create_product(State(state.clone()), ValidatedJson(req)).await.is_ok());
+        }
+        
+        // Recherche insensible à la casse, dans le nom et la description
+        assert_eq!(catalog(&state, "search=rouge").await, vec!["bob_100", "cap_100"]);
+        assert_eq!(catalog(&state, "search=AJUSTABLE").await, vec!["bob_100", "cap_100", "cap_101"]);
+        
+        // Bornes de prix incluses
+        assert_eq!(catalog(&state, "min_price=2500").await, vec!["cap_100", "cap_101"]);
+        assert_eq!(catalog(&state, "max_price=2500").await, vec!["bob_100", "cap_100"]);
+        assert_eq!(catalog(&state, "min_price=2000&max_price=3000").await, vec!["cap_100"]);
+        
+        assert_eq!(catalog(&state, "in_stock=true").await, vec!["bob_100", "cap_100"]);
+        assert_eq!(catalog(&state, "search=casquette&in_stock=true").await, vec!["cap_100"]);
+    }
+    
+    #[tokio::test]
+    async fn test_catalog_price_filter_uses_variant_prices() {
+        let state = create_test_state();
+        // Produit à 25€, taille XL à 32€
+        let mut req = create_request("cap_100", "Casquette Rouge", 2500, 0);
+        req.variants = vec![
+            variant("ROUGE-M", 3),
+            ProductVariant { price: Some(3200), ..variant("ROUGE-XL", 1) },
+        ];
+        assert!(create_product(State(state.clone()), ValidatedJson(req)).await.is_ok());
+        // Variantes toutes à 40€: le prix de base n'est jamais payé
+        let mut req = create_request("cap_101", "Casquette Brodée", 1000, 0);
+        req.variants = vec![ProductVariant { price: Some(4000), ..variant("BRODE-M", 2) }];
+        assert!(create_product(State(state.clone()), ValidatedJson(req)).await.is_ok());
+        
+        assert_eq!(catalog(&state, "min_price=3000").await, vec!["cap_100", "cap_101"]);
+        assert_eq!(catalog(&state, "max_price=2000").await, Vec::<String>::new());
+        assert_eq!(catalog(&state, "min_price=3000&max_price=3500").await, vec!["cap_100"]);
+        // Aucune variante entre 26€ et 31€
+        assert_eq!(catalog(&state, "min_price=2600&max_price=3100").await, Vec::<String>::new());
+    }
+}
@@ -0,0 +1,109 @@
+// Tests unitaires pour le calcul des remboursements
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common;
+    use ruststripe::models::*;
+    use ruststripe::services::refunds::{self, RefundError};
+    use chrono::Utc;
+    
+    // Commande payée: 2 × 25€ + 1 × 30€ + 4,90€ de port = 84,90€
+    fn completed_order() -> Order {
+        Order {
+            items: vec![
+                OrderItem { product_id: "cap_001".to_string(), variant_sku: None, product_name: "Rouge".to_string(), quantity: 2, price: 2500 },
+                OrderItem { product_id: "cap_002".to_string(), variant_sku: None, product_name: "Noire".to_string(), quantity: 1, price: 3000 },
+            ],
+            subtotal: 8000,
+            total: 8490,
+            shipping: None,
+            stock_reserved: false,
+            ..common::order()
+        }
+    }
+    
+    fn refund_line(product_id: &str, quantity: i32) -> RefundItem {
+        RefundItem { product_id: product_id.to_string(), variant_sku: None, quantity }
+    }
+    
+    fn record(order: &mut Order, plan: refunds::RefundPlan) {
+        order.refunds.push(OrderRefund {
+            id: "r".to_string(),
+            stripe_refund_id: "re_1".to_string(),
+            amount: plan.amount,
+            items: plan.items,
+            restocked: false,
+            reason: None,
+            created_at: Utc::now(),
+        });
+    }
+    
+    #[test]
+    fn test_full_refund_includes_shipping() {
+        let order = completed_order();
+        let plan = refunds::plan_refund(&order, None).unwrap();
+        
+        assert_eq!(plan.amount, 8490);
+        assert_eq!(plan.items.len(), 2);
+        assert!(plan.fully_refunded);
+    }
+    
+    #[test]
+    fn test_partial_refund_by_item() {
+        let mut order = completed_order();
+        let plan = refunds::plan_refund(&order, Some(&[refund_line("cap_001", 1)])).unwrap();
+        
+        assert_eq!(plan.amount, 2500);
+        assert!(!plan.fully_refunded);
+        
+        // Le solde restant tient compte du premier remboursement
+        record(&mut order, plan);
+        order.status = OrderStatus::PartiallyRefunded;
+        let rest = refunds::plan_refund(&order, None).unwrap();
+        assert_eq!(rest.amount, 5990);
+        assert_eq!(rest.items, vec![refund_line("cap_001", 1), refund_line("cap_002", 1)]);
+    }
+    
+    #[test]
+    fn test_refund_quantity_cannot_exceed_ordered() {
+        let mut order = completed_order();
+        let plan = refunds::plan_refund(&order, Some(&[refund_line("cap_001", 2)])).unwrap();
+        record(&mut order, plan);
+        order.status = OrderStatus::PartiallyRefunded;
+        
+        let err = refunds::plan_refund(&order, Some(&[refund_line("cap_001", 1)])).unwrap_err();
+        assert!(matches!(err, RefundError::QuantityExceeded { remaining: 0, .. }));
+    }
+    
+    #[test]
+    fn test_unpaid_order_cannot_be_refunded() {
+        let mut order = completed_order();
+        order.status = OrderStatus::Processing;
+        
+        let err = refunds::plan_refund(&order, None).unwrap_err();
+        assert_eq!(err, RefundError::NotRefundable(OrderStatus::Processing));
+    }
+    
+    #[test]
+    fn test_refund_idempotency_key_is_stable_per_refund() {
+        let mut order = completed_order();
+        let plan = refunds::plan_refund(&order, Some(&[refund_line("cap_001", 1)])).unwrap();
+        
+        // Même remboursement rejoué: même clé
+        let replayed = refunds::plan_refund(&order, Some(&[refund_line("cap_001", 1)])).unwrap();
+        assert_eq!(plan.idempotency_key(&order.id), replayed.idempotency_key(&order.id));
+        assert_ne!(
+            plan.idempotency_key(&order.id),
+            refunds::plan_refund(&order, Some(&[refund_line("cap_002", 1)])).unwrap().idempotency_key(&order.id)
+        );
+        
+        // Le même article remboursé une seconde fois est un autre remboursement
+        let key = plan.idempotency_key(&order.id);
+        record(&mut order, plan);
+        order.status = OrderStatus::PartiallyRefunded;
+        let second = refunds::plan_refund(&order, Some(&[refund_line("cap_001", 1)])).unwrap();
+        assert_ne!(second.idempotency_key(&order.id), key);
+    }
+}
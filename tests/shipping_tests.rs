@@ -0,0 +1,53 @@
+// Tests unitaires pour le calcul des frais de port
+
+#[cfg(test)]
+mod tests {
+    use ruststripe::models::{ShippingMethod, ShippingRate, ShippingZone};
+    use ruststripe::services::shipping;
+    
+    fn standard_method() -> ShippingMethod {
+        ShippingMethod {
+            id: "standard".to_string(),
+            name: "Standard".to_string(),
+            rates: vec![
+                ShippingRate { zone: ShippingZone::France, max_weight_grams: 1000, price: 490 },
+                ShippingRate { zone: ShippingZone::France, max_weight_grams: 5000, price: 790 },
+                ShippingRate { zone: ShippingZone::Europe, max_weight_grams: 1000, price: 990 },
+            ],
+            free_shipping_threshold: Some(5000),
+        }
+    }
+    
+    #[test]
+    fn test_zone_for_country() {
+        assert_eq!(shipping::zone_for_country("FR"), ShippingZone::France);
+        assert_eq!(shipping::zone_for_country("de"), ShippingZone::Europe);
+        assert_eq!(shipping::zone_for_country("US"), ShippingZone::International);
+    }
+    
+    #[test]
+    fn test_quote_uses_smallest_weight_bracket() {
+        let method = standard_method();
+        
+        assert_eq!(shipping::quote(&method, ShippingZone::France, 300, 2500), Some(490));
+        assert_eq!(shipping::quote(&method, ShippingZone::France, 1200, 2500), Some(790));
+    }
+    
+    #[test]
+    fn test_quote_unserved_zone_or_weight() {
+        let method = standard_method();
+        
+        // Pas de tarif international
+        assert_eq!(shipping::quote(&method, ShippingZone::International, 300, 2500), None);
+        // Trop lourd pour la zone Europe
+        assert_eq!(shipping::quote(&method, ShippingZone::Europe, 2000, 2500), None);
+    }
+    
+    #[test]
+    fn test_free_shipping_threshold() {
+        let method = standard_method();
+        
+        assert_eq!(shipping::quote(&method, ShippingZone::France, 300, 4999), Some(490));
+        assert_eq!(shipping::quote(&method, ShippingZone::France, 300, 5000), Some(0));
+    }
+}
@@ -0,0 +1,550 @@
+// Tests unitaires pour les règles de gestion des abonnements
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common;
+    use ruststripe::models::*;
+    use ruststripe::services::subscriptions::{self, SubscriptionError, SubscriptionSnapshot};
+    use ruststripe::routes::webhooks;
+    use ruststripe::state::AppState;
+    use axum::{body::Bytes, extract::State};
+    use chrono::{DateTime, Duration, Utc};
+    
+    fn subscription(plan_id: &str) -> UserSubscription {
+        UserSubscription { plan_id: plan_id.to_string(), ..common::subscription() }
+    }
+    
+    #[test]
+    fn test_plan_change_is_recorded_in_history() {
+        let mut sub = subscription("plan_normal");
+        assert_eq!(subscriptions::check_plan_change(&sub, "plan_complet"), Ok(()));
+        
+        let now = Utc::now();
+        subscriptions::apply_plan_change(&mut sub, "plan_complet", ProrationBehavior::ImmediateInvoice, now);
+        subscriptions::apply_plan_change(&mut sub, "plan_supplement", ProrationBehavior::None, now);
+        
+        assert_eq!(sub.plan_id, "plan_supplement");
+        assert_eq!(sub.plan_history.len(), 2);
+        assert_eq!(sub.plan_history[0].from_plan_id, "plan_normal");
+        assert_eq!(sub.plan_history[0].to_plan_id, "plan_complet");
+        assert_eq!(sub.plan_history[1].from_plan_id, "plan_complet");
+        assert_eq!(sub.plan_history[1].proration, ProrationBehavior::None);
+    }
+    
+    #[test]
+    fn test_plan_change_refused() {
+        let mut sub = subscription("plan_normal");
+        assert_eq!(
+            subscriptions::check_plan_change(&sub, "plan_normal"),
+            Err(SubscriptionError::SamePlan("plan_normal".to_string()))
+        );
+        
+        sub.cancel_at_period_end = true;
+        assert_eq!(
+            subscriptions::check_plan_change(&sub, "plan_complet"),
+            Err(SubscriptionError::CancellationScheduled)
+        );
+        
+        sub.status = SubscriptionStatus::PastDue;
+        assert_eq!(
+            subscriptions::check_plan_change(&sub, "plan_complet"),
+            Err(SubscriptionError::NotActive(SubscriptionStatus::PastDue))
+        );
+    }
+    
+    #[test]
+    fn test_proration_defaults_to_next_invoice() {
+        let req: ChangePlanRequest = serde_json::from_str(r#"{"plan_id": "plan_complet"}"#).unwrap();
+        assert_eq!(req.proration, ProrationBehavior::NextInvoice);
+        
+        let req: ChangePlanRequest =
+            serde_json::from_str(r#"{"plan_id": "plan_complet", "proration": "immediate_invoice"}"#).unwrap();
+        assert_eq!(req.proration, ProrationBehavior::ImmediateInvoice);
+    }
+    
+    #[test]
+    fn test_one_trial_per_user() {
+        let plan = SubscriptionPlan {
+            id: "plan_normal".to_string(),
+            name: "Normal".to_string(),
+            price: 1000,
+            description: "Abonnement journal formule normale".to_string(),
+            trial_days: Some(14),
+            prices: Default::default(),
+            metered: None,
+            entitlements: vec![],
+        };
+        assert_eq!(subscriptions::trial_days_for(&plan, &[]), Some(14));
+        
+        // Un abonnement sans essai ne consomme pas l'essai
+        let mut previous = subscription("plan_complet");
+        previous.status = SubscriptionStatus::Cancelled;
+        assert_eq!(subscriptions::trial_days_for(&plan, &[previous.clone()]), Some(14));
+        
+        previous.trial_end = Some(Utc::now() - Duration::days(30));
+        assert_eq!(subscriptions::trial_days_for(&plan, &[previous]), None);
+        
+        let no_trial = SubscriptionPlan { trial_days: None, ..plan };
+        assert_eq!(subscriptions::trial_days_for(&no_trial, &[]), None);
+    }
+    
+    fn create_test_state() -> AppState {
+        common::test_state()
+    }
+    
+    #[test]
+    fn test_trial_is_reserved_once_per_user() {
+        let state = create_test_state();
+        
+        // Deux créations simultanées: seule la première obtient l'essai
+        assert!(state.reserve_trial("user_1"));
+        assert!(!state.reserve_trial("user_1"));
+        assert!(state.reserve_trial("user_2"));
+        
+        // Échec de la création Stripe: l'essai redevient disponible
+        state.release_trial("user_1");
+        assert!(state.reserve_trial("user_1"));
+    }
+    
+    #[test]
+    fn test_cancel_at_period_end_keeps_access_and_can_be_resumed() {
+        let mut sub = subscription("plan_normal");
+        assert_eq!(subscriptions::check_cancel_at_period_end(&sub), Ok(()));
+        assert_eq!(subscriptions::check_resume(&sub, Utc::now()), Err(SubscriptionError::NotScheduled));
+        
+        sub.cancel_at_period_end = true;
+        assert!(matches!(
+            subscriptions::check_cancel_at_period_end(&sub),
+            Err(SubscriptionError::AlreadyScheduled(_))
+        ));
+        assert_eq!(subscriptions::check_resume(&sub, Utc::now()), Ok(()));
+        
+        // Trop tard une fois la période payée écoulée
+        let after_end = sub.current_period_end + Duration::minutes(1);
+        assert_eq!(subscriptions::check_resume(&sub, after_end), Err(SubscriptionError::PeriodEnded));
+        
+        sub.status = SubscriptionStatus::Cancelled;
+        assert_eq!(subscriptions::check_resume(&sub, Utc::now()), Err(SubscriptionError::AlreadyCancelled));
+    }
+    
+    #[test]
+    fn test_prorated_refund_of_unused_time() {
+        let start = Utc::now() - Duration::days(10);
+        let end = start + Duration::days(30);
+        
+        // 20 jours sur 30 non consommés
+        assert_eq!(subscriptions::prorated_refund(3000, start, end, start + Duration::days(10)), 2000);
+        // Arrondi au centime inférieur
+        assert_eq!(subscriptions::prorated_refund(1000, start, end, start + Duration::days(10)), 666);
+        assert_eq!(subscriptions::prorated_refund(1000, start, end, start), 1000);
+        assert_eq!(subscriptions::prorated_refund(1000, start, end, end), 0);
+        // Essai gratuit: rien n'a été payé
+        assert_eq!(subscriptions::prorated_refund(0, start, end, start + Duration::days(1)), 0);
+    }
+    
+    fn invoice_line(amount: i64, period_start: i64, proration: bool, metered: bool) -> stripe::InvoiceLineItem {
+        let usage_type = if metered { stripe::RecurringUsageType::Metered } else { stripe::RecurringUsageType::Licensed };
+        stripe::InvoiceLineItem {
+            amount,
+            proration,
+            type_: stripe::InvoiceLineItemType::Subscription,
+            period: Some(stripe::Period { start: Some(period_start), end: None }),
+            price: Some(stripe::Price {
+                recurring: Some(stripe::Recurring { usage_type, ..Default::default() }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+    
+    #[test]
+    fn test_refund_is_based_on_the_period_line_item() {
+        let period_start = Utc::now().timestamp();
+        let invoice = |lines: Vec<stripe::InvoiceLineItem>, amount_paid: i64| stripe::Invoice {
+            amount_paid: Some(amount_paid),
+            lines: Some(stripe::List { data: lines, ..Default::default() }),
+            ..Default::default()
+        };
+        
+        // Forfait de la période et consommation à l'usage de la période précédente
+        let renewal = invoice(vec![invoice_line(1000, period_start, false, false), invoice_line(450, period_start - 86400 * 30, false, true)], 1450);
+        assert_eq!(subscriptions::period_line_amount(&renewal, period_start), Some(1000));
+        
+        // Facture de changement de formule: que des ajustements au prorata
+        let proration = invoice(vec![invoice_line(-300, period_start, true, false), invoice_line(500, period_start, true, false)], 200);
+        assert_eq!(subscriptions::period_line_amount(&proration, period_start), None);
+        
+        // Jamais plus que le montant payé (remise, crédit client)
+        let discounted = invoice(vec![invoice_line(1000, period_start, false, false)], 800);
+        assert_eq!(subscriptions::period_line_amount(&discounted, period_start), Some(800));
+    }
+    
+    #[test]
+    fn test_immediate_cancellation_overrides_scheduled_one() {
+        let mut sub = subscription("plan_normal");
+        sub.cancel_at_period_end = true;
+        assert_eq!(subscriptions::check_cancel_now(&sub), Ok(()));
+        
+        sub.status = SubscriptionStatus::Cancelled;
+        assert_eq!(subscriptions::check_cancel_now(&sub), Err(SubscriptionError::AlreadyCancelled));
+    }
+    
+    #[test]
+    fn test_cancel_mode_defaults_to_period_end() {
+        assert_eq!(SubscriptionCancelMode::default(), SubscriptionCancelMode::AtPeriodEnd);
+        let mode: SubscriptionCancelMode = serde_json::from_str(r#""immediate_with_refund""#).unwrap();
+        assert_eq!(mode, SubscriptionCancelMode::ImmediateWithRefund);
+    }
+    
+    #[test]
+    fn test_stripe_status_mapping() {
+        use stripe::SubscriptionStatus as Remote;
+        
+        assert_eq!(subscriptions::status_from_stripe(Remote::Active, false), SubscriptionStatus::Active);
+        assert_eq!(subscriptions::status_from_stripe(Remote::Active, true), SubscriptionStatus::Paused);
+        assert_eq!(subscriptions::status_from_stripe(Remote::Trialing, false), SubscriptionStatus::Trialing);
+        assert_eq!(subscriptions::status_from_stripe(Remote::Canceled, false), SubscriptionStatus::Cancelled);
+        assert_eq!(subscriptions::status_from_stripe(Remote::IncompleteExpired, false), SubscriptionStatus::Incomplete);
+        assert_eq!(subscriptions::status_from_stripe(Remote::Unpaid, false), SubscriptionStatus::Unpaid);
+    }
+    
+    #[test]
+    fn test_snapshot_from_stripe_is_authoritative() {
+        let mut sub = subscription("plan_normal");
+        let start = Utc::now() - Duration::days(3);
+        let paid_invoice = SubscriptionInvoiceSummary {
+            stripe_invoice_id: "in_1".to_string(),
+            status: Some("paid".to_string()),
+            amount_due: 1000,
+            amount_paid: 1000,
+            hosted_invoice_url: None,
+        };
+        let mut snapshot = SubscriptionSnapshot {
+            status: SubscriptionStatus::PastDue,
+            stripe_status: "past_due".to_string(),
+            current_period_start: start,
+            current_period_end: start + Duration::days(31),
+            cancel_at_period_end: true,
+            trial_end: None,
+            latest_invoice: Some(paid_invoice.clone()),
+            interval: None,
+            collection_paused: false,
+            pause_resumes_at: None,
+            metered_item_id: None,
+        };
+        
+        let now = Utc::now();
+        subscriptions::apply_snapshot(&mut sub, &snapshot, now);
+        assert_eq!(sub.status, SubscriptionStatus::PastDue);
+        assert_eq!(sub.current_period_start, start);
+        assert_eq!(sub.current_period_end, start + Duration::days(31));
+        assert!(sub.cancel_at_period_end);
+        assert_eq!(sub.synced_at, Some(now));
+        
+        // Une facture référencée sans détail ne remplace pas le résumé connu
+        snapshot.latest_invoice = Some(SubscriptionInvoiceSummary { status: None, amount_paid: 0, ..paid_invoice.clone() });
+        subscriptions::apply_snapshot(&mut sub, &snapshot, now);
+        assert_eq!(sub.latest_invoice, Some(paid_invoice));
+    }
+    
+    fn snapshot(status: SubscriptionStatus) -> SubscriptionSnapshot {
+        let start = Utc::now() - Duration::days(3);
+        SubscriptionSnapshot {
+            status,
+            stripe_status: "past_due".to_string(),
+            current_period_start: start,
+            current_period_end: start + Duration::days(31),
+            cancel_at_period_end: false,
+            trial_end: None,
+            latest_invoice: None,
+            interval: None,
+            collection_paused: false,
+            pause_resumes_at: None,
+            metered_item_id: None,
+        }
+    }
+    
+    #[test]
+    fn test_final_dunning_action_survives_stripe_sync_until_recovery() {
+        let now = Utc::now();
+        let mut sub = subscription("plan_normal");
+        sub.status = SubscriptionStatus::Unpaid;
+        sub.dunning = Some(DunningState {
+            stripe_invoice_id: "in_1".to_string(),
+            attempts: 3,
+            started_at: now - Duration::days(5),
+            next_retry_at: None,
+            grace_until: now - Duration::days(2),
+            technical_failures: 0,
+        });
+        
+        // Stripe n'a pas d'équivalent: il signale encore la facture impayée
+        subscriptions::apply_snapshot(&mut sub, &snapshot(SubscriptionStatus::PastDue), now);
+        assert_eq!(sub.status, SubscriptionStatus::Unpaid);
+        
+        subscriptions::apply_snapshot(&mut sub, &snapshot(SubscriptionStatus::Active), now);
+        assert_eq!(sub.status, SubscriptionStatus::Active);
+    }
+    
+    #[test]
+    fn test_events_older_than_last_sync_are_stale() {
+        let now = Utc::now();
+        let mut sub = subscription("plan_normal");
+        assert!(!subscriptions::is_stale_event(&sub, now - Duration::hours(1)));
+        
+        sub.synced_at = Some(now);
+        assert!(subscriptions::is_stale_event(&sub, now - Duration::seconds(5)));
+        assert!(!subscriptions::is_stale_event(&sub, now));
+        assert!(!subscriptions::is_stale_event(&sub, now + Duration::seconds(5)));
+    }
+    
+    // Événement `customer.subscription.updated` décrivant l'abonnement encore actif
+    fn active_subscription_event(period_end: DateTime<Utc>, created: DateTime<Utc>) -> serde_json::Value {
+        serde_json::json!({
+            "id": "evt_sub_updated",
+            "type": "customer.subscription.updated",
+            "created": created.timestamp(),
+            "data": { "object": {
+                "id": "sub_123",
+                "object": "subscription",
+                "automatic_tax": { "enabled": false },
+                "billing_cycle_anchor": (period_end - Duration::days(30)).timestamp(),
+                "cancel_at_period_end": false,
+                "created": (Utc::now() - Duration::days(40)).timestamp(),
+                "currency": "eur",
+                "current_period_start": (period_end - Duration::days(30)).timestamp(),
+                "current_period_end": period_end.timestamp(),
+                "customer": "cus_123",
+                "items": { "object": "list", "data": [], "has_more": false, "url": "/v1/subscription_items" },
+                "livemode": false,
+                "metadata": {},
+                "start_date": (Utc::now() - Duration::days(40)).timestamp(),
+                "status": "active"
+            }}
+        })
+    }
+    
+    #[tokio::test]
+    async fn test_delayed_update_does_not_revive_cancelled_subscription() {
+        let state = common::test_state();
+        let period_end = Utc::now() + Duration::days(20);
+        let cancelled_at = Utc::now();
+        // État laissé par une annulation immédiate
+        state.subscriptions.insert("sub_local_1".to_string(), UserSubscription {
+            status: SubscriptionStatus::Cancelled,
+            current_period_end: cancelled_at,
+            synced_at: Some(cancelled_at),
+            ..common::subscription()
+        });
+        
+        // Événement émis avant l'annulation, livré après
+        let event = active_subscription_event(period_end, cancelled_at - Duration::minutes(2));
+        webhooks::stripe_webhook(State(state.clone()), Bytes::from(event.to_string())).await.unwrap();
+        let sub = state.subscriptions.get("sub_local_1").unwrap().clone();
+        assert_eq!(sub.status, SubscriptionStatus::Cancelled);
+        assert_eq!(sub.current_period_end, cancelled_at);
+        
+        // Même émis dans la seconde de l'annulation, il ne rend pas l'accès
+        let event = active_subscription_event(period_end, cancelled_at);
+        webhooks::stripe_webhook(State(state.clone()), Bytes::from(event.to_string())).await.unwrap();
+        let sub = state.subscriptions.get("sub_local_1").unwrap().clone();
+        assert_eq!(sub.status, SubscriptionStatus::Cancelled);
+        assert_eq!(sub.current_period_end, cancelled_at);
+    }
+    
+    fn plan_with_intervals() -> SubscriptionPlan {
+        SubscriptionPlan {
+            id: "plan_normal".to_string(),
+            name: "Normal".to_string(),
+            price: 1000,
+            description: "Abonnement journal formule normale".to_string(),
+            trial_days: None,
+            prices: [(BillingInterval::Monthly, 1000), (BillingInterval::Annual, 10200)].into_iter().collect(),
+            metered: None,
+            entitlements: vec![],
+        }
+    }
+    
+    #[test]
+    fn test_plan_price_by_interval() {
+        let plan = plan_with_intervals();
+        assert_eq!(subscriptions::plan_price(&plan, BillingInterval::Annual), Ok(10200));
+        assert!(matches!(
+            subscriptions::plan_price(&plan, BillingInterval::Quarterly),
+            Err(SubscriptionError::IntervalNotOffered { interval: BillingInterval::Quarterly, .. })
+        ));
+        
+        // Sans grille: mensuel au prix de base uniquement
+        let basic = SubscriptionPlan { prices: Default::default(), ..plan };
+        assert_eq!(subscriptions::plan_price(&basic, BillingInterval::Monthly), Ok(1000));
+        assert!(subscriptions::plan_price(&basic, BillingInterval::Annual).is_err());
+    }
+    
+    #[test]
+    fn test_metered_option_is_opt_in() {
+        let plan = SubscriptionPlan {
+            metered: Some(MeteredPrice { unit_amount: 50, unit_label: "article".to_string() }),
+            ..plan_with_intervals()
+        };
+        assert_eq!(subscriptions::metered_option(&plan, false), Ok(None));
+        assert_eq!(subscriptions::metered_option(&plan, true), Ok(plan.metered.clone()));
+        
+        let plan = SubscriptionPlan { metered: None, ..plan };
+        assert_eq!(subscriptions::metered_option(&plan, false), Ok(None));
+        assert_eq!(
+            subscriptions::metered_option(&plan, true),
+            Err(SubscriptionError::MeteredNotOffered("plan_normal".to_string()))
+        );
+    }
+    
+    #[test]
+    fn test_interval_switch_applies_at_renewal() {
+        let plan = plan_with_intervals();
+        let mut sub = subscription("plan_normal");
+        
+        assert_eq!(subscriptions::check_interval_change(&sub, &plan, BillingInterval::Annual), Ok(10200));
+        assert_eq!(
+            subscriptions::check_interval_change(&sub, &plan, BillingInterval::Monthly),
+            Err(SubscriptionError::SameInterval(BillingInterval::Monthly))
+        );
+        
+        sub.scheduled_interval = Some(BillingInterval::Annual);
+        assert_eq!(
+            subscriptions::check_plan_change(&sub, "plan_complet"),
+            Err(SubscriptionError::IntervalChangeScheduled(BillingInterval::Annual))
+        );
+        
+        // Renouvellement: Stripe facture désormais à l'année
+        let snapshot = SubscriptionSnapshot {
+            status: SubscriptionStatus::Active,
+            stripe_status: "active".to_string(),
+            current_period_start: sub.current_period_end,
+            current_period_end: sub.current_period_end + Duration::days(365),
+            cancel_at_period_end: false,
+            trial_end: None,
+            latest_invoice: None,
+            interval: Some(BillingInterval::Annual),
+            collection_paused: false,
+            pause_resumes_at: None,
+            metered_item_id: None,
+        };
+        subscriptions::apply_snapshot(&mut sub, &snapshot, Utc::now());
+        assert_eq!(sub.interval, BillingInterval::Annual);
+        assert_eq!(sub.scheduled_interval, None);
+    }
+    
+    #[test]
+    fn test_scheduled_interval_change_blocks_pause_and_can_be_abandoned() {
+        let now = Utc::now();
+        let mut sub = subscription("plan_normal");
+        assert_eq!(
+            subscriptions::check_cancel_interval_change(&sub),
+            Err(SubscriptionError::NoIntervalChangeScheduled)
+        );
+        
+        sub.scheduled_interval = Some(BillingInterval::Annual);
+        sub.interval_schedule_id = Some("sub_sched_1".to_string());
+        assert_eq!(
+            subscriptions::check_pause(&sub, None, now),
+            Err(SubscriptionError::IntervalChangeScheduled(BillingInterval::Annual))
+        );
+        assert_eq!(subscriptions::check_cancel_interval_change(&sub), Ok("sub_sched_1".to_string()));
+        // L'annulation en fin de période libère d'abord le calendrier
+        assert_eq!(subscriptions::check_cancel_at_period_end(&sub), Ok(()));
+        
+        // Calendrier inconnu: impossible de le libérer
+        sub.interval_schedule_id = None;
+        assert_eq!(subscriptions::check_cancel_at_period_end(&sub), Err(SubscriptionError::IntervalScheduleUnknown));
+        assert_eq!(subscriptions::check_cancel_interval_change(&sub), Err(SubscriptionError::IntervalScheduleUnknown));
+    }
+    
+    #[test]
+    fn test_pause_requires_active_subscription_and_future_resume_date() {
+        let now = Utc::now();
+        let mut sub = subscription("plan_normal");
+        
+        assert_eq!(subscriptions::check_pause(&sub, Some(now + Duration::days(21)), now), Ok(()));
+        assert_eq!(subscriptions::check_pause(&sub, None, now), Ok(()));
+        assert_eq!(
+            subscriptions::check_pause(&sub, Some(now - Duration::days(1)), now),
+            Err(SubscriptionError::ResumeDateInPast)
+        );
+        assert_eq!(subscriptions::check_unpause(&sub), Err(SubscriptionError::NotPaused));
+        
+        sub.status = SubscriptionStatus::Paused;
+        assert_eq!(subscriptions::check_pause(&sub, None, now), Err(SubscriptionError::AlreadyPaused));
+        assert_eq!(subscriptions::check_unpause(&sub), Ok(()));
+    }
+    
+    #[test]
+    fn test_dunning_pause_is_lifted_only_by_payment() {
+        let now = Utc::now();
+        let mut sub = subscription("plan_normal");
+        sub.status = SubscriptionStatus::Paused;
+        sub.dunning = Some(DunningState {
+            stripe_invoice_id: "in_1".to_string(),
+            attempts: 3,
+            started_at: now - Duration::days(5),
+            next_retry_at: None,
+            grace_until: now - Duration::days(1),
+            technical_failures: 0,
+        });
+        
+        assert_eq!(
+            subscriptions::check_unpause(&sub),
+            Err(SubscriptionError::UnpaidInvoice("in_1".to_string()))
+        );
+        
+        // Facture réglée: la relance est close, la reprise redevient possible
+        sub.dunning = None;
+        assert_eq!(subscriptions::check_unpause(&sub), Ok(()));
+    }
+    
+    #[test]
+    fn test_paused_subscription_has_no_access() {
+        let now = Utc::now();
+        let mut sub = subscription("plan_normal");
+        assert!(subscriptions::has_access(&sub, now));
+        
+        sub.status = SubscriptionStatus::Paused;
+        assert!(!subscriptions::has_access(&sub, now));
+        
+        sub.status = SubscriptionStatus::PastDue;
+        assert!(!subscriptions::has_access(&sub, now)); // Pas de relance en cours: pas de période de grâce
+    }
+    
+    #[test]
+    fn test_pause_follows_stripe_pause_collection() {
+        let now = Utc::now();
+        let resumes_at = now + Duration::days(14);
+        let mut sub = subscription("plan_normal");
+        let mut snapshot = SubscriptionSnapshot {
+            status: SubscriptionStatus::Paused,
+            stripe_status: "active".to_string(),
+            current_period_start: sub.current_period_start,
+            current_period_end: sub.current_period_end,
+            cancel_at_period_end: false,
+            trial_end: None,
+            latest_invoice: None,
+            interval: None,
+            collection_paused: true,
+            pause_resumes_at: Some(resumes_at),
+            metered_item_id: None,
+        };
+        
+        // Pause posée depuis le dashboard Stripe
+        subscriptions::apply_snapshot(&mut sub, &snapshot, now);
+        assert_eq!(sub.status, SubscriptionStatus::Paused);
+        assert_eq!(sub.pause, Some(SubscriptionPause { paused_at: now, resumes_at: Some(resumes_at) }));
+        
+        // Reprise automatique à la date prévue
+        snapshot.status = SubscriptionStatus::Active;
+        snapshot.collection_paused = false;
+        snapshot.pause_resumes_at = None;
+        subscriptions::apply_snapshot(&mut sub, &snapshot, resumes_at);
+        assert_eq!(sub.status, SubscriptionStatus::Active);
+        assert_eq!(sub.pause, None);
+    }
+}
@@ -0,0 +1,131 @@
+// Tests unitaires pour la facturation à l'usage
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common;
+    use ruststripe::jobs;
+    use ruststripe::models::*;
+    use ruststripe::services::usage::{self, UsageError};
+    use chrono::{DateTime, Duration, Utc};
+    
+    // Abonnement avec l'option « article » à 0,50€, période commencée il y a 10 jours
+    fn subscription() -> UserSubscription {
+        UserSubscription {
+            metered: Some(MeteredItem {
+                stripe_item_id: "si_metered".to_string(),
+                unit_amount: 50,
+                unit_label: "article".to_string(),
+            }),
+            ..common::subscription()
+        }
+    }
+    
+    fn event(id: &str, quantity: i64, occurred_at: DateTime<Utc>) -> UsageEvent {
+        UsageEvent {
+            id: id.to_string(),
+            quantity,
+            occurred_at,
+            received_at: occurred_at,
+            report_status: UsageReportStatus::Pending,
+            stripe_usage_record_id: None,
+        }
+    }
+    
+    #[test]
+    fn test_usage_only_on_metered_subscription_with_access() {
+        let now = Utc::now();
+        let mut sub = subscription();
+        assert_eq!(usage::check_usage(&sub, None, now), Ok(now));
+        
+        // Pas de consommation avant la période en cours ni dans le futur
+        let before_period = sub.current_period_start - Duration::hours(1);
+        assert!(matches!(usage::check_usage(&sub, Some(before_period), now), Err(UsageError::OutsidePeriod(_))));
+        assert!(matches!(usage::check_usage(&sub, Some(now + Duration::hours(1)), now), Err(UsageError::OutsidePeriod(_))));
+        
+        sub.status = SubscriptionStatus::Paused;
+        assert_eq!(usage::check_usage(&sub, None, now), Err(UsageError::NoAccess(SubscriptionStatus::Paused)));
+        
+        sub.status = SubscriptionStatus::Active;
+        sub.metered = None;
+        assert_eq!(usage::check_usage(&sub, None, now), Err(UsageError::NotMetered));
+    }
+    
+    #[test]
+    fn test_replayed_event_is_counted_once() {
+        let now = Utc::now();
+        let mut events = vec![];
+        
+        let (_, duplicate) = usage::record_event(&mut events, event("evt_1", 2, now));
+        assert!(!duplicate);
+        
+        // Même identifiant, autre quantité: l'événement d'origine est conservé
+        let (kept, duplicate) = usage::record_event(&mut events, event("evt_1", 5, now));
+        assert!(duplicate);
+        assert_eq!(kept.quantity, 2);
+        assert_eq!(events.len(), 1);
+    }
+    
+    #[test]
+    fn test_pending_events_are_aggregated_in_one_batch() {
+        let now = Utc::now();
+        let mut events = vec![event("evt_1", 2, now - Duration::hours(2)), event("evt_2", 1, now)];
+        
+        let batch = usage::take_pending(&mut events).unwrap();
+        assert_eq!(batch.quantity, 3);
+        assert_eq!(batch.timestamp, now);
+        assert_eq!(batch.idempotency_key("sub_local_1"), "usage-sub_local_1-evt_1-2");
+        
+        // Échec de l'envoi: le même lot est renvoyé, sans la consommation arrivée entre-temps
+        usage::record_event(&mut events, event("evt_3", 4, now));
+        let retry = usage::take_pending(&mut events).unwrap();
+        assert_eq!(retry, batch);
+        assert_eq!(retry.idempotency_key("sub_local_1"), batch.idempotency_key("sub_local_1"));
+        
+        // Lot remonté avec l'enregistrement Stripe, puis au tour de la nouvelle consommation
+        usage::finish_batch(&mut events, &retry, "mbur_1");
+        assert_eq!(events[0].report_status, UsageReportStatus::Reported);
+        assert_eq!(events[0].stripe_usage_record_id.as_deref(), Some("mbur_1"));
+        
+        let next = usage::take_pending(&mut events).unwrap();
+        assert_eq!(next.event_ids, vec!["evt_3".to_string()]);
+        assert_ne!(next.idempotency_key("sub_local_1"), batch.idempotency_key("sub_local_1"));
+    }
+    
+    #[test]
+    fn test_current_period_summary_and_estimate() {
+        let sub = subscription();
+        let metered = sub.metered.clone().unwrap();
+        let mut reported = event("evt_1", 3, Utc::now() - Duration::days(2));
+        reported.report_status = UsageReportStatus::Reported;
+        let events = vec![
+            event("evt_0", 10, sub.current_period_start - Duration::days(1)), // Période précédente
+            reported,
+            event("evt_2", 1, Utc::now()),
+        ];
+        
+        let summary = usage::summarize(&sub, &metered, &events);
+        assert_eq!(summary.quantity, 4);
+        assert_eq!(summary.reported_quantity, 3);
+        assert_eq!(summary.estimated_amount, 200); // 4 × 0,50€
+    }
+    
+    #[test]
+    fn test_failed_batch_is_picked_up_by_the_reporter() {
+        let state = common::test_state();
+        let now = Utc::now();
+        let mut failed = vec![event("evt_1", 2, now)];
+        usage::take_pending(&mut failed).unwrap(); // Envoi échoué: le lot reste en cours d'envoi
+        let mut reported = vec![event("evt_2", 1, now)];
+        let batch = usage::take_pending(&mut reported).unwrap();
+        usage::finish_batch(&mut reported, &batch, "mbur_1");
+        state.usage_events.insert("sub_failed".to_string(), failed);
+        state.usage_events.insert("sub_reported".to_string(), reported);
+        state.usage_events.insert("sub_pending".to_string(), vec![event("evt_3", 1, now)]);
+        
+        let mut selected = jobs::subscriptions_with_pending_usage(&state);
+        selected.sort();
+        assert_eq!(selected, vec!["sub_failed".to_string(), "sub_pending".to_string()]);
+    }
+}
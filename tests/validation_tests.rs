@@ -0,0 +1,102 @@
+// Tests unitaires pour la validation des requêtes
+
+#[cfg(test)]
+mod tests {
+    use ruststripe::models::*;
+    use ruststripe::routes::validation::field_errors;
+    use validator::Validate;
+    
+    #[test]
+    fn test_add_to_cart_rejects_non_positive_quantity() {
+        let req = AddToCartRequest {
+            user_id: "user_1".to_string(),
+            product_id: "cap_001".to_string(),
+            variant_sku: None,
+            quantity: -2,
+        };
+        
+        let errors = field_errors(&req.validate().unwrap_err());
+        assert!(errors.contains_key("quantity"));
+        assert_eq!(errors.len(), 1);
+    }
+    
+    #[test]
+    fn test_subscription_request_requires_valid_email() {
+        let req = CreateSubscriptionRequest {
+            user_id: "user_1".to_string(),
+            plan_id: "plan_normal".to_string(),
+            email: "pas-un-email".to_string(),
+            payment_method: "pm_card_visa".to_string(),
+            interval: BillingInterval::Monthly,
+            metered: false,
+        };
+        
+        let errors = field_errors(&req.validate().unwrap_err());
+        assert!(errors.contains_key("email"));
+    }
+    
+    #[test]
+    fn test_pay_with_saved_method_amount_bounds() {
+        let mut req = PayWithSavedMethodRequest {
+            user_id: "user_1".to_string(),
+            payment_method_id: "pm_1".to_string(),
+            amount: 0,
+            description: "Achat".to_string(),
+        };
+        assert!(req.validate().is_err());
+        
+        req.amount = 5000;
+        assert!(req.validate().is_ok());
+    }
+    
+    #[test]
+    fn test_nested_errors_have_field_paths() {
+        let req = UpdateOrderRequest {
+            items: vec![
+                CartItem { product_id: "cap_001".to_string(), variant_sku: None, quantity: 1 },
+                CartItem { product_id: "cap_002".to_string(), variant_sku: None, quantity: 0 },
+            ],
+        };
+        
+        let errors = field_errors(&req.validate().unwrap_err());
+        assert!(errors.contains_key("items[1].quantity"));
+        assert!(!errors.contains_key("items[0].quantity"));
+    }
+    
+    #[test]
+    fn test_product_fields_must_not_be_blank() {
+        let req = CreateProductRequest {
+            id: "   ".to_string(),
+            name: " \t".to_string(),
+            price: 1500,
+            stock: 10,
+            description: String::new(),
+            weight_grams: 0,
+            variants: vec![ProductVariant {
+                sku: " ".to_string(),
+                size: None,
+                color: None,
+                stock: 1,
+                price: None,
+            }],
+        };
+        
+        let errors = field_errors(&req.validate().unwrap_err());
+        assert_eq!(errors["id"], vec!["L'identifiant du produit est requis".to_string()]);
+        assert_eq!(errors["name"], vec!["Le nom du produit est requis".to_string()]);
+        assert!(errors.contains_key("variants[0].sku"));
+        
+        let mut update = UpdateProductRequest {
+            name: Some("  ".to_string()),
+            price: None,
+            stock: None,
+            description: None,
+            weight_grams: None,
+            variants: None,
+        };
+        assert!(field_errors(&update.validate().unwrap_err()).contains_key("name"));
+        
+        update.name = Some(" Casquette ".to_string());
+        assert!(update.validate().is_ok());
+    }
+}